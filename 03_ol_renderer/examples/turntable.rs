@@ -0,0 +1,70 @@
+extern crate renderer;
+
+use renderer::*;
+
+const IMG_W: u32 = 320;
+const IMG_H: u32 = 240;
+const FPS: Real = 24.0;
+const DURATION: Real = 4.0;
+
+fn main() {
+    let entities: Vec<Box<Entity>> = vec![
+        Box::new(sphere::Sphere::new(
+            vec3(0.0, 0.0, 0.0),
+            0.4,
+            Box::new(|_, loc_x, loc_y, _, _| {
+                Phong::new(
+                    color3(0.1, 0.1, 0.1),
+                    color3(0.3, 0.9, 0.7),
+                    loc_x,
+                    loc_y,
+                    1.5,
+                )
+            }),
+        )),
+        Box::new(sphere::Sphere::new(
+            vec3(0.4, 0.1, 0.2),
+            0.1,
+            Box::new(|_, loc_x, loc_y, _, _| {
+                Phong::new(
+                    color3(0.1, 0.1, 0.1),
+                    color3(0.0, 1.0, 0.0),
+                    loc_x,
+                    loc_y,
+                    1.2,
+                )
+            }),
+        )),
+        Box::new(plane::Plane::new(
+            vec3(0.0, -0.4, 0.0),
+            vec3(0.0, 1.0, 0.0),
+            Box::new(|_, loc_x, loc_y, _, _| {
+                Phong::new(BLACK, color3(1.0, 0.6, 0.4), loc_x, loc_y, 1.0)
+            }),
+        )),
+    ];
+
+    let lights: Vec<Box<Light>> = vec![Box::new(PointLight::new(
+        vec3(4.0, 10.0, 4.0),
+        vec3(1.0, 1.0, 1.0),
+    ))];
+
+    let renderer = WhittedRenderer::new(entities, lights, BLACK, 5);
+
+    // One revolution around the y axis, zooming in halfway
+    let keys = (0..5)
+        .map(|i| {
+            let time = i as Real / 4.0 * DURATION;
+            let angle = i as Real / 4.0 * 2.0 * REAL_PI;
+            let eye = vec3(3.0 * angle.cos(), 1.5, 3.0 * angle.sin());
+            let fov_y = if i == 2 { 0.5 } else { 0.8 };
+            CameraKey::look_at(time, eye, ZERO_VEC3, Y_VEC3, fov_y)
+        })
+        .collect();
+    let path = CameraPath::new(keys, IMG_W as Real / IMG_H as Real, 1.0);
+
+    let mut seq = FrameSequence::new("./target/turntable_{}.png", IMG_W, IMG_H, FPS);
+    seq.set_spp(4);
+    seq.render(&renderer, &path, 0..(DURATION * FPS) as u32)
+        .unwrap();
+}
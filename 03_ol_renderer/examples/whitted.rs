@@ -5,16 +5,15 @@ use renderer::*;
 
 const IMG_W: u32 = 640;
 const IMG_H: u32 = 480;
-const CAM_W: Real = 1.0;
-const CAM_H: Real = CAM_W * (IMG_H as Real) / (IMG_W as Real);
+const FOV_Y: Real = 0.72;
 
 fn main() {
-    let camera = PerspectiveCamera::new(
+    let camera = PerspectiveCamera::with_fov(
         vec3(5.0, 3.0, 0.0),
         vec3(0.0, 0.0, 0.0),
         vec3(0.0, 1.0, 0.0),
-        CAM_W,
-        CAM_H,
+        FOV_Y,
+        IMG_W as Real / IMG_H as Real,
         1.0,
     );
 
@@ -23,39 +22,39 @@ fn main() {
             vec3(0.0, 0.0, 0.0),
             0.4,
             Box::new(|_, loc_x, loc_y, _, _| {
-                Box::new(Phong::new(
+                Phong::new(
                     color3(0.1, 0.1, 0.1),
                     color3(0.3, 0.9, 0.7),
                     loc_x,
                     loc_y,
                     1.5,
-                ))
+                )
             }),
         )),
         Box::new(sphere::Sphere::new(
             vec3(0.4, 0.1, 0.2),
             0.1,
             Box::new(|_, loc_x, loc_y, _, _| {
-                Box::new(Phong::new(
+                Phong::new(
                     color3(0.1, 0.1, 0.1),
                     color3(0.0, 1.0, 0.0),
                     loc_x,
                     loc_y,
                     1.2,
-                ))
+                )
             }),
         )),
         Box::new(sphere::Sphere::new(
             vec3(0.0, -5.0, 0.0),
             4.5,
             Box::new(|_, loc_x, loc_y, _, _| {
-                Box::new(Phong::new(
+                Phong::new(
                     color3(0.1, 0.1, 0.1),
                     color3(0.6, 0.4, 0.9),
                     loc_x,
                     loc_y,
                     1.0,
-                ))
+                )
             }),
         )),
     ];
@@ -68,9 +67,7 @@ fn main() {
     let renderer = WhittedRenderer::new(entities, lights, color3(0.0, 0.0, 0.0), 5);
 
     let img = image::ImageBuffer::from_fn(IMG_W, IMG_H, |x, y| {
-        let x = 2.0 * x as Real / (IMG_W - 1) as Real - 1.0;
-        let y = -2.0 * y as Real / (IMG_H - 1) as Real + 1.0;
-        let ray = camera.scr_to_ray(vec2(x, y));
+        let ray = camera.raster_to_ray(x, y, vec2(0.5, 0.5), IMG_W, IMG_H);
         let c = renderer.render(ray.clone()).clamp(0.0, 1.0);
         image::Rgb {
             data: [
@@ -0,0 +1,281 @@
+//! Scratch memory arena
+//!
+//! Objects living no longer than a single sample (BxDFs, sample lists, ...) are
+//! allocated by bumping a pointer in a few large chunks, and freed all at once by
+//! `Arena::reset`. After the first samples the chunks are big enough, and the
+//! global allocator is not touched anymore.
+//!
+//! Objects borrowing data shall be `Copy`, as their destructors are never run.
+//! Owned (`'static`) objects may need to be dropped, which `reset` does.
+
+use std::alloc::{self, Layout};
+use std::cell::{Cell, RefCell};
+use std::mem;
+use std::ptr;
+use std::slice;
+
+const MIN_CHUNK_SIZE: usize = 16 * 1024;
+const CHUNK_ALIGN: usize = 16;
+
+struct Chunk {
+    ptr: *mut u8,
+    size: usize,
+}
+
+impl Chunk {
+    fn new(size: usize) -> Chunk {
+        let ptr = unsafe { alloc::alloc(Self::layout(size)) };
+        if ptr.is_null() {
+            alloc::handle_alloc_error(Self::layout(size));
+        }
+        Chunk { ptr, size }
+    }
+
+    fn layout(size: usize) -> Layout {
+        Layout::from_size_align(size, CHUNK_ALIGN).unwrap()
+    }
+}
+
+impl Drop for Chunk {
+    fn drop(&mut self) {
+        unsafe { alloc::dealloc(self.ptr, Self::layout(self.size)) }
+    }
+}
+
+/// Bump allocator freeing everything at once.
+///
+/// Allocated objects are borrowed from the arena, so `reset` can only be called
+/// once none of them is in use. Values with drop glue (e.g. owning a `Vec` or an
+/// `Rc`) are allocated by `alloc_owned` or `alloc_iter`, and dropped by `reset`.
+///
+/// ```
+/// use renderer::arena::Arena;
+///
+/// let mut arena = Arena::new();
+/// for i in 0..3 {
+///     let x = arena.alloc(i);
+///     let v = arena.alloc_iter((0..10).map(|j| j * *x));
+///     assert_eq!(v[9], 9 * i);
+///     arena.reset();
+/// }
+/// ```
+pub struct Arena {
+    /// The last chunk is the one being filled
+    chunks: RefCell<Vec<Chunk>>,
+    next: Cell<*mut u8>,
+    end: Cell<*mut u8>,
+    /// Objects to drop on reset, in allocation order
+    drops: RefCell<Vec<Owned>>,
+}
+
+/// Slice of `len` owned objects and their drop glue
+struct Owned {
+    ptr: *mut u8,
+    len: usize,
+    drop: unsafe fn(*mut u8, usize),
+}
+
+unsafe fn drop_glue<T>(ptr: *mut u8, len: usize) {
+    ptr::drop_in_place(ptr::slice_from_raw_parts_mut(ptr as *mut T, len))
+}
+
+impl Default for Arena {
+    fn default() -> Arena {
+        Arena::new()
+    }
+}
+
+impl Arena {
+    pub fn new() -> Arena {
+        Arena {
+            chunks: RefCell::new(Vec::new()),
+            next: Cell::new(ptr::null_mut()),
+            end: Cell::new(ptr::null_mut()),
+            drops: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Total size of the chunks
+    pub fn capacity(&self) -> usize {
+        self.chunks.borrow().iter().map(|c| c.size).sum()
+    }
+
+    /// Uninitialized memory for `len` values of `T`
+    fn alloc_raw<T>(&self, len: usize) -> *mut T {
+        let size = mem::size_of::<T>().checked_mul(len).unwrap();
+        if size == 0 {
+            return ptr::NonNull::dangling().as_ptr();
+        }
+        let align = mem::align_of::<T>();
+
+        let next = self.next.get();
+        if !next.is_null() {
+            let pad = next.align_offset(align);
+            if pad.saturating_add(size) <= self.end.get() as usize - next as usize {
+                let ret = unsafe { next.add(pad) };
+                self.next.set(unsafe { ret.add(size) });
+                return ret as *mut T;
+            }
+        }
+
+        let last_size = self.chunks.borrow().last().map_or(0, |c| c.size);
+        let chunk = Chunk::new(MIN_CHUNK_SIZE.max(2 * last_size).max(size + align));
+        let (base, chunk_size) = (chunk.ptr, chunk.size);
+        self.chunks.borrow_mut().push(chunk);
+        unsafe {
+            let ret = base.add(base.align_offset(align));
+            self.next.set(ret.add(size));
+            self.end.set(base.add(chunk_size));
+            ret as *mut T
+        }
+    }
+
+    pub fn alloc<T: Copy>(&self, v: T) -> &T {
+        let ptr = self.alloc_raw::<T>(1);
+        unsafe {
+            ptr::write(ptr, v);
+            &*ptr
+        }
+    }
+
+    /// Allocate a value which is dropped by `reset`. Unlike `alloc`, the value
+    /// cannot borrow anything, since what it borrows might be gone by then.
+    pub fn alloc_owned<T: 'static>(&self, v: T) -> &T {
+        let ptr = self.alloc_raw::<T>(1);
+        unsafe { ptr::write(ptr, v) };
+        self.own(ptr, 1);
+        unsafe { &*ptr }
+    }
+
+    fn own<T: 'static>(&self, ptr: *mut T, len: usize) {
+        if mem::needs_drop::<T>() && len > 0 {
+            self.drops.borrow_mut().push(Owned {
+                ptr: ptr as *mut u8,
+                len,
+                drop: drop_glue::<T>,
+            });
+        }
+    }
+
+    /// Collect `iter` into a slice of owned objects, dropped by `reset`. The iterator
+    /// shall have an upper bound of its length, and may allocate in the arena itself.
+    pub fn alloc_iter<T, I>(&self, iter: I) -> &[T]
+    where
+        T: 'static,
+        I: IntoIterator<Item = T>,
+    {
+        let iter = iter.into_iter();
+        let cap = iter
+            .size_hint()
+            .1
+            .expect("Arena::alloc_iter: unbounded iterator");
+        let ptr = self.alloc_raw::<T>(cap);
+        let mut len = 0;
+        for v in iter.take(cap) {
+            unsafe { ptr::write(ptr.add(len), v) };
+            len += 1;
+        }
+        self.own(ptr, len);
+        unsafe { slice::from_raw_parts(ptr, len) }
+    }
+
+    fn run_drops(&mut self) {
+        for o in self.drops.get_mut().drain(..).rev() {
+            unsafe { (o.drop)(o.ptr, o.len) };
+        }
+    }
+
+    /// Drop the owned objects, and make the memory of all allocated objects
+    /// available again. Chunks are merged into one, so that the same workload
+    /// fits in it next time.
+    pub fn reset(&mut self) {
+        self.run_drops();
+        let chunks = self.chunks.get_mut();
+        if chunks.len() > 1 {
+            let total = chunks.iter().map(|c| c.size).sum();
+            chunks.clear();
+            chunks.push(Chunk::new(total));
+        }
+        match chunks.first() {
+            Some(c) => {
+                self.next.set(c.ptr);
+                self.end.set(unsafe { c.ptr.add(c.size) });
+            }
+            None => {
+                self.next.set(ptr::null_mut());
+                self.end.set(ptr::null_mut());
+            }
+        }
+    }
+}
+
+impl Drop for Arena {
+    fn drop(&mut self) {
+        self.run_drops();
+    }
+}
+
+thread_local! {
+    static THREAD_ARENA: RefCell<Arena> = RefCell::new(Arena::new());
+}
+
+/// Call `f` with the arena of the current thread, which is reset afterwards.
+/// Calls shall not be nested.
+pub fn with_thread_arena<F, R>(f: F) -> R
+where
+    F: FnOnce(&Arena) -> R,
+{
+    THREAD_ARENA.with(|arena| {
+        let mut arena = arena.borrow_mut();
+        let ret = f(&arena);
+        arena.reset();
+        ret
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn drop_owned() {
+        let counter = Rc::new(());
+        let mut arena = Arena::new();
+        for _ in 0..3 {
+            let v = arena.alloc_owned(vec![counter.clone(); 4]);
+            assert_eq!(v.len(), 4);
+            assert_eq!(Rc::strong_count(&counter), 5);
+            arena.reset();
+            assert_eq!(Rc::strong_count(&counter), 1);
+        }
+        arena.alloc_iter((0..3).map(|_| counter.clone()));
+        drop(arena);
+        assert_eq!(Rc::strong_count(&counter), 1);
+    }
+
+    #[test]
+    fn growth_and_reuse() {
+        #[repr(align(64))]
+        #[derive(Clone, Copy)]
+        struct Aligned(u8);
+
+        let mut arena = Arena::new();
+        for round in 0..3 {
+            for i in 0..10000 {
+                let a = arena.alloc(Aligned(i as u8));
+                assert_eq!(a as *const Aligned as usize % 64, 0);
+                assert_eq!(a.0, i as u8);
+                // Nested allocation while filling a slice
+                let v = arena.alloc_iter((0..4).filter(|j| j % 2 == 0).map(|j| *arena.alloc(j)));
+                assert_eq!(v, &[0, 2]);
+            }
+            if round == 0 {
+                assert!(arena.chunks.borrow().len() > 1);
+            } else {
+                assert_eq!(arena.chunks.borrow().len(), 1);
+            }
+            arena.reset();
+        }
+    }
+}
@@ -0,0 +1,161 @@
+//! Keyframed camera paths
+
+use camera::*;
+use math::*;
+
+/// Pose and field of view of a perspective camera at a given time.
+///
+/// The camera looks at -z with +y up in the local space given by `rotation`.
+#[derive(Clone, Copy, Debug)]
+pub struct CameraKey {
+    pub time: Real,
+    pub eye: Vec3f,
+    pub rotation: Quatf,
+    /// Vertical field of view in radians
+    pub fov_y: Real,
+}
+
+impl CameraKey {
+    pub fn new(time: Real, eye: Vec3f, rotation: Quatf, fov_y: Real) -> CameraKey {
+        CameraKey {
+            time,
+            eye,
+            rotation: rotation.normalize(),
+            fov_y,
+        }
+    }
+
+    /// Key of a camera at `eye` looking at `look_at`, oriented like `PerspectiveCamera::new`
+    pub fn look_at(
+        time: Real,
+        eye: Vec3f,
+        look_at: Vec3f,
+        up_dir: Vec3f,
+        fov_y: Real,
+    ) -> CameraKey {
+        let dir = (look_at - eye).normalize();
+        let up = (up_dir - dot(up_dir, dir) * dir).normalize();
+        let right = dir.cross(up).normalize();
+        let rotation = Quatf::from(Mat3f::from_cols(right, up, -dir));
+        CameraKey::new(time, eye, rotation, fov_y)
+    }
+
+    pub fn get_dir(&self) -> Vec3f {
+        self.rotation * -Z_VEC3
+    }
+
+    pub fn get_up(&self) -> Vec3f {
+        self.rotation * Y_VEC3
+    }
+}
+
+/// Perspective camera moving along keyframes.
+///
+/// The eye position, orientation and field of view are interpolated with
+/// Catmull-Rom splines, the orientation component-wise before normalization.
+/// Before the first (after the last) key the camera stands still.
+pub struct CameraPath {
+    keys: Vec<CameraKey>,
+    aspect: Real,
+    near_dis: Real,
+}
+
+impl CameraPath {
+    /// `aspect` and `near_dis` are those of `PerspectiveCamera::with_fov`
+    pub fn new(mut keys: Vec<CameraKey>, aspect: Real, near_dis: Real) -> CameraPath {
+        assert!(!keys.is_empty());
+        keys.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        // Neighboring rotations on the same hemisphere, so that the shorter arcs are taken
+        for i in 1..keys.len() {
+            if keys[i - 1].rotation.dot(keys[i].rotation) < 0.0 {
+                keys[i].rotation = -keys[i].rotation;
+            }
+        }
+        CameraPath {
+            keys,
+            aspect,
+            near_dis,
+        }
+    }
+
+    pub fn get_keys(&self) -> &[CameraKey] {
+        &self.keys
+    }
+
+    /// Time of the first and the last key
+    pub fn get_time_range(&self) -> (Real, Real) {
+        (self.keys[0].time, self.keys[self.keys.len() - 1].time)
+    }
+
+    /// Interpolated key at `time`
+    pub fn key_at(&self, time: Real) -> CameraKey {
+        let times: Vec<Real> = self.keys.iter().map(|k| k.time).collect();
+        let eyes: Vec<Vec3f> = self.keys.iter().map(|k| k.eye).collect();
+        let rotations: Vec<Quatf> = self.keys.iter().map(|k| k.rotation).collect();
+        let fovs: Vec<Real> = self.keys.iter().map(|k| k.fov_y).collect();
+        CameraKey::new(
+            time,
+            catmull_rom(&times, &eyes, time),
+            catmull_rom(&times, &rotations, time),
+            catmull_rom(&times, &fovs, time),
+        )
+    }
+
+    /// Camera at `time`, casting all its rays at that moment
+    pub fn camera_at(&self, time: Real) -> PerspectiveCamera {
+        let k = self.key_at(time);
+        let mut camera = PerspectiveCamera::with_fov(
+            k.eye,
+            k.eye + k.get_dir(),
+            k.get_up(),
+            k.fov_y,
+            self.aspect,
+            self.near_dis,
+        );
+        camera.set_shutter(time, time);
+        camera
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn look_at_keys() {
+        let eye = vec3(1.0, 2.0, 3.0);
+        let k = CameraKey::look_at(0.0, eye, vec3(4.0, 2.0, 3.0), vec3(0.5, 1.0, 0.0), 1.0);
+        assert!(k.get_dir().relative_eq(&X_VEC3, 1e-9, 1e-9));
+        assert!(k.get_up().relative_eq(&Y_VEC3, 1e-9, 1e-9));
+
+        let path = CameraPath::new(
+            vec![
+                CameraKey::look_at(2.0, eye, eye - Z_VEC3, Y_VEC3, 0.5),
+                k,
+                CameraKey::look_at(1.0, eye, eye + Y_VEC3, Z_VEC3, 0.8),
+            ],
+            1.0,
+            1.0,
+        );
+        assert_eq!(path.get_time_range(), (0.0, 2.0));
+        let mid = path.key_at(1.0);
+        assert!(mid.get_dir().relative_eq(&Y_VEC3, 1e-9, 1e-9));
+        assert!(mid.get_up().relative_eq(&Z_VEC3, 1e-9, 1e-9));
+        assert!((mid.fov_y - 0.8).abs() < 1e-9);
+
+        // Same view as a still camera built from the key
+        let cam = path.camera_at(2.0);
+        let still = PerspectiveCamera::with_fov(eye, eye - Z_VEC3, Y_VEC3, 0.5, 1.0, 1.0);
+        let (a, b) = (
+            cam.scr_to_ray(vec2(0.3, 0.6)),
+            still.scr_to_ray(vec2(0.3, 0.6)),
+        );
+        assert!(a.d.relative_eq(&b.d, 1e-9, 1e-9));
+        assert_eq!(a.time, 2.0);
+
+        // Smooth in between
+        let q = path.key_at(0.5);
+        assert!((q.rotation.magnitude() - 1.0).abs() < 1e-9);
+        assert!(q.get_dir().x > 0.0 && q.get_dir().y > 0.0);
+    }
+}
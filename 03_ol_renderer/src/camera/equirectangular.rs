@@ -0,0 +1,75 @@
+//! Equirectangular (latitude-longitude) panoramic camera model
+
+use camera::*;
+use math::*;
+
+/// Camera covering the full sphere around the eye.
+///
+/// `scr_point.x` in `[-1, 1]` maps linearly to the longitude in `[-pi, pi]`, measured
+/// from the view direction towards the right, and `scr_point.y` to the latitude in
+/// `[-pi / 2, pi / 2]`. The screen should thus have an aspect ratio of 2:1.
+pub struct EquirectangularCamera {
+    eye: Vec3f,
+    dir: Vec3f,
+    right: Vec3f,
+    up: Vec3f,
+    shutter: Shutter,
+}
+
+impl Camera for EquirectangularCamera {
+    fn scr_to_ray(&self, scr_point: Vec2f) -> Ray {
+        Ray::with_time(
+            self.eye,
+            self.scr_to_dir(scr_point),
+            self.shutter.sample_time(),
+        )
+    }
+}
+
+impl EquirectangularCamera {
+    /// `look_at` is at the centre of the image, and `up_dir` at its top edge
+    pub fn new(eye: Vec3f, look_at: Vec3f, up_dir: Vec3f) -> EquirectangularCamera {
+        let dir = (look_at - eye).normalize();
+        let up = (up_dir - dot(up_dir, dir) * dir).normalize();
+        EquirectangularCamera {
+            eye,
+            dir,
+            right: dir.cross(up).normalize(),
+            up,
+            shutter: Shutter::default(),
+        }
+    }
+
+    /// Rays are cast at times uniformly distributed in `[open, close]`
+    pub fn set_shutter(&mut self, open: Real, close: Real) -> &mut Self {
+        self.shutter = Shutter::new(open, close);
+        self
+    }
+
+    /// Normalized direction seen at `scr_point`
+    pub fn scr_to_dir(&self, scr_point: Vec2f) -> Vec3f {
+        let phi = scr_point.x * REAL_PI;
+        let theta = scr_point.y * REAL_PI / 2.0;
+        let horizontal = phi.cos() * self.dir + phi.sin() * self.right;
+        theta.cos() * horizontal + theta.sin() * self.up
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn directions() {
+        let cam = EquirectangularCamera::new(ZERO_VEC3, -Z_VEC3, Y_VEC3);
+        let check = |x: Real, y: Real, d: Vec3f| {
+            assert!(cam.scr_to_ray(vec2(x, y)).d.relative_eq(&d, 1e-9, 1e-9));
+        };
+        check(0.0, 0.0, -Z_VEC3);
+        check(0.5, 0.0, X_VEC3);
+        check(-0.5, 0.0, -X_VEC3);
+        check(1.0, 0.0, Z_VEC3);
+        check(0.3, 1.0, Y_VEC3);
+        check(0.0, -1.0, -Y_VEC3);
+    }
+}
@@ -0,0 +1,121 @@
+//! Fisheye camera model
+
+use camera::*;
+use math::*;
+
+/// How the angle from the view direction relates to the distance from the image centre
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FisheyeMapping {
+    /// The distance is proportional to the angle
+    Equidistant,
+    /// The distance is proportional to `sin(angle / 2)`, preserving solid angles
+    Equisolid,
+}
+
+/// Camera projecting directions within `fov / 2` of the view direction onto the
+/// unit circle of the screen.
+///
+/// A field of view of pi (the default) covers the hemisphere in front of the eye,
+/// and 2 * pi the full sphere. Points of the screen outside the unit circle see
+/// nothing: their rays have an empty range, so that renderers return the background.
+pub struct FisheyeCamera {
+    eye: Vec3f,
+    dir: Vec3f,
+    right: Vec3f,
+    up: Vec3f,
+    mapping: FisheyeMapping,
+    fov: Real,
+    shutter: Shutter,
+}
+
+impl Camera for FisheyeCamera {
+    fn scr_to_ray(&self, scr_point: Vec2f) -> Ray {
+        let time = self.shutter.sample_time();
+        match self.scr_to_dir(scr_point) {
+            Some(d) => Ray::with_time(self.eye, d, time),
+            None => Ray::with_time(self.eye, self.dir, time).with_range(0.0, 0.0),
+        }
+    }
+}
+
+impl FisheyeCamera {
+    pub fn new(
+        eye: Vec3f,
+        look_at: Vec3f,
+        up_dir: Vec3f,
+        mapping: FisheyeMapping,
+    ) -> FisheyeCamera {
+        let dir = (look_at - eye).normalize();
+        let up = (up_dir - dot(up_dir, dir) * dir).normalize();
+        FisheyeCamera {
+            eye,
+            dir,
+            right: dir.cross(up).normalize(),
+            up,
+            mapping,
+            fov: REAL_PI,
+            shutter: Shutter::default(),
+        }
+    }
+
+    /// Field of view (in radians) across the image circle, in `(0, 2 * pi]`
+    pub fn set_fov(&mut self, fov: Real) -> &mut Self {
+        assert!(fov > 0.0 && fov <= 2.0 * REAL_PI);
+        self.fov = fov;
+        self
+    }
+
+    /// Rays are cast at times uniformly distributed in `[open, close]`
+    pub fn set_shutter(&mut self, open: Real, close: Real) -> &mut Self {
+        self.shutter = Shutter::new(open, close);
+        self
+    }
+
+    pub fn get_fov(&self) -> Real {
+        self.fov
+    }
+
+    /// Normalized direction seen at `scr_point`, or `None` outside the image circle
+    pub fn scr_to_dir(&self, scr_point: Vec2f) -> Option<Vec3f> {
+        let r = scr_point.magnitude();
+        if r > 1.0 {
+            return None;
+        }
+        let theta = match self.mapping {
+            FisheyeMapping::Equidistant => r * self.fov / 2.0,
+            FisheyeMapping::Equisolid => 2.0 * (r * (self.fov / 4.0).sin()).min(1.0).asin(),
+        };
+        let phi = scr_point.y.atan2(scr_point.x);
+        let side = phi.cos() * self.right + phi.sin() * self.up;
+        Some(theta.cos() * self.dir + theta.sin() * side)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mappings() {
+        let mut cam = FisheyeCamera::new(ZERO_VEC3, -Z_VEC3, Y_VEC3, FisheyeMapping::Equidistant);
+        let dir = |cam: &FisheyeCamera, x: Real, y: Real| cam.scr_to_dir(vec2(x, y)).unwrap();
+        assert!(dir(&cam, 0.0, 0.0).relative_eq(&-Z_VEC3, 1e-9, 1e-9));
+        assert!(dir(&cam, 1.0, 0.0).relative_eq(&X_VEC3, 1e-9, 1e-9));
+        assert!(dir(&cam, 0.0, -1.0).relative_eq(&-Y_VEC3, 1e-9, 1e-9));
+        let half = dir(&cam, 0.0, 0.5);
+        assert!((dot(half, -Z_VEC3).acos() - REAL_PI / 4.0).abs() < 1e-9);
+        assert!(cam.scr_to_dir(vec2(0.8, 0.8)).is_none());
+        let r = cam.scr_to_ray(vec2(0.8, 0.8));
+        assert!(!r.is_in_range(r.t_min + 1.0));
+
+        cam.set_fov(2.0 * REAL_PI);
+        assert!(dir(&cam, 0.0, 1.0).relative_eq(&Z_VEC3, 1e-9, 1e-9));
+
+        // Equal areas of the screen cover equal solid angles
+        let mut cam = FisheyeCamera::new(ZERO_VEC3, -Z_VEC3, Y_VEC3, FisheyeMapping::Equisolid);
+        cam.set_fov(2.0 * REAL_PI);
+        let inner = dir(&cam, (0.5 as Real).sqrt(), 0.0);
+        assert!(dot(inner, -Z_VEC3).abs() < 1e-9);
+        assert!(dir(&cam, -1.0, 0.0).relative_eq(&Z_VEC3, 1e-9, 1e-9));
+    }
+}
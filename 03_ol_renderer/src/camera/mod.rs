@@ -1,13 +1,64 @@
 //! Camera model
 
+pub mod animated;
+pub mod equirectangular;
+pub mod fisheye;
+pub mod orthographic;
 pub mod perspective;
+pub mod thin_lens;
 
 pub mod prelude {
+    extern crate rand;
+
+    pub use super::animated::*;
+    pub use super::equirectangular::*;
+    pub use super::fisheye::*;
+    pub use super::orthographic::*;
     pub use super::perspective::*;
+    pub use super::thin_lens::*;
     use math::*;
 
     pub trait Camera {
+        /// Ray through `scr_point` in `[-1, 1]^2`, cast at a time sampled over the shutter interval
         fn scr_to_ray(&self, scr_point: Vec2f) -> Ray;
+
+        /// Ray through the point `offset` (in `[0, 1)^2`, with `vec2(0.5, 0.5)` at the centre)
+        /// of pixel `(x, y)` in an `img_w` x `img_h` image, whose row 0 is at the top
+        fn raster_to_ray(&self, x: u32, y: u32, offset: Vec2f, img_w: u32, img_h: u32) -> Ray {
+            let raster = vec2(x as Real + offset.x, y as Real + offset.y);
+            self.scr_to_ray(raster_to_scr(raster, img_w, img_h))
+        }
+    }
+
+    /// Map a point in raster space, `[0, img_w] x [0, img_h]` with y going down,
+    /// to the screen space `[-1, 1]^2` with y going up
+    pub fn raster_to_scr(raster: Vec2f, img_w: u32, img_h: u32) -> Vec2f {
+        vec2(
+            2.0 * raster.x / img_w as Real - 1.0,
+            1.0 - 2.0 * raster.y / img_h as Real,
+        )
+    }
+
+    /// Time interval over which a camera casts rays
+    #[derive(Clone, Copy, Debug, Default, PartialEq)]
+    pub struct Shutter {
+        pub open: Real,
+        pub close: Real,
+    }
+
+    impl Shutter {
+        pub fn new(open: Real, close: Real) -> Shutter {
+            assert!(open <= close);
+            Shutter { open, close }
+        }
+
+        /// Uniformly distributed in `[open, close]`
+        pub fn sample_time(&self) -> Real {
+            if self.open == self.close {
+                return self.open;
+            }
+            self.open + rand::random::<Real>() * (self.close - self.open)
+        }
     }
 }
 
@@ -0,0 +1,64 @@
+//! Orthographic (parallel projection) camera model
+
+use camera::*;
+use math::*;
+
+/// Camera casting parallel rays from a rectangle centered at the eye
+pub struct OrthographicCamera {
+    dir: Vec3f,
+    view_o: Vec3f,
+    view_x: Vec3f,
+    view_y: Vec3f,
+    shutter: Shutter,
+}
+
+impl Camera for OrthographicCamera {
+    fn scr_to_ray(&self, scr_point: Vec2f) -> Ray {
+        let pnt = self.view_o + scr_point.x * self.view_x + scr_point.y * self.view_y;
+        Ray::with_time(pnt, self.dir, self.shutter.sample_time())
+    }
+}
+
+impl OrthographicCamera {
+    /// `view_width` and `view_height` are the size of the view volume's cross section.
+    /// Rays start at the plane through `eye`, so everything behind it is clipped.
+    pub fn new(
+        eye: Vec3f,
+        look_at: Vec3f,
+        up_dir: Vec3f,
+        view_width: Real,
+        view_height: Real,
+    ) -> OrthographicCamera {
+        let dir = (look_at - eye).normalize();
+        let view_y = view_height / 2.0 * (up_dir - dot(up_dir, dir) * dir).normalize();
+        OrthographicCamera {
+            dir,
+            view_o: eye,
+            view_x: view_width / 2.0 * (dir.cross(view_y).normalize()),
+            view_y,
+            shutter: Shutter::default(),
+        }
+    }
+
+    /// Rays are cast at times uniformly distributed in `[open, close]`
+    pub fn set_shutter(&mut self, open: Real, close: Real) -> &mut Self {
+        self.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parallel_rays() {
+        let cam = OrthographicCamera::new(vec3(0.0, 0.0, 5.0), ZERO_VEC3, Y_VEC3, 4.0, 2.0);
+        let center = cam.scr_to_ray(vec2(0.0, 0.0));
+        let corner = cam.scr_to_ray(vec2(1.0, 1.0));
+        assert!(center.p.relative_eq(&vec3(0.0, 0.0, 5.0), 1e-9, 1e-9));
+        assert!(corner.p.relative_eq(&vec3(2.0, 1.0, 5.0), 1e-9, 1e-9));
+        assert!(center.d.relative_eq(&-Z_VEC3, 1e-9, 1e-9));
+        assert!(corner.d.relative_eq(&-Z_VEC3, 1e-9, 1e-9));
+    }
+}
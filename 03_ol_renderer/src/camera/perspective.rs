@@ -9,12 +9,13 @@ pub struct PerspectiveCamera {
     scr_o: Vec3f,
     scr_x: Vec3f,
     scr_y: Vec3f,
+    shutter: Shutter,
 }
 
 impl Camera for PerspectiveCamera {
     fn scr_to_ray(&self, scr_point: Vec2f) -> Ray {
         let pnt = self.scr_o + scr_point.x * self.scr_x + scr_point.y * self.scr_y;
-        Ray::new(pnt, (pnt - self.eye).normalize())
+        Ray::with_time(pnt, (pnt - self.eye).normalize(), self.shutter.sample_time())
     }
 }
 
@@ -34,6 +35,49 @@ impl PerspectiveCamera {
             scr_o: eye + near_dis * dir,
             scr_x: scr_width / 2.0 * (dir.cross(scr_y).normalize()),
             scr_y,
+            shutter: Shutter::default(),
         }
     }
+
+    /// Camera with vertical field of view `fov_y` (in radians) and screen width to
+    /// height ratio `aspect`. Rays start at the screen `near_dis` in front of the eye.
+    pub fn with_fov(
+        eye: Vec3f,
+        look_at: Vec3f,
+        up_dir: Vec3f,
+        fov_y: Real,
+        aspect: Real,
+        near_dis: Real,
+    ) -> PerspectiveCamera {
+        let scr_height = 2.0 * near_dis * (0.5 * fov_y).tan();
+        PerspectiveCamera::new(eye, look_at, up_dir, scr_height * aspect, scr_height, near_dis)
+    }
+
+    /// Rays are cast at times uniformly distributed in `[open, close]`
+    pub fn set_shutter(&mut self, open: Real, close: Real) -> &mut Self {
+        self.shutter = Shutter::new(open, close);
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fov_and_raster() {
+        let fov_y = REAL_PI / 2.0;
+        let cam = PerspectiveCamera::with_fov(ZERO_VEC3, -Z_VEC3, Y_VEC3, fov_y, 2.0, 0.1);
+
+        // Edges of the image are at half the field of view
+        let top = cam.raster_to_ray(4, 0, vec2(0.0, 0.0), 8, 4);
+        assert!(top.d.relative_eq(&vec3(0.0, 1.0, -1.0).normalize(), 1e-9, 1e-9));
+        let right = cam.raster_to_ray(7, 1, vec2(1.0, 1.0), 8, 4);
+        assert!(right.d.relative_eq(&vec3(2.0, 0.0, -1.0).normalize(), 1e-9, 1e-9));
+
+        // Pixel centres are symmetric about the view direction
+        let a = cam.raster_to_ray(0, 0, vec2(0.5, 0.5), 8, 4).d;
+        let b = cam.raster_to_ray(7, 3, vec2(0.5, 0.5), 8, 4).d;
+        assert!(vec3(-a.x, -a.y, a.z).relative_eq(&b, 1e-9, 1e-9));
+    }
 }
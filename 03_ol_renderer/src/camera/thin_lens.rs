@@ -0,0 +1,215 @@
+//! Thin lens camera model with depth of field
+
+extern crate rand;
+
+use camera::*;
+use math::*;
+
+/// Shape of the lens aperture
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Aperture {
+    Circle,
+    /// Regular polygon with `blades` vertices, the first one at angle `rotation`
+    /// (in radians) from the camera's right direction
+    Polygon {
+        blades: u32,
+        rotation: Real,
+    },
+}
+
+impl Aperture {
+    /// Map `u` in `[0, 1)^2` uniformly onto the aperture, scaled into the unit circle
+    pub fn sample(&self, u: Vec2f) -> Vec2f {
+        match *self {
+            Aperture::Circle => disk_concentric(u),
+            Aperture::Polygon { blades, rotation } => polygon_uniform(blades, rotation, u),
+        }
+    }
+}
+
+/// Camera with a thin lens focusing at a plane `focus_dis` in front of the eye.
+///
+/// The screen is placed like that of `PerspectiveCamera`, and rays are clipped
+/// by the plane containing it. With a zero lens radius (the default), the camera
+/// is a pinhole one.
+pub struct ThinLensCamera {
+    eye: Vec3f,
+    dir: Vec3f,
+    right: Vec3f,
+    up: Vec3f,
+    scr_o: Vec3f,
+    scr_x: Vec3f,
+    scr_y: Vec3f,
+    near_dis: Real,
+    lens_radius: Real,
+    focus_dis: Real,
+    aperture: Aperture,
+    shutter: Shutter,
+}
+
+impl Camera for ThinLensCamera {
+    fn scr_to_ray(&self, scr_point: Vec2f) -> Ray {
+        let lens_sample = vec2(rand::random::<Real>(), rand::random::<Real>());
+        self.scr_lens_to_ray(scr_point, lens_sample)
+    }
+}
+
+impl ThinLensCamera {
+    /// Camera focusing at `look_at`. See `PerspectiveCamera::new` for the arguments.
+    pub fn new(
+        eye: Vec3f,
+        look_at: Vec3f,
+        up_dir: Vec3f,
+        scr_width: Real,
+        scr_height: Real,
+        near_dis: Real,
+    ) -> ThinLensCamera {
+        let dir = (look_at - eye).normalize();
+        let up = (up_dir - dot(up_dir, dir) * dir).normalize();
+        let right = dir.cross(up).normalize();
+        ThinLensCamera {
+            eye,
+            dir,
+            right,
+            up,
+            scr_o: eye + near_dis * dir,
+            scr_x: scr_width / 2.0 * right,
+            scr_y: scr_height / 2.0 * up,
+            near_dis,
+            lens_radius: 0.0,
+            focus_dis: (look_at - eye).magnitude(),
+            aperture: Aperture::Circle,
+            shutter: Shutter::default(),
+        }
+    }
+
+    /// Camera focusing at `look_at`. See `PerspectiveCamera::with_fov` for the arguments.
+    pub fn with_fov(
+        eye: Vec3f,
+        look_at: Vec3f,
+        up_dir: Vec3f,
+        fov_y: Real,
+        aspect: Real,
+        near_dis: Real,
+    ) -> ThinLensCamera {
+        let scr_height = 2.0 * near_dis * (0.5 * fov_y).tan();
+        ThinLensCamera::new(eye, look_at, up_dir, scr_height * aspect, scr_height, near_dis)
+    }
+
+    /// Points at `focus_dis` in front of the eye are in focus
+    pub fn set_lens(&mut self, lens_radius: Real, focus_dis: Real) -> &mut Self {
+        assert!(lens_radius >= 0.0 && focus_dis > 0.0);
+        self.lens_radius = lens_radius;
+        self.focus_dis = focus_dis;
+        self
+    }
+
+    pub fn set_aperture(&mut self, aperture: Aperture) -> &mut Self {
+        self.aperture = aperture;
+        self
+    }
+
+    /// Rays are cast at times uniformly distributed in `[open, close]`
+    pub fn set_shutter(&mut self, open: Real, close: Real) -> &mut Self {
+        self.shutter = Shutter::new(open, close);
+        self
+    }
+
+    pub fn get_lens_radius(&self) -> Real {
+        self.lens_radius
+    }
+
+    pub fn get_focus_dis(&self) -> Real {
+        self.focus_dis
+    }
+
+    pub fn get_aperture(&self) -> Aperture {
+        self.aperture
+    }
+
+    /// Ray through `scr_point` in `[-1, 1]^2` from the point of the lens
+    /// given by `lens_sample` in `[0, 1)^2`
+    pub fn scr_lens_to_ray(&self, scr_point: Vec2f, lens_sample: Vec2f) -> Ray {
+        let pnt = self.scr_o + scr_point.x * self.scr_x + scr_point.y * self.scr_y;
+        let pinhole_dir = (pnt - self.eye).normalize();
+        let time = self.shutter.sample_time();
+        if self.lens_radius == 0.0 {
+            return Ray::with_time(pnt, pinhole_dir, time);
+        }
+
+        let focus = self.eye + self.focus_dis / dot(pinhole_dir, self.dir) * pinhole_dir;
+        let lens = self.lens_radius * self.aperture.sample(lens_sample);
+        let origin = self.eye + lens.x * self.right + lens.y * self.up;
+        let d = (focus - origin).normalize();
+        Ray::with_time(origin, d, time).with_range(self.near_dis / dot(d, self.dir), REAL_MAX)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn camera() -> ThinLensCamera {
+        ThinLensCamera::new(vec3(0.0, 0.0, 5.0), ZERO_VEC3, Y_VEC3, 0.4, 0.3, 1.0)
+    }
+
+    #[test]
+    fn pinhole() {
+        let cam = camera();
+        let pinhole = PerspectiveCamera::new(vec3(0.0, 0.0, 5.0), ZERO_VEC3, Y_VEC3, 0.4, 0.3, 1.0);
+        let scr = vec2(0.3, -0.7);
+        let (a, b) = (
+            cam.scr_lens_to_ray(scr, vec2(0.1, 0.9)),
+            pinhole.scr_to_ray(scr),
+        );
+        assert!(a.p.relative_eq(&b.p, 1e-9, 1e-9));
+        assert!(a.d.relative_eq(&b.d, 1e-9, 1e-9));
+    }
+
+    #[test]
+    fn focus() {
+        let mut cam = camera();
+        cam.set_lens(0.5, 3.0).set_aperture(Aperture::Polygon {
+            blades: 6,
+            rotation: 0.3,
+        });
+        let scr = vec2(-0.4, 0.2);
+        let rays: Vec<Ray> = [
+            vec2(0.0, 0.0),
+            vec2(0.3, 0.8),
+            vec2(0.9, 0.1),
+            vec2(0.5, 0.5),
+        ]
+        .iter()
+        .map(|&u| cam.scr_lens_to_ray(scr, u))
+        .collect();
+
+        // All rays meet on the focus plane z = 2, and start on the lens
+        let on_focus = |r: &Ray| r.t_to_point((2.0 - r.p.z) / r.d.z);
+        for r in &rays {
+            assert!(on_focus(r).relative_eq(&on_focus(&rays[0]), 1e-9, 1e-9));
+            assert!((r.p.z - 5.0).abs() < 1e-9);
+            assert!(vec2(r.p.x, r.p.y).magnitude() <= 0.5 + 1e-9);
+            assert!(r.t_to_point(r.t_min).z.relative_eq(&4.0, 1e-9, 1e-9));
+        }
+        assert!(rays[1].p.relative_ne(&rays[2].p, 1e-9, 1e-9));
+    }
+
+    #[test]
+    fn polygon_aperture() {
+        // Inside the hexagon: not farther than the apothem along any edge normal
+        let aperture = Aperture::Polygon {
+            blades: 6,
+            rotation: 0.0,
+        };
+        let apothem = (REAL_PI / 6.0).cos();
+        for i in 0..100 {
+            let u = vec2((i as Real * 0.618).fract(), (i as Real * 0.377).fract());
+            let p = aperture.sample(u);
+            for k in 0..6 {
+                let a = REAL_PI / 6.0 + k as Real * REAL_PI / 3.0;
+                assert!(dot(p, vec2(a.cos(), a.sin())) <= apothem + 1e-9);
+            }
+        }
+    }
+}
@@ -0,0 +1,420 @@
+//! Bounding volume hierarchy
+//!
+//! Built top-down with the bucketed surface area heuristic. See Wald, I. (2007).
+//! On fast construction of SAH-based bounding volume hierarchies.
+//! IEEE Symposium on Interactive Ray Tracing, 33-40.
+
+use entity::*;
+use math::{model::AABB, *};
+
+const BUCKET_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+/// Inline capacity of the traversal stack, enough for balanced trees
+const STACK_CAPACITY: usize = 64;
+
+/// Cost of visiting an interior node, relative to one primitive intersection
+const TRAVERSAL_COST: Real = 0.125;
+
+#[derive(Clone, Copy)]
+enum BvhNodeKind {
+    Leaf { start: usize, count: usize },
+    /// The first child always directly follows its parent
+    Interior { second: usize, axis: usize },
+}
+
+/// Traversal stack kept on the call stack. Only degenerate trees deeper than
+/// `STACK_CAPACITY` spill to the heap.
+struct NodeStack {
+    inline: [usize; STACK_CAPACITY],
+    len: usize,
+    spill: Vec<usize>,
+}
+
+impl NodeStack {
+    fn new() -> NodeStack {
+        NodeStack {
+            inline: [0; STACK_CAPACITY],
+            len: 0,
+            spill: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, idx: usize) {
+        if self.len < STACK_CAPACITY {
+            self.inline[self.len] = idx;
+            self.len += 1;
+        } else {
+            self.spill.push(idx);
+        }
+    }
+
+    fn pop(&mut self) -> Option<usize> {
+        if let Some(idx) = self.spill.pop() {
+            return Some(idx);
+        }
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        Some(self.inline[self.len])
+    }
+}
+
+struct BvhNode {
+    bounding: AABB,
+    kind: BvhNodeKind,
+}
+
+/// Flattened BVH over a list of bounding boxes.
+///
+/// The tree knows nothing about what it bounds: primitives are referred to
+/// by their indices in the slice passed to `BvhTree::new`.
+pub struct BvhTree {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+impl BvhTree {
+    pub fn new(boundings: &[AABB]) -> BvhTree {
+        let mut tree = BvhTree {
+            nodes: Vec::with_capacity(2 * boundings.len()),
+            indices: (0..boundings.len()).collect(),
+        };
+        if !boundings.is_empty() {
+            let centroids: Vec<Vec3f> = boundings.iter().map(|b| b.centroid()).collect();
+            tree.build(boundings, &centroids, 0, boundings.len());
+        }
+        tree
+    }
+
+    /// Bounding box of all primitives
+    pub fn bounding(&self) -> AABB {
+        match self.nodes.first() {
+            Some(root) => root.bounding.clone(),
+            None => AABB::from_point(ZERO_VEC3),
+        }
+    }
+
+    /// Visit primitives whose bounding boxes may contain the nearest intersection.
+    ///
+    /// `f` is called with the index of a primitive and shall return the `t` of its
+    /// intersection with `r`, if any. Subtrees farther than the nearest `t` seen so
+    /// far are skipped.
+    pub fn traverse<F>(&self, r: &Ray, mut f: F)
+    where
+        F: FnMut(usize) -> Option<Real>,
+    {
+        if self.nodes.is_empty() {
+            return;
+        }
+
+        let mut t_max = r.t_max;
+        // Degenerate SAH trees may be as deep as the primitive count
+        let mut stack = NodeStack::new();
+        stack.push(0);
+
+        while let Some(idx) = stack.pop() {
+            let node = &self.nodes[idx];
+            match node.bounding.is_intersected(r) {
+                Some((t_near, _)) if t_near <= t_max => (),
+                _ => continue,
+            }
+
+            match node.kind {
+                BvhNodeKind::Leaf { start, count } => {
+                    for &prim in &self.indices[start..start + count] {
+                        if let Some(t) = f(prim) {
+                            t_max = t_max.min(t);
+                        }
+                    }
+                }
+                BvhNodeKind::Interior { second, axis } => {
+                    // Push the far child first so that the near one is visited first
+                    let (near, far) = if r.d[axis] < 0.0 {
+                        (second, idx + 1)
+                    } else {
+                        (idx + 1, second)
+                    };
+                    stack.push(far);
+                    stack.push(near);
+                }
+            }
+        }
+    }
+
+    fn push_leaf(&mut self, bounding: AABB, start: usize, end: usize) -> usize {
+        self.nodes.push(BvhNode {
+            bounding,
+            kind: BvhNodeKind::Leaf {
+                start,
+                count: end - start,
+            },
+        });
+        self.nodes.len() - 1
+    }
+
+    /// Build subtree for `self.indices[start..end]` and return its root index
+    fn build(&mut self, boundings: &[AABB], centroids: &[Vec3f], start: usize, end: usize) -> usize {
+        let prims = &self.indices[start..end];
+        let bounding = prims[1..]
+            .iter()
+            .fold(boundings[prims[0]].clone(), |acc, &i| acc.union(&boundings[i]));
+        let count = end - start;
+        if count == 1 {
+            return self.push_leaf(bounding, start, end);
+        }
+
+        let cen_bounding = prims[1..]
+            .iter()
+            .fold(AABB::from_point(centroids[prims[0]]), |acc, &i| {
+                acc.union_point(centroids[i])
+            });
+        let axis = cen_bounding.max_extent();
+        let cen_lower = cen_bounding.get_lower()[axis];
+        let cen_extent = cen_bounding.get_upper()[axis] - cen_lower;
+        if cen_extent <= 0.0 {
+            // All centroids coincide, no split can separate them
+            return self.push_leaf(bounding, start, end);
+        }
+
+        let bucket_of = |i: usize| {
+            let b = ((centroids[i][axis] - cen_lower) / cen_extent * BUCKET_COUNT as Real) as usize;
+            b.min(BUCKET_COUNT - 1)
+        };
+
+        let mut bucket_counts = [0_usize; BUCKET_COUNT];
+        let mut bucket_bounds: Vec<Option<AABB>> = vec![None; BUCKET_COUNT];
+        for &i in prims {
+            let b = bucket_of(i);
+            bucket_counts[b] += 1;
+            bucket_bounds[b] = Some(match bucket_bounds[b].take() {
+                None => boundings[i].clone(),
+                Some(acc) => acc.union(&boundings[i]),
+            });
+        }
+
+        // Cost of splitting after bucket i, for i in [0, BUCKET_COUNT - 1)
+        let merge = |range: &[Option<AABB>]| {
+            range.iter().fold(None, |acc: Option<AABB>, b| match (acc, b) {
+                (None, b) => b.clone(),
+                (Some(acc), None) => Some(acc),
+                (Some(acc), Some(b)) => Some(acc.union(b)),
+            })
+        };
+        let total_area = bounding.surface_area();
+        let mut best: Option<(usize, Real)> = None;
+        for split in 0..BUCKET_COUNT - 1 {
+            let cnt0: usize = bucket_counts[..=split].iter().sum();
+            let cnt1 = count - cnt0;
+            if cnt0 == 0 || cnt1 == 0 {
+                continue;
+            }
+            let area0 = merge(&bucket_bounds[..=split]).unwrap().surface_area();
+            let area1 = merge(&bucket_bounds[split + 1..]).unwrap().surface_area();
+            let cost = if total_area > 0.0 {
+                TRAVERSAL_COST + (cnt0 as Real * area0 + cnt1 as Real * area1) / total_area
+            } else {
+                TRAVERSAL_COST + 0.5 * count as Real
+            };
+            best = match best {
+                Some((_, c)) if c <= cost => best,
+                _ => Some((split, cost)),
+            };
+        }
+
+        let (split, cost) = match best {
+            Some(b) => b,
+            None => return self.push_leaf(bounding, start, end),
+        };
+        if count <= MAX_LEAF_SIZE && cost >= count as Real {
+            return self.push_leaf(bounding, start, end);
+        }
+
+        // Partition indices by bucket
+        let mut mid = start;
+        for k in start..end {
+            if bucket_of(self.indices[k]) <= split {
+                self.indices.swap(k, mid);
+                mid += 1;
+            }
+        }
+
+        let node_idx = self.nodes.len();
+        self.nodes.push(BvhNode {
+            bounding,
+            kind: BvhNodeKind::Leaf { start, count: 0 },
+        });
+        self.build(boundings, centroids, start, mid);
+        let second = self.build(boundings, centroids, mid, end);
+        self.nodes[node_idx].kind = BvhNodeKind::Interior { second, axis };
+        node_idx
+    }
+}
+
+/// Entity grouping other entities in a BVH
+pub struct BvhEntity {
+    entities: Vec<Box<Entity>>,
+    tree: BvhTree,
+}
+
+impl Entity for BvhEntity {
+    fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
+        let mut ret: Option<Intersection> = None;
+        self.tree.traverse(&r, |i| {
+            let t_max = ret.as_ref().map_or(r.t_max, |v| v.t);
+            let mut inct = self.entities[i].inct(r.clone().with_range(r.t_min, t_max))?;
+            inct.entity_id.get_or_insert(i);
+            let t = inct.t;
+            ret = Some(match ret.take() {
+                None => inct,
+                Some(v) => v.nearer(inct),
+            });
+            Some(t)
+        });
+        ret
+    }
+
+    fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        let mut ret: Option<(Real, Vec3f)> = None;
+        self.tree.traverse(&r, |i| {
+            let t_max = ret.map_or(r.t_max, |v| v.0);
+            let inct = self.entities[i].has_inct(r.clone().with_range(r.t_min, t_max))?;
+            ret = match ret {
+                Some(v) if v.0 <= inct.0 => Some(v),
+                _ => Some(inct),
+            };
+            Some(inct.0)
+        });
+        ret
+    }
+
+    fn bounding(&self) -> AABB {
+        self.tree.bounding()
+    }
+}
+
+impl BvhEntity {
+    pub fn new(entities: Vec<Box<Entity>>) -> BvhEntity {
+        let boundings: Vec<AABB> = entities.iter().map(|e| e.bounding()).collect();
+        let tree = BvhTree::new(&boundings);
+        BvhEntity { entities, tree }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arena::Arena;
+    use material::*;
+
+    fn spheres() -> Vec<Box<Entity>> {
+        let mut ret: Vec<Box<Entity>> = Vec::new();
+        for i in 0..10 {
+            for j in 0..10 {
+                for k in 0..10 {
+                    let cen = vec3(i as Real, j as Real, k as Real) * 0.5;
+                    let radius = 0.1 + 0.02 * ((i + 2 * j + 3 * k) % 7) as Real;
+                    ret.push(Box::new(sphere::Sphere::new(
+                        cen,
+                        radius,
+                        Box::new(|_, lx, ly, _, _| Phong::new(BLACK, WHITE, lx, ly, 1.0)),
+                    )));
+                }
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn same_as_linear() {
+        let linear = spheres();
+        let bvh = BvhEntity::new(spheres());
+
+        let b = bvh.bounding();
+        assert!(b.get_lower().relative_eq(&vec3(-0.22, -0.22, -0.22), 1e-9, 1e-9));
+        assert!(b.get_upper().relative_eq(&vec3(4.72, 4.72, 4.72), 1e-9, 1e-9));
+
+        for n in 0..500 {
+            let n = n as Real;
+            let p = vec3(-3.0 + (n * 0.37).sin(), 2.0 + (n * 1.3).cos(), -3.0);
+            let d = vec3(1.0 + (n * 0.71).sin(), (n * 0.23).cos() * 0.5, 1.0);
+            let r = Ray::new(p, d);
+
+            let expected = nearest_inct(&linear, r.clone()).map(|i| (i.t, i.entity_id));
+
+            let got = bvh.has_inct(r.clone()).map(|(t, _)| t);
+            let got_inct = bvh.inct(r.clone()).map(|i| (i.t, i.entity_id));
+            match (expected, got, got_inct) {
+                (None, None, None) => (),
+                (Some((e, e_id)), Some(g), Some((gi, gi_id))) => {
+                    assert!(e.relative_eq(&g, 1e-9, 1e-9));
+                    assert!(e.relative_eq(&gi, 1e-9, 1e-9));
+                    assert_eq!(e_id, gi_id);
+                }
+                _ => panic!("BVH and linear search disagree"),
+            }
+        }
+    }
+
+    #[test]
+    fn material_of_closest_hit_only() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        static BUILT: AtomicUsize = AtomicUsize::new(0);
+
+        // Listed back to front, so that every sphere is a new nearest hit
+        let spheres = || -> Vec<Box<Entity>> {
+            (0..10)
+                .map(|i| -> Box<Entity> {
+                    Box::new(sphere::Sphere::new(
+                        vec3(0.0, 0.0, i as Real),
+                        1.0,
+                        Box::new(|_, _, ly, _, _| {
+                            BUILT.fetch_add(1, Ordering::SeqCst);
+                            DiffuseLight::new(ly, WHITE)
+                        }),
+                    ))
+                })
+                .collect()
+        };
+        let r = Ray::new(vec3(0.0, 0.0, 20.0), -Z_VEC3);
+
+        let list = spheres();
+        assert_eq!(nearest_inct(&list, r.clone()).unwrap().entity_id, Some(9));
+        let bvh = BvhEntity::new(spheres());
+        let inct = bvh.inct(r.clone()).unwrap();
+        assert_eq!(inct.entity_id, Some(9));
+        assert_eq!(BUILT.load(Ordering::SeqCst), 0);
+        assert_eq!(inct.material.build(&Arena::new()).emit(-r.d), WHITE);
+        assert_eq!(BUILT.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn deep_tree() {
+        // Each split separates the nearest sphere from the rest, one level at a time
+        let entities: Vec<Box<Entity>> = (0..100)
+            .map(|i| -> Box<Entity> {
+                Box::new(sphere::Sphere::new(
+                    vec3((16.0 as Real).powi(i), 0.0, 0.0),
+                    0.5,
+                    Box::new(|_, lx, ly, _, _| Phong::new(BLACK, WHITE, lx, ly, 1.0)),
+                ))
+            })
+            .collect();
+        let bvh = BvhEntity::new(entities);
+        let inct = bvh.inct(Ray::new(vec3(-5.0, 0.0, 0.0), X_VEC3)).unwrap();
+        assert!(inct.t.relative_eq(&5.5, 1e-9, 1e-9));
+    }
+
+    #[test]
+    fn stack_spill() {
+        let mut stack = NodeStack::new();
+        for i in 0..100 {
+            stack.push(i);
+        }
+        assert_eq!(stack.spill.len(), 100 - STACK_CAPACITY);
+        for i in (0..100).rev() {
+            assert_eq!(stack.pop(), Some(i));
+        }
+        assert_eq!(stack.pop(), None);
+    }
+}
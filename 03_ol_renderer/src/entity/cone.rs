@@ -0,0 +1,88 @@
+//! Cone entity
+
+use entity::*;
+use material::*;
+use math::*;
+
+/// Cone entity
+///
+/// A capped cone is a closed surface and `local_y` passed to the material closure
+/// is the outward normal. Without caps the surface is two-sided and `local_y` faces
+/// the incoming ray.
+///
+/// Only a capped cone can be used as a `Solid`.
+pub struct Cone<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    cone: model::Cone,
+    fm: Box<FM>,
+}
+
+impl<M, FM> Entity for Cone<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
+        let (t, p) = self.cone.nearest_inct(r.clone())?;
+        let flip = !self.cone.is_closed() && dot(self.cone.inct_to_local_y(p), r.d) > 0.0;
+        Some(self.inct_at(r, t, flip))
+    }
+
+    fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        self.cone.nearest_inct(r)
+    }
+
+    fn bounding(&self) -> model::AABB {
+        self.cone.to_aabb_bounding()
+    }
+}
+
+impl<M, FM> Solid for Cone<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    fn inct_intervals(&self, r: Ray) -> Vec<(Real, Real)> {
+        self.cone.inct_intervals(&r)
+    }
+
+    fn inct_at(&self, r: Ray, t: Real, flip: bool) -> Intersection<'_> {
+        let (p, p_error) = self.cone.inct_point(&r, t);
+        let local_y = self.cone.inct_to_local_y(p);
+        let local_y = if flip { -local_y } else { local_y };
+        let local_x = self.cone.inct_to_local_x(p);
+        let (u, v) = self.cone.inct_to_uv(p);
+        let (dpdu, dpdv) = self.cone.inct_to_dpduv(p);
+        let args = MaterialArgs::new(p, local_x, local_y, vec2(u, v));
+        Intersection {
+            t,
+            position: p,
+            p_error,
+            normal: local_y,
+            geo_normal: local_y,
+            uv: vec2(u, v),
+            dpdu,
+            dpdv,
+            front_face: dot(local_y, r.d) < 0.0,
+            prim_id: 0,
+            entity_id: None,
+            material: DeferredMaterial::new(&*self.fm, args),
+        }
+    }
+}
+
+impl<M, FM> Cone<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    pub fn new(base: Vec3f, radius: Real, height: Real, capped: bool, fm: Box<FM>) -> Self {
+        Cone {
+            cone: model::Cone::new(base, radius, height, capped),
+            fm,
+        }
+    }
+}
@@ -0,0 +1,218 @@
+//! Constructive solid geometry
+
+use entity::*;
+use math::{model::AABB, *};
+
+/// Boolean operation of `CsgEntity`
+#[derive(Clone, Copy, PartialEq, Debug)]
+pub enum CsgOp {
+    Union,
+    Intersection,
+    /// The first operand minus the second one
+    Difference,
+}
+
+impl CsgOp {
+    fn is_inside(self, in_a: bool, in_b: bool) -> bool {
+        match self {
+            CsgOp::Union => in_a || in_b,
+            CsgOp::Intersection => in_a && in_b,
+            CsgOp::Difference => in_a && !in_b,
+        }
+    }
+}
+
+/// Combine two sorted lists of disjoint spans with given operation
+pub fn combine_intervals(op: CsgOp, a: &[(Real, Real)], b: &[(Real, Real)]) -> Vec<(Real, Real)> {
+    // (t, is the event of a, entering)
+    let mut events = Vec::with_capacity(2 * (a.len() + b.len()));
+    for &(t_in, t_out) in a {
+        events.push((t_in, true, true));
+        events.push((t_out, true, false));
+    }
+    for &(t_in, t_out) in b {
+        events.push((t_in, false, true));
+        events.push((t_out, false, false));
+    }
+    events.sort_by(|x, y| x.0.partial_cmp(&y.0).unwrap());
+
+    let (mut in_a, mut in_b) = (false, false);
+    let mut start = 0.0;
+    let mut ret: Vec<(Real, Real)> = Vec::new();
+    for (t, is_a, entering) in events {
+        let was_inside = op.is_inside(in_a, in_b);
+        if is_a {
+            in_a = entering;
+        } else {
+            in_b = entering;
+        }
+        match (was_inside, op.is_inside(in_a, in_b)) {
+            (false, true) => start = t,
+            (true, false) if start < t => match ret.last_mut() {
+                // Spans touching each other (e.g. in a union) make one span
+                Some(last) if start <= last.1 => last.1 = last.1.max(t),
+                _ => ret.push((start, t)),
+            },
+            _ => (),
+        }
+    }
+    ret
+}
+
+/// Union, intersection or difference of two solids.
+///
+/// Surfaces of the second operand bounding a difference are seen from inside,
+/// so their normals are flipped. A `CsgEntity` is a solid itself and can be nested.
+pub struct CsgEntity {
+    op: CsgOp,
+    a: Box<Solid>,
+    b: Box<Solid>,
+}
+
+impl CsgEntity {
+    pub fn new(op: CsgOp, a: Box<Solid>, b: Box<Solid>) -> CsgEntity {
+        CsgEntity { op, a, b }
+    }
+
+    pub fn get_op(&self) -> CsgOp {
+        self.op
+    }
+
+    fn first_in_range(&self, r: Ray) -> Option<Real> {
+        for (t_in, t_out) in self.inct_intervals(r.clone()) {
+            if r.is_in_range(t_in) {
+                return Some(t_in);
+            }
+            if r.is_in_range(t_out) {
+                return Some(t_out);
+            }
+        }
+        None
+    }
+}
+
+impl Entity for CsgEntity {
+    fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
+        let t = self.first_in_range(r.clone())?;
+        Some(self.inct_at(r, t, false))
+    }
+
+    fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        let t = self.first_in_range(r.clone())?;
+        Some((t, r.t_to_point(t)))
+    }
+
+    fn bounding(&self) -> AABB {
+        match self.op {
+            CsgOp::Union => self.a.bounding().union(&self.b.bounding()),
+            CsgOp::Intersection | CsgOp::Difference => self.a.bounding(),
+        }
+    }
+}
+
+impl Solid for CsgEntity {
+    fn inct_intervals(&self, r: Ray) -> Vec<(Real, Real)> {
+        let a = self.a.inct_intervals(r.clone());
+        if a.is_empty() && self.op != CsgOp::Union {
+            return a;
+        }
+        combine_intervals(self.op, &a, &self.b.inct_intervals(r))
+    }
+
+    fn inct_at(&self, r: Ray, t: Real, flip: bool) -> Intersection<'_> {
+        // Find the operand owning the span end nearest to t
+        let dis = |spans: Vec<(Real, Real)>| {
+            spans
+                .into_iter()
+                .map(|(t_in, t_out)| (t_in - t).abs().min((t_out - t).abs()))
+                .fold(REAL_MAX, Real::min)
+        };
+        let dis_a = dis(self.a.inct_intervals(r.clone()));
+        let dis_b = dis(self.b.inct_intervals(r.clone()));
+        if dis_a <= dis_b {
+            self.a.inct_at(r, t, flip)
+        } else {
+            let flip = flip != (self.op == CsgOp::Difference);
+            self.b.inct_at(r, t, flip)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arena::Arena;
+    use material::*;
+
+    #[test]
+    fn combine() {
+        let a = [(0.0, 2.0), (4.0, 6.0)];
+        let b = [(1.0, 5.0)];
+        assert_eq!(combine_intervals(CsgOp::Union, &a, &b), vec![(0.0, 6.0)]);
+        assert_eq!(
+            combine_intervals(CsgOp::Intersection, &a, &b),
+            vec![(1.0, 2.0), (4.0, 5.0)]
+        );
+        assert_eq!(
+            combine_intervals(CsgOp::Difference, &a, &b),
+            vec![(0.0, 1.0), (5.0, 6.0)]
+        );
+        assert_eq!(
+            combine_intervals(CsgOp::Difference, &b, &a),
+            vec![(2.0, 4.0)]
+        );
+
+        // Touching spans are merged, whichever of them is listed first
+        let c = [(2.0, 4.0)];
+        assert_eq!(combine_intervals(CsgOp::Union, &a, &c), vec![(0.0, 6.0)]);
+        assert_eq!(combine_intervals(CsgOp::Union, &c, &a), vec![(0.0, 6.0)]);
+    }
+
+    #[test]
+    fn sphere_minus_cylinder() {
+        let light = |_, _, ly, _, _| DiffuseLight::new(ly, WHITE);
+        let sph = sphere::Sphere::new(ZERO_VEC3, 1.0, Box::new(light));
+        let cyl = cylinder::Cylinder::new(vec3(0.0, -2.0, 0.0), 0.5, 4.0, true, Box::new(light));
+        let csg = CsgEntity::new(CsgOp::Difference, Box::new(sph), Box::new(cyl));
+
+        // Straight through the hole
+        assert!(csg.inct(Ray::new(vec3(0.0, 5.0, 0.0), -Y_VEC3)).is_none());
+
+        let r = Ray::new(vec3(-3.0, 0.0, 0.0), X_VEC3);
+        let inct = csg.inct(r).unwrap();
+        assert!(inct.t.relative_eq(&2.0, 1e-9, 1e-9));
+        assert!(inct.normal.relative_eq(&-X_VEC3, 1e-9, 1e-9));
+
+        // From inside the material, leaving into the hole
+        let r = Ray::new(vec3(-0.7, 0.0, 0.0), X_VEC3);
+        let inct = csg.inct(r).unwrap();
+        assert!(inct.t.relative_eq(&0.2, 1e-9, 1e-9));
+        assert!(inct.normal.relative_eq(&X_VEC3, 1e-9, 1e-9));
+
+        // From the hole, the wall faces the ray
+        let r = Ray::new(ZERO_VEC3, X_VEC3);
+        let inct = csg.inct(r.clone()).unwrap();
+        assert!(inct.t.relative_eq(&0.5, 1e-9, 1e-9));
+        assert!(inct.normal.relative_eq(&-X_VEC3, 1e-9, 1e-9));
+        let arena = Arena::new();
+        let material = inct.material.build(&arena);
+        assert_eq!(material.emit(-r.d), WHITE);
+        assert_eq!(material.emit(r.d), BLACK);
+    }
+
+    #[test]
+    fn open_surfaces() {
+        // Uncapped cylinders and cones enclose nothing
+        let light = |_, _, ly, _, _| DiffuseLight::new(ly, WHITE);
+        let cyl = cylinder::Cylinder::new(vec3(0.0, -2.0, 0.0), 0.5, 4.0, false, Box::new(light));
+        let sph = sphere::Sphere::new(ZERO_VEC3, 1.0, Box::new(light));
+        let csg = CsgEntity::new(CsgOp::Difference, Box::new(cyl), Box::new(sph));
+        assert!(csg.has_inct(Ray::new(vec3(-3.0, 1.5, 0.0), X_VEC3)).is_none());
+
+        let cone = cone::Cone::new(vec3(0.0, -2.0, 0.0), 0.5, 4.0, false, Box::new(light));
+        let sph = sphere::Sphere::new(ZERO_VEC3, 1.0, Box::new(light));
+        let csg = CsgEntity::new(CsgOp::Union, Box::new(cone), Box::new(sph));
+        let inct = csg.inct(Ray::new(vec3(-3.0, 0.0, 0.0), X_VEC3)).unwrap();
+        assert!(inct.t.relative_eq(&2.0, 1e-9, 1e-9));
+    }
+}
@@ -0,0 +1,44 @@
+//! Disk entity
+
+use entity::*;
+use material::*;
+use math::{model::AABB, *};
+
+/// Disk entity
+pub type Disk<M, FM> = PlanarEntity<model::Disk, M, FM>;
+
+impl PlanarShape for model::Disk {
+    fn get_plane(&self) -> &model::Plane {
+        self.get_plane()
+    }
+
+    fn contains(&self, p: Vec3f) -> bool {
+        self.contains(p)
+    }
+
+    fn to_aabb_bounding(&self) -> AABB {
+        self.to_aabb_bounding()
+    }
+
+    fn inct_to_uv(&self, p: Vec3f) -> (Real, Real) {
+        self.inct_to_uv(p)
+    }
+
+    fn inct_to_dpduv(&self, p: Vec3f) -> (Vec3f, Vec3f) {
+        self.inct_to_dpduv(p)
+    }
+
+    fn inct_to_local_x(&self, p: Vec3f) -> Vec3f {
+        self.inct_to_local_x(p)
+    }
+}
+
+impl<M, FM> Disk<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    pub fn new(centre: Vec3f, normal: Vec3f, radius: Real, fm: Box<FM>) -> Self {
+        PlanarEntity::from_shape(model::Disk::new(centre, normal, radius), fm)
+    }
+}
@@ -0,0 +1,341 @@
+//! Transformed instance of a shared entity
+
+use std::sync::Arc;
+
+use entity::*;
+use math::{model::AABB, *};
+
+/// Object-to-world transformation with cached derived matrices
+struct InstanceTransform {
+    obj_to_world: Mat4f,
+    world_to_obj: Mat4f,
+    /// Inverse transpose of the upper 3x3 part of `obj_to_world`
+    normal_to_world: Mat3f,
+    dir_to_world: Mat3f,
+}
+
+impl InstanceTransform {
+    fn new(obj_to_world: Mat4f) -> InstanceTransform {
+        let world_to_obj = obj_to_world.invert();
+        assert!(world_to_obj.is_some());
+        let world_to_obj = world_to_obj.unwrap();
+        let dir_to_world = Mat3f::from_cols(
+            obj_to_world.x.xyz(),
+            obj_to_world.y.xyz(),
+            obj_to_world.z.xyz(),
+        );
+        let world_to_obj3 = Mat3f::from_cols(
+            world_to_obj.x.xyz(),
+            world_to_obj.y.xyz(),
+            world_to_obj.z.xyz(),
+        );
+        InstanceTransform {
+            obj_to_world,
+            world_to_obj,
+            normal_to_world: world_to_obj3.transpose(),
+            dir_to_world,
+        }
+    }
+
+    fn point_to_world(&self, p: Vec3f) -> Vec3f {
+        (self.obj_to_world * vec4(p.x, p.y, p.z, 1.0)).xyz()
+    }
+
+    /// Bound of the absolute error of `point_to_world(p)`, given that of `p`
+    fn error_to_world(&self, p: Vec3f, p_error: Vec3f) -> Vec3f {
+        let m = &self.dir_to_world;
+        let abs_m = Mat3f::from_cols(abs_vec3(m.x), abs_vec3(m.y), abs_vec3(m.z));
+        let abs_trans = abs_vec3(self.obj_to_world.w.xyz());
+        (gamma(3) + 1.0) * (abs_m * p_error) + gamma(3) * (abs_m * abs_vec3(p) + abs_trans)
+    }
+
+    /// Distance along world space ray `r` to world space point `p` on it
+    fn world_t(r: &Ray, p: Vec3f) -> Real {
+        dot(p - r.p, r.d)
+    }
+
+    /// Shading frame in world space. Normals and tangents orthogonal in object space
+    /// stay orthogonal, up to rounding, when the normals use the inverse transpose.
+    fn frame_to_world(&self, local_x: Vec3f, local_y: Vec3f) -> (Vec3f, Vec3f) {
+        let local_y = (self.normal_to_world * local_y).normalize();
+        let local_x = self.dir_to_world * local_x;
+        let local_x = (local_x - local_y * dot(local_x, local_y)).normalize();
+        (local_x, local_y)
+    }
+
+    fn inct<'a>(&self, entity: &'a Entity, r: Ray) -> Option<Intersection<'a>> {
+        let inct = entity.inct(self.world_to_obj * r.clone())?;
+        let position = self.point_to_world(inct.position);
+        let args = *inct.material.get_args();
+        let (local_x, local_y) = self.frame_to_world(args.local_x, args.local_y);
+        Some(Intersection {
+            t: Self::world_t(&r, position),
+            position,
+            p_error: self.error_to_world(inct.position, inct.p_error),
+            normal: (self.normal_to_world * inct.normal).normalize(),
+            geo_normal: (self.normal_to_world * inct.geo_normal).normalize(),
+            dpdu: self.dir_to_world * inct.dpdu,
+            dpdv: self.dir_to_world * inct.dpdv,
+            material: inct.material.with_frame(local_x, local_y),
+            ..inct
+        })
+    }
+
+    fn has_inct(&self, entity: &Entity, r: Ray) -> Option<(Real, Vec3f)> {
+        let (_, p) = entity.has_inct(self.world_to_obj * r.clone())?;
+        let p = self.point_to_world(p);
+        Some((Self::world_t(&r, p), p))
+    }
+
+    fn bounding(&self, b: &AABB) -> AABB {
+        let (lower, upper) = (*b.get_lower(), *b.get_upper());
+        let corner = |i: usize| {
+            vec3(
+                if i & 1 == 0 { lower.x } else { upper.x },
+                if i & 2 == 0 { lower.y } else { upper.y },
+                if i & 4 == 0 { lower.z } else { upper.z },
+            )
+        };
+        (1..8).fold(AABB::from_point(self.point_to_world(corner(0))), |acc, i| {
+            acc.union_point(self.point_to_world(corner(i)))
+        })
+    }
+}
+
+/// Places a shared entity in the world with an object-to-world matrix.
+///
+/// Rays are transformed into object space before being passed to the wrapped entity,
+/// so the same entity (e.g. a large mesh) can be instanced many times without copying.
+/// Materials of the wrapped entity get positions in object space, and their shading
+/// frame in world space.
+pub struct InstanceEntity {
+    entity: Arc<Entity + Send>,
+    trans: InstanceTransform,
+}
+
+impl InstanceEntity {
+    pub fn new(entity: Arc<Entity + Send>, obj_to_world: Mat4f) -> InstanceEntity {
+        InstanceEntity {
+            entity,
+            trans: InstanceTransform::new(obj_to_world),
+        }
+    }
+
+    pub fn get_obj_to_world(&self) -> &Mat4f {
+        &self.trans.obj_to_world
+    }
+}
+
+impl Entity for InstanceEntity {
+    fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
+        self.trans.inct(&*self.entity, r)
+    }
+
+    fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        self.trans.has_inct(&*self.entity, r)
+    }
+
+    fn bounding(&self) -> AABB {
+        self.trans.bounding(&self.entity.bounding())
+    }
+}
+
+/// Like `InstanceEntity`, with the object-to-world transformation interpolated
+/// between keyframes at the time of each ray
+pub struct AnimatedInstanceEntity {
+    entity: Arc<Entity + Send>,
+    track: KeyframeTrack,
+}
+
+/// Transformations sampled per keyframe interval when computing the bounding box
+const BOUNDING_STEPS: usize = 16;
+
+/// Bound of the distance any point of `b` moves, between keyframes `k0` and `k1`,
+/// within `1 / BOUNDING_STEPS` of the interval
+fn step_motion(b: &AABB, k0: &Keyframe, k1: &Keyframe) -> Real {
+    let (lower, upper) = (*b.get_lower(), *b.get_upper());
+    let radius = (0..3).fold(0.0 as Real, |acc, i| {
+        acc + lower[i].abs().max(upper[i].abs()).powi(2)
+    });
+    let radius = radius.sqrt();
+    let max_scale = (0..3).fold(0.0 as Real, |acc, i| {
+        acc.max(k0.scale[i].abs()).max(k1.scale[i].abs())
+    });
+    // Rotation angle along the shorter arc, as interpolated by `KeyframeTrack`
+    let angle = 2.0 * k0.rotation.dot(k1.rotation).abs().min(1.0).acos();
+    let total = (k1.translation - k0.translation).magnitude()
+        + (k1.scale - k0.scale).magnitude() * radius
+        + angle * max_scale * radius;
+    total / BOUNDING_STEPS as Real
+}
+
+impl AnimatedInstanceEntity {
+    pub fn new(entity: Arc<Entity + Send>, track: KeyframeTrack) -> AnimatedInstanceEntity {
+        AnimatedInstanceEntity { entity, track }
+    }
+
+    pub fn get_track(&self) -> &KeyframeTrack {
+        &self.track
+    }
+}
+
+impl Entity for AnimatedInstanceEntity {
+    fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
+        InstanceTransform::new(self.track.at(r.time)).inct(&*self.entity, r)
+    }
+
+    fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        InstanceTransform::new(self.track.at(r.time)).has_inct(&*self.entity, r)
+    }
+
+    fn bounding(&self) -> AABB {
+        // Rotations may sweep outside the boxes at keyframes, so sample in between,
+        // and pad the samples by how far points may move until the next one
+        let b = self.entity.bounding();
+        let keys = self.track.get_keyframes();
+        let mut ret = InstanceTransform::new(keys[0].to_mat4()).bounding(&b);
+        for w in keys.windows(2) {
+            let pad = vec3(1.0, 1.0, 1.0) * step_motion(&b, &w[0], &w[1]);
+            for i in 0..BOUNDING_STEPS {
+                let time = w[0].time + (w[1].time - w[0].time) * i as Real / BOUNDING_STEPS as Real;
+                let step = InstanceTransform::new(self.track.at(time)).bounding(&b);
+                let step = AABB::new(*step.get_lower() - pad, *step.get_upper() + pad);
+                ret = ret.union(&step);
+            }
+        }
+        let last = keys[keys.len() - 1].to_mat4();
+        ret.union(&InstanceTransform::new(last).bounding(&b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arena::Arena;
+    use material::*;
+
+    #[test]
+    fn scaled_rotated_sphere() {
+        let sph: Arc<Entity + Send> = Arc::new(sphere::Sphere::new(
+            vec3(1.0, 0.0, 0.0),
+            1.0,
+            Box::new(|_, _, ly, _, _| DiffuseLight::new(ly, WHITE)),
+        ));
+        // Rotate by 90 degrees around y (object +x goes to world -z), scale by 2, move up
+        let obj_to_world = Mat4f::from_translation(vec3(0.0, 5.0, 0.0))
+            * Mat4f::from_angle_y(Deg(90.0))
+            * Mat4f::from_scale(2.0);
+        let inst = InstanceEntity::new(sph, obj_to_world);
+
+        // World space sphere: centre (0, 5, -2), radius 2
+        let b = inst.bounding();
+        assert!(b.get_lower().relative_eq(&vec3(-2.0, 3.0, -4.0), 1e-9, 1e-9));
+        assert!(b.get_upper().relative_eq(&vec3(2.0, 7.0, 0.0), 1e-9, 1e-9));
+
+        let r = Ray::new(vec3(10.0, 5.0, -2.0), vec3(-1.0, 0.0, 0.0));
+        let inct = inst.inct(r.clone()).unwrap();
+        assert!(inct.t.relative_eq(&8.0, 1e-9, 1e-9));
+        assert!(inct.position.relative_eq(&vec3(2.0, 5.0, -2.0), 1e-9, 1e-9));
+        assert!(inct.normal.relative_eq(&X_VEC3, 1e-9, 1e-9));
+        let arena = Arena::new();
+        let material = inct.material.build(&arena);
+        assert_eq!(material.emit(-r.d), WHITE);
+        assert_eq!(material.emit(r.d), BLACK);
+
+        let (t, p) = inst.has_inct(r).unwrap();
+        assert!(t.relative_eq(&8.0, 1e-9, 1e-9));
+        assert!(p.relative_eq(&vec3(2.0, 5.0, -2.0), 1e-9, 1e-9));
+
+        assert!(inst.has_inct(Ray::new(vec3(10.0, 0.0, 0.0), vec3(-1.0, 0.0, 0.0))).is_none());
+    }
+
+    #[test]
+    fn non_uniform_scale() {
+        let sph: Arc<Entity + Send> = Arc::new(sphere::Sphere::new(
+            ZERO_VEC3,
+            1.0,
+            Box::new(|_, lx, ly, _, _| Phong::new(BLACK, WHITE, lx, ly, 1.0)),
+        ));
+        let inst = InstanceEntity::new(sph, Mat4f::from_nonuniform_scale(1.0, 4.0, 1.0));
+
+        let r = Ray::new(vec3(5.0, 2.0, 0.3), vec3(-1.0, 0.0, 0.0));
+        let inct = inst.inct(r.clone()).unwrap();
+        // Ellipsoid x^2 + y^2 / 16 + z^2 = 1
+        let p = inct.position;
+        let n = vec3(p.x, p.y / 16.0, p.z).normalize();
+        assert!(inct.normal.relative_eq(&n, 1e-9, 1e-9));
+        assert!(dot(inct.dpdu, n).abs() < 1e-9);
+
+        // Samples follow the pdf: E[cos / pdf] is the integral of cos over the hemisphere
+        let arena = Arena::new();
+        let material = inct.material.build(&arena);
+        let v = -r.d;
+        let count = 100000;
+        let sum: Real = material
+            .sample(&v, count, &arena)
+            .iter()
+            .map(|s| {
+                let cos = dot(*s, n);
+                assert!(cos >= -1e-9);
+                assert!(material.pdf(&v, s) > 0.0);
+                cos / material.pdf(&v, s)
+            })
+            .sum();
+        assert!((sum / count as Real - REAL_PI).abs() < 0.03);
+    }
+
+    #[test]
+    fn animated_translation() {
+        let sph: Arc<Entity + Send> = Arc::new(sphere::Sphere::new(
+            ZERO_VEC3,
+            1.0,
+            Box::new(|_, _, ly, _, _| DiffuseLight::new(ly, WHITE)),
+        ));
+        let rot = Quatf::from_angle_y(Deg(0.0));
+        let one = vec3(1.0, 1.0, 1.0);
+        let inst = AnimatedInstanceEntity::new(
+            sph,
+            KeyframeTrack::new(vec![
+                Keyframe::new(0.0, ZERO_VEC3, rot, one),
+                Keyframe::new(1.0, vec3(0.0, 0.0, 4.0), rot, one),
+            ]),
+        );
+
+        // Padded by the motion within a step
+        let b = inst.bounding();
+        assert!(b.get_lower().relative_eq(&vec3(-1.25, -1.25, -1.25), 1e-9, 1e-9));
+        assert!(b.get_upper().relative_eq(&vec3(1.25, 1.25, 5.0), 1e-9, 1e-9));
+
+        let r = |time| Ray::with_time(vec3(10.0, 0.0, 2.0), -X_VEC3, time);
+        assert!(inst.has_inct(r(0.0)).is_none());
+        let inct = inst.inct(r(0.5)).unwrap();
+        assert!(inct.t.relative_eq(&9.0, 1e-9, 1e-9));
+        assert!(inst.has_inct(r(1.0)).is_none());
+    }
+
+    #[test]
+    fn animated_rotation_bounding() {
+        let radius = 0.01;
+        let cen = vec3(3.0, 0.0, 0.0);
+        let sph: Arc<Entity + Send> = Arc::new(sphere::Sphere::new(
+            cen,
+            radius,
+            Box::new(|_, _, ly, _, _| DiffuseLight::new(ly, WHITE)),
+        ));
+        let one = vec3(1.0, 1.0, 1.0);
+        let track = KeyframeTrack::new(vec![
+            Keyframe::new(0.0, ZERO_VEC3, Quatf::from_angle_y(Deg(0.0)), one),
+            Keyframe::new(1.0, ZERO_VEC3, Quatf::from_angle_y(Deg(170.0)), one),
+        ]);
+        let inst = AnimatedInstanceEntity::new(sph, track.clone());
+
+        let b = inst.bounding();
+        let (lower, upper) = (*b.get_lower(), *b.get_upper());
+        for i in 0..=1000 {
+            let p = (track.at(i as Real / 1000.0) * cen.extend(1.0)).truncate();
+            for k in 0..3 {
+                assert!(lower[k] <= p[k] - radius && p[k] + radius <= upper[k]);
+            }
+        }
+    }
+}
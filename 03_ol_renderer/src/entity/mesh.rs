@@ -0,0 +1,356 @@
+//! Triangle mesh entity
+
+use arena::Arena;
+use entity::*;
+use material::*;
+use math::{model::AABB, *};
+use std::cmp::Ordering;
+
+/// Vertex and index buffers of a triangle mesh.
+///
+/// Every attribute buffer is either empty or has exactly one element per position.
+/// Missing normals fall back to flat shading, missing uvs to barycentric coordinates,
+/// missing tangents to `dp/du` of each face and missing colors to white.
+#[derive(Clone, Default)]
+pub struct MeshData {
+    pub positions: Vec<Vec3f>,
+    pub normals: Vec<Vec3f>,
+    pub uvs: Vec<Vec2f>,
+    pub tangents: Vec<Vec3f>,
+    pub colors: Vec<Color3f>,
+    pub indices: Vec<[u32; 3]>,
+}
+
+impl MeshData {
+    fn is_valid(&self) -> bool {
+        let n = self.positions.len();
+        let attr_ok = |len: usize| len == 0 || len == n;
+        attr_ok(self.normals.len())
+            && attr_ok(self.uvs.len())
+            && attr_ok(self.tangents.len())
+            && attr_ok(self.colors.len())
+            && self
+                .indices
+                .iter()
+                .all(|f| f.iter().all(|&i| (i as usize) < n))
+    }
+
+    pub fn face_count(&self) -> usize {
+        self.indices.len()
+    }
+
+    fn face_vtx(&self, face: usize) -> [Vec3f; 3] {
+        let f = &self.indices[face];
+        [
+            self.positions[f[0] as usize],
+            self.positions[f[1] as usize],
+            self.positions[f[2] as usize],
+        ]
+    }
+
+    fn face_bounding(&self, face: usize) -> AABB {
+        let vtx = self.face_vtx(face);
+        AABB::from_point(vtx[0])
+            .union_point(vtx[1])
+            .union_point(vtx[2])
+    }
+
+    fn interpolate<T>(&self, buf: &[T], face: usize, beta: Real, gamma: Real) -> T
+    where
+        T: Copy + ::std::ops::Mul<Real, Output = T> + ::std::ops::Add<Output = T>,
+    {
+        let f = &self.indices[face];
+        buf[f[0] as usize] * (1.0 - beta - gamma)
+            + buf[f[1] as usize] * beta
+            + buf[f[2] as usize] * gamma
+    }
+
+    /// dp/du and dp/dv of a face, or None when the face has no (or degenerate) uvs
+    fn face_dpduv(&self, face: usize) -> Option<(Vec3f, Vec3f)> {
+        if self.uvs.is_empty() {
+            return None;
+        }
+        let f = &self.indices[face];
+        let vtx = self.face_vtx(face);
+        let duv1 = self.uvs[f[1] as usize] - self.uvs[f[0] as usize];
+        let duv2 = self.uvs[f[2] as usize] - self.uvs[f[0] as usize];
+        let det = duv1.x * duv2.y - duv1.y * duv2.x;
+        if det.abs() < 1e-12 {
+            return None;
+        }
+        let (e1, e2) = (vtx[1] - vtx[0], vtx[2] - vtx[0]);
+        Some(((duv2.y * e1 - duv1.y * e2) / det, (duv1.x * e2 - duv2.x * e1) / det))
+    }
+}
+
+/// Triangle mesh sharing one set of vertex buffers among all faces.
+///
+/// The material closure receives `(position, local_x, local_y, u, v, color)`.
+/// The first five are the same as in `entity::sphere::Sphere`, where `local_y` is
+/// the shading normal and `local_x` the tangent orthogonalized against it.
+/// `color` is the interpolated vertex color.
+pub struct TriangleMesh<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real, Color3f) -> M,
+{
+    data: MeshData,
+    tree: BvhTree,
+    /// Cumulative face areas, for sampling faces proportionally to their areas
+    area_cdf: Vec<Real>,
+    fm: Box<FM>,
+}
+
+impl<M, FM> TriangleMesh<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real, Color3f) -> M,
+{
+    pub fn new(data: MeshData, fm: Box<FM>) -> Self {
+        assert!(data.is_valid());
+        let boundings: Vec<AABB> = (0..data.face_count())
+            .map(|f| data.face_bounding(f))
+            .collect();
+        let mut area = 0.0;
+        let area_cdf = (0..data.face_count())
+            .map(|f| {
+                let vtx = data.face_vtx(f);
+                area += 0.5 * (vtx[1] - vtx[0]).cross(vtx[2] - vtx[0]).magnitude();
+                area
+            })
+            .collect();
+        TriangleMesh {
+            tree: BvhTree::new(&boundings),
+            area_cdf,
+            data,
+            fm,
+        }
+    }
+
+    pub fn get_data(&self) -> &MeshData {
+        &self.data
+    }
+
+    // `Option::is_none_or` needs Rust 1.82
+    #[allow(clippy::unnecessary_map_or)]
+    fn nearest_face_inct(&self, r: &Ray) -> Option<(usize, model::TriangleIntersection)> {
+        let mut ret: Option<(usize, model::TriangleIntersection)> = None;
+        let wr = model::WatertightRay::new(r);
+        self.tree.traverse(r, |face| {
+            let inct = model::Triangle::new(self.data.face_vtx(face)).watertight_inct(&wr)?;
+            let t = inct.t;
+            if ret.as_ref().map_or(true, |v| t < v.1.t) {
+                ret = Some((face, inct));
+            }
+            Some(t)
+        });
+        ret
+    }
+}
+
+impl<M, FM> Surface for TriangleMesh<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real, Color3f) -> M,
+{
+    fn area(&self) -> Real {
+        self.area_cdf.last().cloned().unwrap_or(0.0)
+    }
+
+    fn sample_area(&self, u: Vec2f, _time: Real) -> (Vec3f, Vec3f) {
+        // Pick the first face whose cumulative area exceeds `a`, and reuse `u.x` within it
+        let a = u.x * self.area();
+        let face = self
+            .area_cdf
+            .binary_search_by(|&c| if c <= a { Ordering::Less } else { Ordering::Greater })
+            .unwrap_or_else(|i| i)
+            .min(self.area_cdf.len() - 1);
+        let lower = if face == 0 { 0.0 } else { self.area_cdf[face - 1] };
+        let face_area = self.area_cdf[face] - lower;
+        let ux = if face_area > 0.0 {
+            ((a - lower) / face_area).min(1.0)
+        } else {
+            0.0
+        };
+
+        let (beta, gamma) = triangle_uniform(vec2(ux, u.y));
+        let vtx = self.data.face_vtx(face);
+        let (e1, e2) = (vtx[1] - vtx[0], vtx[2] - vtx[0]);
+        (vtx[0] + beta * e1 + gamma * e2, e1.cross(e2).normalize())
+    }
+}
+
+impl<M, FM> MaterialSource for TriangleMesh<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real, Color3f) -> M,
+{
+    fn build_material<'b>(&self, a: &MaterialArgs, arena: &'b Arena) -> &'b BxDF {
+        arena.alloc_owned((self.fm)(a.position, a.local_x, a.local_y, a.uv.x, a.uv.y, a.color))
+    }
+}
+
+impl<M, FM> Entity for TriangleMesh<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real, Color3f) -> M,
+{
+    fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
+        let (face, inct) = self.nearest_face_inct(&r)?;
+        let (beta, gamma) = (inct.beta, inct.gamma);
+        let vtx = self.data.face_vtx(face);
+        let (p, p_error) = model::Triangle::new(vtx).inct_point(&inct);
+
+        // Geometric normal follows the winding order, or the vertex normals if any.
+        // It does not depend on the ray: like for spheres, materials decide
+        // whether the back side is lit.
+        let mut geo_normal = (vtx[1] - vtx[0]).cross(vtx[2] - vtx[0]).normalize();
+        let front_face = dot(geo_normal, r.d) < 0.0;
+        let normal = if self.data.normals.is_empty() {
+            geo_normal
+        } else {
+            let n = self.data.interpolate(&self.data.normals, face, beta, gamma).normalize();
+            if dot(n, geo_normal) < 0.0 {
+                geo_normal = -geo_normal;
+            }
+            n
+        };
+
+        let uv = if self.data.uvs.is_empty() {
+            vec2(beta, gamma)
+        } else {
+            self.data.interpolate(&self.data.uvs, face, beta, gamma)
+        };
+        let (dpdu, dpdv) = self
+            .data
+            .face_dpduv(face)
+            .unwrap_or((vtx[1] - vtx[0], vtx[2] - vtx[0]));
+
+        let tangent = if self.data.tangents.is_empty() {
+            dpdu
+        } else {
+            self.data.interpolate(&self.data.tangents, face, beta, gamma)
+        };
+        let tangent = tangent - dot(tangent, normal) * normal;
+        let local_x = if tangent.magnitude2() > 1e-12 {
+            tangent.normalize()
+        } else {
+            perpendicular_vec3(normal)
+        };
+
+        let color = if self.data.colors.is_empty() {
+            WHITE
+        } else {
+            self.data.interpolate(&self.data.colors, face, beta, gamma)
+        };
+
+        Some(Intersection {
+            t: inct.t,
+            position: p,
+            p_error,
+            normal,
+            geo_normal,
+            uv,
+            dpdu,
+            dpdv,
+            front_face,
+            prim_id: face,
+            entity_id: None,
+            material: DeferredMaterial::new(
+                self,
+                MaterialArgs {
+                    color,
+                    ..MaterialArgs::new(p, local_x, normal, uv)
+                },
+            ),
+        })
+    }
+
+    fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        let mut ret: Option<Real> = None;
+        let wr = model::WatertightRay::new(&r);
+        self.tree.traverse(&r, |face| {
+            let t = model::Triangle::new(self.data.face_vtx(face)).watertight_inct(&wr)?.t;
+            ret = Some(ret.map_or(t, |v| v.min(t)));
+            Some(t)
+        });
+        ret.map(|t| (t, r.t_to_point(t)))
+    }
+
+    fn bounding(&self) -> AABB {
+        self.tree.bounding()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arena::Arena;
+
+    /// Unit quad on the xz-plane with normals tilted towards +x on one side
+    fn quad() -> MeshData {
+        MeshData {
+            positions: vec![
+                vec3(0.0, 0.0, 0.0),
+                vec3(1.0, 0.0, 0.0),
+                vec3(1.0, 0.0, 1.0),
+                vec3(0.0, 0.0, 1.0),
+            ],
+            normals: vec![
+                vec3(0.0, 1.0, 0.0),
+                vec3(1.0, 1.0, 0.0).normalize(),
+                vec3(1.0, 1.0, 0.0).normalize(),
+                vec3(0.0, 1.0, 0.0),
+            ],
+            uvs: vec![
+                vec2(0.0, 0.0),
+                vec2(1.0, 0.0),
+                vec2(1.0, 1.0),
+                vec2(0.0, 1.0),
+            ],
+            tangents: vec![],
+            colors: vec![],
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+        }
+    }
+
+    #[test]
+    fn smooth_shading() {
+        let mesh = TriangleMesh::new(
+            quad(),
+            Box::new(|_, lx: Vec3f, ly: Vec3f, u, v, _| {
+                assert!(dot(lx, ly).abs() < 1e-9);
+                assert!((lx.magnitude() - 1.0).abs() < 1e-9);
+                DiffuseLight::new(ly, color3(u, v, 0.0))
+            }),
+        );
+
+        let r = Ray::new(vec3(0.5, 1.0, 0.25), vec3(0.0, -1.0, 0.0));
+        let inct = mesh.inct(r.clone()).unwrap();
+        assert!(inct.t.relative_eq(&1.0, 1e-9, 1e-9));
+        assert!(inct.position.relative_eq(&vec3(0.5, 0.0, 0.25), 1e-9, 1e-9));
+
+        let tilted: Vec3f = vec3(1.0, 1.0, 0.0).normalize();
+        let expected_n = (0.5 * Y_VEC3 + 0.5 * tilted).normalize();
+        assert!(inct.normal.relative_eq(&expected_n, 1e-9, 1e-9));
+
+        let uv = inct.material.build(&Arena::new()).emit(inct.normal);
+        assert!(uv.relative_eq(&color3(0.5, 0.25, 0.0), 1e-9, 1e-9));
+        assert!(inct.uv.relative_eq(&vec2(0.5, 0.25), 1e-9, 1e-9));
+        assert!(inct.geo_normal.relative_eq(&Y_VEC3, 1e-9, 1e-9));
+        assert!(inct.dpdu.relative_eq(&X_VEC3, 1e-9, 1e-9));
+        assert!(inct.dpdv.relative_eq(&Z_VEC3, 1e-9, 1e-9));
+        assert_eq!(inct.prim_id, 0);
+        assert!(!inct.front_face);
+
+        // Hit from below: the normals keep pointing up, following the vertex normals
+        let r = Ray::new(vec3(0.25, -1.0, 0.5), vec3(0.0, 1.0, 0.0));
+        let inct = mesh.inct(r).unwrap();
+        assert!(inct.normal.y > 0.0);
+        assert!(inct.geo_normal.relative_eq(&Y_VEC3, 1e-9, 1e-9));
+        assert_eq!(inct.prim_id, 1);
+        assert!(inct.front_face);
+
+        assert!(mesh.has_inct(Ray::new(vec3(2.0, 1.0, 0.5), vec3(0.0, -1.0, 0.0))).is_none());
+    }
+}
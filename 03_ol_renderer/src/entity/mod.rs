@@ -1,25 +1,143 @@
 //! Entities in scene
 
+pub mod bvh;
+pub mod cone;
+pub mod csg;
+pub mod cylinder;
+pub mod disk;
+pub mod instance;
+pub mod mesh;
+pub mod planar;
+pub mod plane;
+pub mod rectangle;
 pub mod sphere;
+pub mod torus;
 pub mod triangle;
 
 pub mod prelude {
+    pub use super::bvh::*;
+    pub use super::cone::*;
+    pub use super::csg::*;
+    pub use super::cylinder::*;
+    pub use super::disk::*;
+    pub use super::instance::*;
+    pub use super::mesh::*;
+    pub use super::planar::*;
+    pub use super::plane::*;
+    pub use super::rectangle::*;
     pub use super::sphere::*;
+    pub use super::torus::*;
     pub use super::triangle::*;
+    use arena::Arena;
     use material::*;
-    use math::*;
+    use math::{model::AABB, *};
+    use std::sync::Arc;
 
-    /// Intersection between a ray and an entity. Once the intersection
-    /// computed, the entity becomes totally useless.
-    pub struct Intersection {
+    /// Arguments of a material closure. `position` and `uv` are those of the entity
+    /// calling it, while the shading frame (`local_x`, `local_y`) is in world space,
+    /// so that BxDFs work with world space directions.
+    #[derive(Clone, Copy)]
+    pub struct MaterialArgs {
+        pub position: Vec3f,
+        pub local_x: Vec3f,
+        pub local_y: Vec3f,
+        pub uv: Vec2f,
+        /// Vertex color, white for entities without one
+        pub color: Color3f,
+    }
+
+    impl MaterialArgs {
+        pub fn new(position: Vec3f, local_x: Vec3f, local_y: Vec3f, uv: Vec2f) -> MaterialArgs {
+            MaterialArgs {
+                position,
+                local_x,
+                local_y,
+                uv,
+                color: WHITE,
+            }
+        }
+    }
+
+    /// Entities building materials from their closures
+    pub trait MaterialSource {
+        fn build_material<'b>(&self, args: &MaterialArgs, arena: &'b Arena) -> &'b BxDF;
+    }
+
+    /// Material closures taking `(position, local_x, local_y, u, v)`
+    impl<M, FM> MaterialSource for FM
+    where
+        M: BxDF + 'static,
+        FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+    {
+        fn build_material<'b>(&self, a: &MaterialArgs, arena: &'b Arena) -> &'b BxDF {
+            arena.alloc_owned(self(a.position, a.local_x, a.local_y, a.uv.x, a.uv.y))
+        }
+    }
+
+    /// Material of an intersection, which is only built by `build`.
+    ///
+    /// Most intersections found during a traversal are discarded for nearer ones,
+    /// so they shall not pay for allocating a BxDF.
+    pub struct DeferredMaterial<'a> {
+        source: &'a MaterialSource,
+        args: MaterialArgs,
+    }
+
+    impl<'a> DeferredMaterial<'a> {
+        pub fn new(source: &'a MaterialSource, args: MaterialArgs) -> DeferredMaterial<'a> {
+            DeferredMaterial { source, args }
+        }
+
+        /// The same material with another shading frame, e.g. one transformed
+        /// out of an instance
+        pub fn with_frame(self, local_x: Vec3f, local_y: Vec3f) -> DeferredMaterial<'a> {
+            DeferredMaterial {
+                args: MaterialArgs {
+                    local_x,
+                    local_y,
+                    ..self.args
+                },
+                ..self
+            }
+        }
+
+        pub fn get_args(&self) -> &MaterialArgs {
+            &self.args
+        }
+
+        pub fn build<'b>(&self, arena: &'b Arena) -> &'b BxDF {
+            self.source.build_material(&self.args, arena)
+        }
+    }
+
+    /// Intersection between a ray and an entity, borrowing the entity
+    /// until its material is built.
+    pub struct Intersection<'a> {
         pub t: Real,
         pub position: Vec3f,
+        /// Bound of the absolute rounding error of `position`, see `offset_ray_origin`
+        pub p_error: Vec3f,
+        /// Shading normal
         pub normal: Vec3f,
-        pub material: Box<BxDF>,
+        /// Normal of the actual surface, on the same side as `normal`
+        pub geo_normal: Vec3f,
+        pub uv: Vec2f,
+        /// Partial derivatives of `position` with respect to `uv`
+        pub dpdu: Vec3f,
+        pub dpdv: Vec3f,
+        /// Does the ray hit the outside of a closed surface, or the side an
+        /// open surface is oriented to (e.g. the counter-clockwise side of a triangle)
+        pub front_face: bool,
+        /// Index of the hit primitive in its entity, e.g. the face of a `TriangleMesh`
+        pub prim_id: usize,
+        /// Index of the hit entity in the innermost `BvhEntity` or entity list
+        /// (see `nearest_inct`) containing it
+        pub entity_id: Option<usize>,
+        pub material: DeferredMaterial<'a>,
     }
 
-    impl Intersection {
-        pub fn nearer(self, other: Intersection) -> Intersection {
+    impl<'a> Intersection<'a> {
+        pub fn nearer(self, other: Intersection<'a>) -> Intersection<'a> {
             assert!(self.t >= 0.0 && other.t >= 0.0);
             if self.t < other.t {
                 self
@@ -29,6 +147,21 @@ pub mod prelude {
         }
     }
 
+    /// Nearest intersection of `r` with any of `entities`
+    pub fn nearest_inct(entities: &[Box<Entity>], r: Ray) -> Option<Intersection<'_>> {
+        // Only look for hits nearer than the nearest one so far
+        let mut near_r = r;
+        let mut ret: Option<Intersection> = None;
+        for (i, ent) in entities.iter().enumerate() {
+            if let Some(mut inct) = ent.inct(near_r.clone()) {
+                near_r.t_max = inct.t;
+                inct.entity_id.get_or_insert(i);
+                ret = Some(inct);
+            }
+        }
+        ret
+    }
+
     /// (Renderable) Entities in scene.
     ///
     /// # Entity
@@ -37,9 +170,85 @@ pub mod prelude {
     /// An entity can also be an BVH tree of other entities,
     /// an visivle light source, or anything else that can be in the rendered scene.
     pub trait Entity: Sync {
-        fn inct(&self, r: Ray) -> Option<Intersection>;
+        fn inct(&self, r: Ray) -> Option<Intersection<'_>>;
 
         fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)>;
+
+        /// Axis-aligned bounding box of the entity in world space
+        fn bounding(&self) -> AABB;
+    }
+
+    /// Entity shared with other owners, e.g. an `AreaLight`
+    impl<E: Entity + Send + ?Sized> Entity for Arc<E> {
+        fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
+            (**self).inct(r)
+        }
+
+        fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+            (**self).has_inct(r)
+        }
+
+        fn bounding(&self) -> AABB {
+            (**self).bounding()
+        }
+    }
+
+    /// Entities whose surface can be sampled, so that they are usable as area lights.
+    ///
+    /// The densities of the `*_solid_angle` methods are with respect to the solid
+    /// angle at the point the surface is seen from. By default, points are sampled
+    /// uniformly by area.
+    pub trait Surface: Entity {
+        /// Total area of the surface
+        fn area(&self) -> Real;
+
+        /// Point uniformly distributed over the surface at `time` given `u` in
+        /// `[0, 1)^2`, and the normal there
+        fn sample_area(&self, u: Vec2f, time: Real) -> (Vec3f, Vec3f);
+
+        /// Point on the surface at `time` seen from `dst_pnt`, and its density
+        fn sample_solid_angle(
+            &self,
+            u: Vec2f,
+            dst_pnt: Vec3f,
+            time: Real,
+        ) -> Option<(Vec3f, Real)> {
+            let (p, n) = self.sample_area(u, time);
+            let d = p - dst_pnt;
+            let cos = dot(n, d).abs() / d.magnitude();
+            if cos <= 0.0 {
+                return None;
+            }
+            Some((p, d.magnitude2() / (cos * self.area())))
+        }
+
+        /// Density of `sample_solid_angle` at the first point of the surface hit
+        /// by `r`, seen from `r.p`. Zero when `r` misses the surface.
+        fn pdf_solid_angle(&self, r: Ray) -> Real {
+            match self.inct(r.clone()) {
+                Some(inct) => {
+                    let cos = dot(inct.geo_normal, r.d).abs();
+                    if cos <= 0.0 {
+                        0.0
+                    } else {
+                        inct.t * inct.t / (cos * self.area())
+                    }
+                }
+                None => 0.0,
+            }
+        }
+    }
+
+    /// Entities enclosing a volume, which can be combined by `CsgEntity`.
+    pub trait Solid: Entity {
+        /// Sorted, disjoint spans `(t_in, t_out)` of the whole line `r.p + t * r.d`
+        /// inside the solid. Spans may start or end behind the ray origin.
+        fn inct_intervals(&self, r: Ray) -> Vec<(Real, Real)>;
+
+        /// Intersection at `r.t_to_point(t)`, where `t` is an end of a span returned
+        /// by `inct_intervals`. The normal points out of the solid, or into it
+        /// when `flip` is set.
+        fn inct_at(&self, r: Ray, t: Real, flip: bool) -> Intersection<'_>;
     }
 
 }
@@ -0,0 +1,128 @@
+//! Flat entities, e.g. planes, disks and rectangles
+
+use entity::*;
+use material::*;
+use math::{model::AABB, *};
+
+/// Region of a plane bounding a `PlanarEntity`
+pub trait PlanarShape: Sync {
+    fn get_plane(&self) -> &model::Plane;
+
+    /// Does point `p` of the plane lie within the shape
+    fn contains(&self, p: Vec3f) -> bool;
+
+    fn to_aabb_bounding(&self) -> AABB;
+
+    fn inct_to_uv(&self, p: Vec3f) -> (Real, Real);
+
+    /// Partial derivatives of the point with respect to `inct_to_uv`
+    fn inct_to_dpduv(&self, p: Vec3f) -> (Vec3f, Vec3f);
+
+    fn inct_to_local_x(&self, p: Vec3f) -> Vec3f;
+}
+
+/// Entity covering a region of a plane.
+///
+/// The surface is two-sided: `local_y` passed to the material closure faces the
+/// incoming ray.
+pub struct PlanarEntity<S, M, FM>
+where
+    S: PlanarShape,
+    M: BxDF + 'static,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    shape: S,
+    fm: Box<FM>,
+}
+
+impl<S, M, FM> PlanarEntity<S, M, FM>
+where
+    S: PlanarShape,
+    M: BxDF + 'static,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    pub fn from_shape(shape: S, fm: Box<FM>) -> Self {
+        PlanarEntity { shape, fm }
+    }
+
+    pub fn get_shape(&self) -> &S {
+        &self.shape
+    }
+
+    fn nearest_t(&self, r: &Ray) -> Option<Real> {
+        let t = self.shape.get_plane().nearest_t(r)?;
+        if self.shape.contains(r.t_to_point(t)) {
+            Some(t)
+        } else {
+            None
+        }
+    }
+}
+
+impl<S, M, FM> Entity for PlanarEntity<S, M, FM>
+where
+    S: PlanarShape,
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
+        let t = self.nearest_t(&r)?;
+        let plane = self.shape.get_plane();
+        let (p, p_error) = plane.inct_point(&r, t);
+        let local_y = plane.inct_to_local_y(p);
+        let front_face = dot(local_y, r.d) < 0.0;
+        let local_y = if front_face { local_y } else { -local_y };
+        let local_x = self.shape.inct_to_local_x(p);
+        let (u, v) = self.shape.inct_to_uv(p);
+        let (dpdu, dpdv) = self.shape.inct_to_dpduv(p);
+        let args = MaterialArgs::new(p, local_x, local_y, vec2(u, v));
+        Some(Intersection {
+            t,
+            position: p,
+            p_error,
+            normal: local_y,
+            geo_normal: local_y,
+            uv: vec2(u, v),
+            dpdu,
+            dpdv,
+            front_face,
+            prim_id: 0,
+            entity_id: None,
+            material: DeferredMaterial::new(&*self.fm, args),
+        })
+    }
+
+    fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        let t = self.nearest_t(&r)?;
+        Some((t, r.t_to_point(t)))
+    }
+
+    fn bounding(&self) -> AABB {
+        self.shape.to_aabb_bounding()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn two_sided() {
+        let light = |_, _, ly, _, _| DiffuseLight::new(ly, WHITE);
+        let disk = disk::Disk::new(ZERO_VEC3, Y_VEC3, 1.0, Box::new(light));
+        let rect = rectangle::Rectangle::new(ZERO_VEC3, X_VEC3, Z_VEC3, Box::new(light));
+        let entities: [&Entity; 2] = [&disk, &rect];
+        for e in &entities {
+            let inct = e.inct(Ray::new(vec3(0.5, -2.0, 0.5), Y_VEC3)).unwrap();
+            assert!(inct.t.relative_eq(&2.0, 1e-9, 1e-9));
+            assert!(inct.normal.relative_eq(&-Y_VEC3, 1e-9, 1e-9));
+            assert!(e.has_inct(Ray::new(vec3(1.5, 2.0, 1.5), -Y_VEC3)).is_none());
+        }
+        let inct = disk.inct(Ray::new(vec3(0.5, 2.0, 0.0), -Y_VEC3)).unwrap();
+        assert!(inct.normal.relative_eq(&Y_VEC3, 1e-9, 1e-9));
+        assert!(inct.front_face);
+        // The rectangle is oriented to edge_u x edge_v = -y
+        let inct = rect.inct(Ray::new(vec3(0.5, 2.0, 0.5), -Y_VEC3)).unwrap();
+        assert!(!inct.front_face);
+    }
+}
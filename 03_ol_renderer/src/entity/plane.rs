@@ -0,0 +1,44 @@
+//! Plane entity
+
+use entity::*;
+use material::*;
+use math::{model::AABB, *};
+
+/// Infinite plane. Its bounding box is unbounded, so keep it out of `BvhEntity`.
+pub type Plane<M, FM> = PlanarEntity<model::Plane, M, FM>;
+
+impl PlanarShape for model::Plane {
+    fn get_plane(&self) -> &model::Plane {
+        self
+    }
+
+    fn contains(&self, _p: Vec3f) -> bool {
+        true
+    }
+
+    fn to_aabb_bounding(&self) -> AABB {
+        self.to_aabb_bounding()
+    }
+
+    fn inct_to_uv(&self, p: Vec3f) -> (Real, Real) {
+        self.inct_to_uv(p)
+    }
+
+    fn inct_to_dpduv(&self, p: Vec3f) -> (Vec3f, Vec3f) {
+        self.inct_to_dpduv(p)
+    }
+
+    fn inct_to_local_x(&self, p: Vec3f) -> Vec3f {
+        self.inct_to_local_x(p)
+    }
+}
+
+impl<M, FM> Plane<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    pub fn new(point: Vec3f, normal: Vec3f, fm: Box<FM>) -> Self {
+        PlanarEntity::from_shape(model::Plane::new(point, normal), fm)
+    }
+}
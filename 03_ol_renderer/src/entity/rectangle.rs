@@ -0,0 +1,44 @@
+//! Rectangle entity
+
+use entity::*;
+use material::*;
+use math::{model::AABB, *};
+
+/// Parallelogram `corner + u * edge_u + v * edge_v` for `u, v` in `[0, 1]`
+pub type Rectangle<M, FM> = PlanarEntity<model::Rectangle, M, FM>;
+
+impl PlanarShape for model::Rectangle {
+    fn get_plane(&self) -> &model::Plane {
+        self.get_plane()
+    }
+
+    fn contains(&self, p: Vec3f) -> bool {
+        self.contains(p)
+    }
+
+    fn to_aabb_bounding(&self) -> AABB {
+        self.to_aabb_bounding()
+    }
+
+    fn inct_to_uv(&self, p: Vec3f) -> (Real, Real) {
+        self.inct_to_uv(p)
+    }
+
+    fn inct_to_dpduv(&self, p: Vec3f) -> (Vec3f, Vec3f) {
+        self.inct_to_dpduv(p)
+    }
+
+    fn inct_to_local_x(&self, p: Vec3f) -> Vec3f {
+        self.inct_to_local_x(p)
+    }
+}
+
+impl<M, FM> Rectangle<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    pub fn new(corner: Vec3f, edge_u: Vec3f, edge_v: Vec3f, fm: Box<FM>) -> Self {
+        PlanarEntity::from_shape(model::Rectangle::new(corner, edge_u, edge_v), fm)
+    }
+}
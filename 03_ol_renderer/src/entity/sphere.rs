@@ -4,51 +4,204 @@ use entity::*;
 use material::*;
 use math::*;
 
+/// Sphere entity
+///
+/// A moving sphere has its centre at `sph.get_centre() + time * velocity`
+/// for rays cast at `time`, which is clamped to `[0, 1]`.
 pub struct Sphere<M, FM>
 where
     M: BxDF + 'static,
-    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> Box<M>,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
 {
     sph: model::Sphere,
+    velocity: Vec3f,
     fm: Box<FM>,
 }
 
 impl<M, FM> Entity for Sphere<M, FM>
 where
     M: BxDF + 'static,
-    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> Box<M>,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
 {
-    fn inct(&self, r: Ray) -> Option<Intersection> {
-        if let Some((t, p)) = self.sph.nearest_inct(r) {
-            let local_y = self.sph.inct_to_local_y(p);
-            let local_x = self.sph.inct_to_local_x(p);
-            let (u, v) = self.sph.inct_to_uv(p);
-            let material = (self.fm)(p, local_x, local_y, u, v);
-            Some(Intersection {
-                t,
-                position: p,
-                normal: local_y,
-                material,
-            })
-        } else {
-            None
-        }
+    fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
+        let (t, _) = self.sph_at(r.time).nearest_inct(r.clone())?;
+        Some(self.inct_at(r, t, false))
     }
 
     fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
-        self.sph.nearest_inct(r)
+        self.sph_at(r.time).nearest_inct(r)
+    }
+
+    fn bounding(&self) -> model::AABB {
+        self.sph_at(0.0)
+            .to_aabb_bounding()
+            .union(&self.sph_at(1.0).to_aabb_bounding())
+    }
+}
+
+impl<M, FM> Solid for Sphere<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    fn inct_intervals(&self, r: Ray) -> Vec<(Real, Real)> {
+        self.sph_at(r.time).inct_intervals(&r)
+    }
+
+    fn inct_at(&self, r: Ray, t: Real, flip: bool) -> Intersection<'_> {
+        let sph = self.sph_at(r.time);
+        let (p, p_error) = sph.inct_point(&r, t);
+        let local_y = sph.inct_to_local_y(p);
+        let local_y = if flip { -local_y } else { local_y };
+        let local_x = sph.inct_to_local_x(p);
+        let (u, v) = sph.inct_to_uv(p);
+        let (dpdu, dpdv) = sph.inct_to_dpduv(p);
+        let args = MaterialArgs::new(p, local_x, local_y, vec2(u, v));
+        Intersection {
+            t,
+            position: p,
+            p_error,
+            normal: local_y,
+            geo_normal: local_y,
+            uv: vec2(u, v),
+            dpdu,
+            dpdv,
+            front_face: dot(local_y, r.d) < 0.0,
+            prim_id: 0,
+            entity_id: None,
+            material: DeferredMaterial::new(&*self.fm, args),
+        }
+    }
+}
+
+/// Seen from outside, points are sampled uniformly over the cone of directions
+/// subtended by the sphere.
+///
+/// See Pharr, M., Jakob, W., & Humphreys, G. (2016).
+/// Physically based rendering: From theory to implementation, 14.2.2.
+impl<M, FM> Surface for Sphere<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    fn area(&self) -> Real {
+        let r = self.sph.get_radius();
+        4.0 * REAL_PI * r * r
+    }
+
+    fn sample_area(&self, u: Vec2f, time: Real) -> (Vec3f, Vec3f) {
+        let y = 1.0 - 2.0 * u.x;
+        let r = (1.0 - y * y).max(0.0).sqrt();
+        let phi = 2.0 * REAL_PI * u.y;
+        let n = vec3(r * phi.cos(), y, r * phi.sin());
+        (self.sph_at(time).get_centre() + self.sph.get_radius() * n, n)
+    }
+
+    fn sample_solid_angle(&self, u: Vec2f, dst_pnt: Vec3f, time: Real) -> Option<(Vec3f, Real)> {
+        let sph = self.sph_at(time);
+        let (c, radius) = (*sph.get_centre(), sph.get_radius());
+        let dis2 = (c - dst_pnt).magnitude2();
+        if dis2 <= radius * radius {
+            let (p, n) = self.sample_area(u, time);
+            let d = p - dst_pnt;
+            let cos = dot(n, d).abs() / d.magnitude();
+            return if cos > 0.0 {
+                Some((p, d.magnitude2() / (cos * self.area())))
+            } else {
+                None
+            };
+        }
+
+        // Direction in the cone, then the point on the sphere it hits first
+        let dis = dis2.sqrt();
+        let sin2_max = radius * radius / dis2;
+        let cos_max = (1.0 - sin2_max).max(0.0).sqrt();
+        let cos_theta = 1.0 - u.x * (1.0 - cos_max);
+        let sin2_theta = (1.0 - cos_theta * cos_theta).max(0.0);
+        let phi = 2.0 * REAL_PI * u.y;
+
+        let ds = dis * cos_theta - (radius * radius - dis2 * sin2_theta).max(0.0).sqrt();
+        let cos_alpha = (dis2 + radius * radius - ds * ds) / (2.0 * dis * radius);
+        let sin_alpha = (1.0 - cos_alpha * cos_alpha).max(0.0).sqrt();
+        // Frame with y pointing from the sphere to `dst_pnt`
+        let frame = local_frame(dst_pnt - c);
+        let n = frame * vec3(sin_alpha * phi.cos(), cos_alpha, sin_alpha * phi.sin());
+        Some((c + radius * n, 1.0 / (2.0 * REAL_PI * (1.0 - cos_max))))
+    }
+
+    fn pdf_solid_angle(&self, r: Ray) -> Real {
+        let sph = self.sph_at(r.time);
+        let dis2 = (sph.get_centre() - r.p).magnitude2();
+        let radius = sph.get_radius();
+        if dis2 <= radius * radius {
+            return match self.inct(r.clone()) {
+                Some(inct) => inct.t * inct.t / (dot(inct.geo_normal, r.d).abs() * self.area()),
+                None => 0.0,
+            };
+        }
+        if sph.nearest_inct(r).is_none() {
+            return 0.0;
+        }
+        let cos_max = (1.0 - radius * radius / dis2).max(0.0).sqrt();
+        1.0 / (2.0 * REAL_PI * (1.0 - cos_max))
     }
 }
 
 impl<M, FM> Sphere<M, FM>
 where
     M: BxDF + 'static,
-    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> Box<M>,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
 {
     pub fn new(cen: Vec3f, radius: Real, fm: Box<FM>) -> Self {
+        Sphere::new_moving(cen, cen, radius, fm)
+    }
+
+    /// Sphere moving linearly from `cen0` at time 0 to `cen1` at time 1.
+    /// It stays at `cen0` before, and at `cen1` after this interval.
+    pub fn new_moving(cen0: Vec3f, cen1: Vec3f, radius: Real, fm: Box<FM>) -> Self {
         Sphere {
-            sph: model::Sphere::new(cen, radius),
+            sph: model::Sphere::new(cen0, radius),
+            velocity: cen1 - cen0,
             fm,
         }
     }
+
+    fn sph_at(&self, time: Real) -> model::Sphere {
+        let mut sph = self.sph.clone();
+        if self.velocity != ZERO_VEC3 {
+            let time = time.clamp(0.0, 1.0);
+            sph.set_centre(self.sph.get_centre() + time * self.velocity);
+        }
+        sph
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn moving() {
+        let sph = Sphere::new_moving(
+            ZERO_VEC3,
+            vec3(0.0, 2.0, 0.0),
+            0.5,
+            Box::new(|_, _, ly, _, _| DiffuseLight::new(ly, WHITE)),
+        );
+        let b = sph.bounding();
+        assert_eq!(*b.get_lower(), vec3(-0.5, -0.5, -0.5));
+        assert_eq!(*b.get_upper(), vec3(0.5, 2.5, 0.5));
+
+        let r = |time| Ray::with_time(vec3(-5.0, 1.0, 0.0), X_VEC3, time);
+        assert!(sph.has_inct(r(0.0)).is_none());
+        let inct = sph.inct(r(0.5)).unwrap();
+        assert!(inct.t.relative_eq(&4.5, 1e-9, 1e-9));
+        assert!(inct.normal.relative_eq(&-X_VEC3, 1e-9, 1e-9));
+        assert!(sph.has_inct(r(1.0)).is_none());
+
+        // Shutters beyond [0, 1] see the sphere at rest, within its bounding box
+        let r = |time| Ray::with_time(vec3(-5.0, 2.0, 0.0), X_VEC3, time);
+        assert!(sph.has_inct(r(1.5)).is_some());
+        assert!(sph.has_inct(r(-0.5)).is_none());
+    }
 }
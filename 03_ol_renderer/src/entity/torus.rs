@@ -0,0 +1,83 @@
+//! Torus entity
+
+use entity::*;
+use material::*;
+use math::*;
+
+/// Torus entity
+///
+/// `local_y` passed to the material closure is the outward normal.
+pub struct Torus<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    torus: model::Torus,
+    fm: Box<FM>,
+}
+
+impl<M, FM> Entity for Torus<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
+        let (t, _) = self.torus.nearest_inct(r.clone())?;
+        Some(self.inct_at(r, t, false))
+    }
+
+    fn has_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        self.torus.nearest_inct(r)
+    }
+
+    fn bounding(&self) -> model::AABB {
+        self.torus.to_aabb_bounding()
+    }
+}
+
+impl<M, FM> Solid for Torus<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    fn inct_intervals(&self, r: Ray) -> Vec<(Real, Real)> {
+        self.torus.inct_intervals(&r)
+    }
+
+    fn inct_at(&self, r: Ray, t: Real, flip: bool) -> Intersection<'_> {
+        let (p, p_error) = self.torus.inct_point(&r, t);
+        let local_y = self.torus.inct_to_local_y(p);
+        let local_y = if flip { -local_y } else { local_y };
+        let local_x = self.torus.inct_to_local_x(p);
+        let (u, v) = self.torus.inct_to_uv(p);
+        let (dpdu, dpdv) = self.torus.inct_to_dpduv(p);
+        let args = MaterialArgs::new(p, local_x, local_y, vec2(u, v));
+        Intersection {
+            t,
+            position: p,
+            p_error,
+            normal: local_y,
+            geo_normal: local_y,
+            uv: vec2(u, v),
+            dpdu,
+            dpdv,
+            front_face: dot(local_y, r.d) < 0.0,
+            prim_id: 0,
+            entity_id: None,
+            material: DeferredMaterial::new(&*self.fm, args),
+        }
+    }
+}
+
+impl<M, FM> Torus<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    pub fn new(centre: Vec3f, major_radius: Real, minor_radius: Real, fm: Box<FM>) -> Self {
+        Torus {
+            torus: model::Torus::new(centre, major_radius, minor_radius),
+            fm,
+        }
+    }
+}
@@ -7,7 +7,7 @@ use math::*;
 pub struct Triangle<M, FM>
 where
     M: BxDF + 'static,
-    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> Box<M>,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
 {
     tri: model::Triangle,
     fm: Box<FM>
@@ -16,17 +16,28 @@ where
 impl<M, FM> Entity for Triangle<M, FM>
 where
     M: BxDF + 'static,
-    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> Box<M>,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
 {
-    fn inct(&self, r: Ray) -> Option<Intersection> {
+    fn inct(&self, r: Ray) -> Option<Intersection<'_>> {
         if let Some(inct) = self.tri.nearest_inct(r.clone()) {
-            let p = r.t_to_point(inct.t);
+            let (p, p_error) = self.tri.inct_point(&inct);
             let n = self.tri.normal(&r);
+            let (dpdu, dpdv) = (self.tri[1] - self.tri[0], self.tri[2] - self.tri[0]);
+            let local_x = dpdu.normalize();
+            let uv = vec2(inct.beta, inct.gamma);
             Some(Intersection {
                 t: inct.t,
                 position: p,
+                p_error,
                 normal: n,
-                material: (self.fm)(p, ZERO_VEC3, n, inct.beta, inct.gamma),
+                geo_normal: n,
+                uv,
+                dpdu,
+                dpdv,
+                front_face: dot(dpdu.cross(dpdv), r.d) < 0.0,
+                prim_id: 0,
+                entity_id: None,
+                material: DeferredMaterial::new(&*self.fm, MaterialArgs::new(p, local_x, n, uv)),
             })
         } else {
             None
@@ -39,12 +50,32 @@ where
             None => None
         }
     }
+
+    fn bounding(&self) -> model::AABB {
+        self.tri.to_aabb_bounding()
+    }
+}
+
+impl<M, FM> Surface for Triangle<M, FM>
+where
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
+{
+    fn area(&self) -> Real {
+        0.5 * (self.tri[1] - self.tri[0]).cross(self.tri[2] - self.tri[0]).magnitude()
+    }
+
+    fn sample_area(&self, u: Vec2f, _time: Real) -> (Vec3f, Vec3f) {
+        let (beta, gamma) = triangle_uniform(u);
+        let (e1, e2) = (self.tri[1] - self.tri[0], self.tri[2] - self.tri[0]);
+        (self.tri[0] + beta * e1 + gamma * e2, e1.cross(e2).normalize())
+    }
 }
 
 impl<M, FM> Triangle<M, FM>
 where
     M: BxDF + 'static,
-    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> Box<M>,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real) -> M,
 {
     pub fn new(vtx: [Vec3f; 3], fm: Box<FM>) -> Self {
         Triangle {
@@ -0,0 +1,191 @@
+//! Area light source made of an emissive entity
+
+extern crate rand;
+
+use std::sync::Arc;
+
+use arena::Arena;
+use entity::*;
+use light::*;
+use math::*;
+
+/// Light emitted by the material of a surface, which shall also be added to the
+/// scene for the light to be visible and to cast shadows:
+///
+/// ```
+/// use renderer::*;
+/// use std::sync::Arc;
+///
+/// let bulb: Arc<Surface + Send> = Arc::new(sphere::Sphere::new(
+///     vec3(0.0, 2.0, 0.0),
+///     0.1,
+///     Box::new(|_, _, loc_y, _, _| DiffuseLight::new(loc_y, color3(8.0, 8.0, 8.0))),
+/// ));
+/// let entities: Vec<Box<Entity>> = vec![Box::new(bulb.clone())];
+/// let lights: Vec<Box<Light>> = vec![Box::new(AreaLight::new(bulb))];
+/// ```
+///
+/// The emitted radiance is that of the material built at the sampled point, as
+/// seen from where it is sampled to. Moving surfaces are sampled where they are
+/// at time 0.
+pub struct AreaLight {
+    surface: Arc<Surface + Send>,
+}
+
+impl Light for AreaLight {
+    fn sample<'a>(&self, n: u32, arena: &'a Arena) -> &'a [LightSample] {
+        arena.alloc_iter((0..n).map(|_| {
+            let (p, normal) = self.surface.sample_area(random_vec2(), 0.0);
+            let dir = local_frame(normal) * hemisphere_uniform();
+            LightSample {
+                light_normal: normal,
+                ray: Ray::new(p, dir),
+                color: self.emission(p + dir, p, arena).map_or(BLACK, |(c, _)| c),
+            }
+        }))
+    }
+
+    fn pdf(&self, _ray: Ray) -> Real {
+        1.0 / (2.0 * REAL_PI * self.surface.area())
+    }
+
+    fn sample_to<'a>(&self, n: u32, dst_pnt: Vec3f, arena: &'a Arena) -> &'a [LightSample] {
+        arena.alloc_iter((0..n).filter_map(|_| {
+            let (p, _) = self
+                .surface
+                .sample_solid_angle(random_vec2(), dst_pnt, 0.0)?;
+            let (color, light_normal) = self.emission(dst_pnt, p, arena)?;
+            Some(LightSample {
+                light_normal,
+                ray: Ray::new(p, dst_pnt - p),
+                color,
+            })
+        }))
+    }
+
+    fn pdf_to(&self, ray: Ray, dst_pnt: Vec3f) -> Real {
+        // Only the light point itself counts, not the surface behind it
+        let dis = (dst_pnt - ray.p).magnitude();
+        let back = Ray::new(dst_pnt, -ray.d).with_range(0.0, dis * (1.0 + SHADOW_EPSILON));
+        self.surface.pdf_solid_angle(back)
+    }
+}
+
+impl AreaLight {
+    pub fn new(surface: Arc<Surface + Send>) -> AreaLight {
+        AreaLight { surface }
+    }
+
+    pub fn get_surface(&self) -> &Arc<Surface + Send> {
+        &self.surface
+    }
+
+    /// Radiance from point `p` of the surface toward `from`, and the normal there.
+    /// None when another part of the surface is in between.
+    fn emission(&self, from: Vec3f, p: Vec3f, arena: &Arena) -> Option<(Color3f, Vec3f)> {
+        let dis = (p - from).magnitude();
+        let probe = Ray::new(from, p - from).with_range(0.0, dis * (1.0 + SHADOW_EPSILON));
+        let inct = self.surface.inct(probe.clone())?;
+        if inct.t < dis * (1.0 - SHADOW_EPSILON) {
+            return None;
+        }
+        let material = inct.material.build(arena);
+        Some((material.emit(-probe.d), inct.geo_normal))
+    }
+}
+
+fn random_vec2() -> Vec2f {
+    vec2(rand::random::<Real>(), rand::random::<Real>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use material::DiffuseLight;
+
+    /// Monte Carlo estimate of the irradiance at `p` with normal `n`
+    fn irradiance(light: &AreaLight, p: Vec3f, n: Vec3f) -> Real {
+        let cnt = 20000;
+        let arena = Arena::new();
+        light
+            .sample_to(cnt, p, &arena)
+            .iter()
+            .map(|sam| {
+                assert!(sam
+                    .ray
+                    .d
+                    .relative_eq(&(p - sam.ray.p).normalize(), 1e-9, 1e-9));
+                sam.color.x * dot(-sam.ray.d, n).max(0.0) / light.pdf_to(sam.ray.clone(), p)
+            })
+            .sum::<Real>()
+            / cnt as Real
+    }
+
+    /// Cosine-weighted solid angle of a polygon above `p`, by Lambert's formula
+    fn projected_solid_angle(p: Vec3f, n: Vec3f, vtx: &[Vec3f]) -> Real {
+        let mut ret = 0.0;
+        for i in 0..vtx.len() {
+            let (a, b) = (
+                (vtx[i] - p).normalize(),
+                (vtx[(i + 1) % vtx.len()] - p).normalize(),
+            );
+            ret += 0.5 * dot(a, b).acos() * dot(a.cross(b).normalize(), n);
+        }
+        ret.abs()
+    }
+
+    #[test]
+    fn sphere_light() {
+        let sph: Arc<Surface + Send> = Arc::new(sphere::Sphere::new(
+            vec3(0.0, 3.0, 0.0),
+            1.0,
+            Box::new(|_, _, ly, _, _| DiffuseLight::new(ly, WHITE)),
+        ));
+        let light = AreaLight::new(sph);
+        let e = irradiance(&light, ZERO_VEC3, Y_VEC3);
+        assert!((e - REAL_PI / 9.0).abs() < 0.01 * REAL_PI / 9.0);
+
+        // Samples are on the side facing the point, and only rays leaving the sphere count
+        let arena = Arena::new();
+        let sam = &light.sample_to(1, ZERO_VEC3, &arena)[0];
+        assert!(sam.light_normal.y < 0.0);
+        let on_light = Ray::new(vec3(0.0, 2.0, 0.0), -Y_VEC3);
+        assert!(light.pdf_to(on_light, ZERO_VEC3) > 0.0);
+        let below_light = Ray::new(vec3(0.0, 1.5, 0.0), -Y_VEC3);
+        assert_eq!(light.pdf_to(below_light, ZERO_VEC3), 0.0);
+    }
+
+    #[test]
+    fn polygon_lights() {
+        let vtx = [
+            vec3(-1.0, 1.0, -1.0),
+            vec3(1.0, 1.0, -1.0),
+            vec3(1.0, 1.0, 1.0),
+            vec3(-1.0, 1.0, 1.0),
+        ];
+        let p = vec3(0.3, 0.0, -0.2);
+        let n = vec3(0.2, 1.0, 0.0).normalize();
+
+        let tri: Arc<Surface + Send> = Arc::new(triangle::Triangle::new(
+            [vtx[0], vtx[1], vtx[2]],
+            Box::new(|_, _, ly, _, _| DiffuseLight::new(ly, WHITE)),
+        ));
+        let expected = projected_solid_angle(p, n, &vtx[..3]);
+        let e = irradiance(&AreaLight::new(tri), p, n);
+        assert!((e - expected).abs() < 0.02 * expected);
+
+        let quad = MeshData {
+            positions: vtx.to_vec(),
+            indices: vec![[0, 1, 2], [0, 2, 3]],
+            ..MeshData::default()
+        };
+        let mesh: Arc<Surface + Send> = Arc::new(TriangleMesh::new(
+            quad,
+            Box::new(|_, _, ly, _, _, _| DiffuseLight::new(ly, WHITE)),
+        ));
+        assert!((mesh.area() - 4.0).abs() < 1e-9);
+        let expected = projected_solid_angle(p, n, &vtx);
+        let e = irradiance(&AreaLight::new(mesh), p, n);
+        assert!((e - expected).abs() < 0.02 * expected);
+    }
+}
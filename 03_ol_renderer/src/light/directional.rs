@@ -0,0 +1,147 @@
+//! Directional light source at infinity, e.g. the sun
+
+extern crate rand;
+
+use arena::Arena;
+use light::*;
+use math::*;
+
+/// Light arriving from a direction, or from a disk of directions when the light
+/// has an angular diameter.
+///
+/// `color` is the irradiance on a surface facing the light, whatever its size.
+/// A light with an angular diameter casts soft shadows, and is visible to rays
+/// leaving the scene toward it.
+pub struct DirectionalLight {
+    /// Direction the light travels along
+    dir: Vec3f,
+    color: Color3f,
+    /// Cosine of the angular radius
+    cos_max: Real,
+}
+
+impl Light for DirectionalLight {
+    /// Rays are cast from the plane at `INFINITE_DISTANCE` through the world origin
+    fn sample<'a>(&self, n: u32, arena: &'a Arena) -> &'a [LightSample] {
+        arena.alloc_iter((0..n).map(|_| {
+            let dir = -self.sample_dir_to_light();
+            LightSample {
+                light_normal: self.dir,
+                ray: Ray::new(-INFINITE_DISTANCE * dir, dir),
+                color: self.color,
+            }
+        }))
+    }
+
+    fn pdf(&self, ray: Ray) -> Real {
+        self.pdf_to(ray, ZERO_VEC3)
+    }
+
+    fn sample_to<'a>(&self, n: u32, dst_pnt: Vec3f, arena: &'a Arena) -> &'a [LightSample] {
+        arena.alloc_iter((0..n).map(|_| {
+            let to_light = self.sample_dir_to_light();
+            LightSample {
+                light_normal: self.dir,
+                ray: Ray::new(dst_pnt + INFINITE_DISTANCE * to_light, -to_light),
+                color: self.radiance(),
+            }
+        }))
+    }
+
+    fn pdf_to(&self, ray: Ray, _dst_pnt: Vec3f) -> Real {
+        if self.is_delta() {
+            return 1.0;
+        }
+        if dot(ray.d, self.dir) < self.cos_max {
+            return 0.0;
+        }
+        cone_uniform_pdf(self.cos_max)
+    }
+
+    fn is_delta(&self) -> bool {
+        self.cos_max >= 1.0
+    }
+
+    fn emit(&self, r: &Ray) -> Color3f {
+        if self.is_delta() || dot(-r.d, self.dir) < self.cos_max {
+            return BLACK;
+        }
+        self.radiance()
+    }
+}
+
+impl DirectionalLight {
+    /// Light traveling along `dir`
+    pub fn new(dir: Vec3f, color: Color3f) -> DirectionalLight {
+        DirectionalLight {
+            dir: dir.normalize(),
+            color,
+            cos_max: 1.0,
+        }
+    }
+
+    /// Apparent diameter (in radians) of the light, about 0.0093 for the sun
+    pub fn set_angular_diameter(&mut self, angle: Real) -> &mut Self {
+        assert!((0.0..REAL_PI).contains(&angle));
+        self.cos_max = (0.5 * angle).cos();
+        self
+    }
+
+    pub fn get_dir(&self) -> Vec3f {
+        self.dir
+    }
+
+    /// Radiance of the light disk, or the irradiance of a delta light
+    fn radiance(&self) -> Color3f {
+        if self.is_delta() {
+            self.color
+        } else {
+            self.color * cone_uniform_pdf(self.cos_max)
+        }
+    }
+
+    /// Direction toward the light, uniformly distributed over its disk
+    fn sample_dir_to_light(&self) -> Vec3f {
+        if self.is_delta() {
+            return -self.dir;
+        }
+        let u = vec2(rand::random::<Real>(), rand::random::<Real>());
+        local_frame(-self.dir) * cone_uniform(self.cos_max, u)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn soft_sun() {
+        let mut sun = DirectionalLight::new(vec3(1.0, -1.0, 0.0), WHITE);
+        let arena = Arena::new();
+        let sam = &sun.sample_to(1, ZERO_VEC3, &arena)[0];
+        assert!(sam.ray.d.relative_eq(&sun.get_dir(), 1e-9, 1e-9));
+        assert!(sam
+            .ray
+            .p
+            .relative_eq(&(-INFINITE_DISTANCE * sun.get_dir()), 1e-3, 1e-9));
+        assert!(sun.emit(&Ray::new(ZERO_VEC3, -sun.get_dir())) == BLACK);
+
+        // Same irradiance on a surface facing the light, with any diameter
+        sun.set_angular_diameter(0.2);
+        let sams = sun.sample_to(10000, ZERO_VEC3, &arena);
+        let n = -sun.get_dir();
+        let irradiance = sams
+            .iter()
+            .map(|s| s.color.x * dot(-s.ray.d, n) / sun.pdf_to(s.ray.clone(), ZERO_VEC3))
+            .sum::<Real>()
+            / sams.len() as Real;
+        assert!((irradiance - 1.0).abs() < 0.01);
+        for s in sams {
+            assert!(dot(s.ray.d, sun.get_dir()) >= (0.1 as Real).cos() - 1e-9);
+        }
+
+        let toward = Ray::new(ZERO_VEC3, -sun.get_dir());
+        assert!(sun.emit(&toward).relative_eq(&sams[0].color, 1e-9, 1e-9));
+        assert!(sun.emit(&Ray::new(ZERO_VEC3, Y_VEC3)) == BLACK);
+    }
+}
@@ -0,0 +1,267 @@
+//! Environment light from an equirectangular (latitude-longitude) image
+
+extern crate image;
+extern crate rand;
+
+use self::image::hdr::HDRDecoder;
+use self::image::ImageResult;
+use arena::Arena;
+use buf::Buf2D;
+use light::*;
+use material::ImageTexture;
+use math::*;
+use std::fs::File;
+use std::io::BufReader;
+
+/// Light arriving from all directions at infinity, e.g. a studio HDRI.
+///
+/// The top row of the image is seen toward +y and the bottom one toward -y. The
+/// image center is seen toward -z, and its right part toward +x. Directions are
+/// importance-sampled according to the luminance of the image.
+pub struct EnvironmentLight {
+    texture: ImageTexture,
+    distribution: Distribution2D,
+    scale: Real,
+}
+
+impl Light for EnvironmentLight {
+    /// Rays are cast from the sphere of radius `INFINITE_DISTANCE` through the world origin
+    fn sample<'a>(&self, n: u32, arena: &'a Arena) -> &'a [LightSample] {
+        arena.alloc_iter((0..n).filter_map(|_| {
+            let to_light = self.sample_dir_to_light()?;
+            Some(LightSample {
+                light_normal: -to_light,
+                ray: Ray::new(INFINITE_DISTANCE * to_light, -to_light),
+                color: self.radiance(to_light),
+            })
+        }))
+    }
+
+    fn pdf(&self, ray: Ray) -> Real {
+        self.pdf_to(ray, ZERO_VEC3)
+    }
+
+    fn sample_to<'a>(&self, n: u32, dst_pnt: Vec3f, arena: &'a Arena) -> &'a [LightSample] {
+        arena.alloc_iter((0..n).filter_map(|_| {
+            let to_light = self.sample_dir_to_light()?;
+            Some(LightSample {
+                light_normal: -to_light,
+                ray: Ray::new(dst_pnt + INFINITE_DISTANCE * to_light, -to_light),
+                color: self.radiance(to_light),
+            })
+        }))
+    }
+
+    fn pdf_to(&self, ray: Ray, _dst_pnt: Vec3f) -> Real {
+        let uv = equirect_dir_to_uv(-ray.d);
+        let sin_theta = (uv.y * REAL_PI).sin();
+        if sin_theta <= 0.0 {
+            return 0.0;
+        }
+        self.distribution.pdf(uv) / (2.0 * REAL_PI * REAL_PI * sin_theta)
+    }
+
+    fn emit(&self, r: &Ray) -> Color3f {
+        self.radiance(r.d)
+    }
+}
+
+impl EnvironmentLight {
+    /// Light with the linear radiance of each pixel of `buf`
+    pub fn new(buf: Buf2D<Color3f>) -> EnvironmentLight {
+        let (w, h) = (buf.get_width(), buf.get_height());
+        // Bilinear lookups blend neighboring pixels, so each pixel is sampled after
+        // the brightest of its neighbors. Rows near the poles cover smaller solid angles.
+        let lum = |x: i64, y: i64| {
+            let x = x.rem_euclid(w as i64) as u32;
+            let y = y.max(0).min(h as i64 - 1) as u32;
+            luminance(buf[(x, y)]).max(0.0)
+        };
+        let mut func = Vec::with_capacity((w * h) as usize);
+        for y in 0..h as i64 {
+            let sin_theta = ((y as Real + 0.5) / h as Real * REAL_PI).sin();
+            for x in 0..w as i64 {
+                let mut max_lum: Real = 0.0;
+                for dy in -1..2 {
+                    for dx in -1..2 {
+                        max_lum = max_lum.max(lum(x + dx, y + dy));
+                    }
+                }
+                func.push(max_lum * sin_theta);
+            }
+        }
+        EnvironmentLight {
+            distribution: Distribution2D::new(&func, w as usize, h as usize),
+            texture: ImageTexture::new(buf),
+            scale: 1.0,
+        }
+    }
+
+    /// Load a Radiance HDR (`.hdr`) image
+    pub fn load(path: &str) -> ImageResult<EnvironmentLight> {
+        let decoder = HDRDecoder::new(BufReader::new(File::open(path)?))?;
+        let meta = decoder.metadata();
+        let pixels = decoder.read_image_hdr()?;
+        let buf = Buf2D::from_fn(meta.width, meta.height, |x, y| {
+            let p = pixels[(y * meta.width + x) as usize].data;
+            color3(p[0] as Real, p[1] as Real, p[2] as Real)
+        });
+        Ok(EnvironmentLight::new(buf))
+    }
+
+    /// Multiply the radiance of the image by `scale`
+    pub fn set_scale(&mut self, scale: Real) -> &mut Self {
+        assert!(scale >= 0.0);
+        self.scale = scale;
+        self
+    }
+
+    pub fn get_scale(&self) -> Real {
+        self.scale
+    }
+
+    /// Radiance arriving from direction `to_light`
+    fn radiance(&self, to_light: Vec3f) -> Color3f {
+        let uv = equirect_dir_to_uv(to_light);
+        self.scale * self.texture.sample_clamp_v(uv.x, uv.y)
+    }
+
+    /// Direction toward the light, None when landing on a pole
+    fn sample_dir_to_light(&self) -> Option<Vec3f> {
+        let u = vec2(rand::random::<Real>(), rand::random::<Real>());
+        let (uv, pdf) = self.distribution.sample_continuous(u);
+        if pdf <= 0.0 || (uv.y * REAL_PI).sin() <= 0.0 {
+            return None;
+        }
+        Some(equirect_uv_to_dir(uv))
+    }
+}
+
+/// Image coordinates in `[0, 1]^2` of normalized direction `d`, as mapped
+/// by `EnvironmentLight`
+pub fn equirect_dir_to_uv(d: Vec3f) -> Vec2f {
+    let theta = d.y.clamp(-1.0, 1.0).acos();
+    let phi = d.x.atan2(-d.z);
+    vec2(phi / (2.0 * REAL_PI) + 0.5, theta / REAL_PI)
+}
+
+/// Normalized direction seen at image coordinates `uv`, as mapped by `EnvironmentLight`
+pub fn equirect_uv_to_dir(uv: Vec2f) -> Vec3f {
+    let theta = uv.y * REAL_PI;
+    let phi = 2.0 * REAL_PI * (uv.x - 0.5);
+    vec3(
+        theta.sin() * phi.sin(),
+        theta.cos(),
+        -theta.sin() * phi.cos(),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::image::hdr::HDREncoder;
+    use super::image::Rgb;
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    /// Dim sky with a bright patch toward +x, slightly above the horizon
+    fn studio() -> Buf2D<Color3f> {
+        Buf2D::from_fn(16, 8, |x, y| {
+            if x == 12 && y == 3 {
+                color3(50.0, 40.0, 30.0)
+            } else {
+                color3(0.2, 0.3, 0.4)
+            }
+        })
+    }
+
+    #[test]
+    fn mapping() {
+        assert!(equirect_uv_to_dir(vec2(0.5, 0.5)).relative_eq(&-Z_VEC3, 1e-9, 1e-9));
+        assert!(equirect_uv_to_dir(vec2(0.75, 0.5)).relative_eq(&X_VEC3, 1e-9, 1e-9));
+        assert!(equirect_uv_to_dir(vec2(0.3, 0.0)).relative_eq(&Y_VEC3, 1e-9, 1e-9));
+        let uv = vec2(0.1, 0.7);
+        assert!(equirect_dir_to_uv(equirect_uv_to_dir(uv)).relative_eq(&uv, 1e-9, 1e-9));
+
+        // The poles see the top and bottom rows only
+        let light = EnvironmentLight::new(Buf2D::from_fn(4, 2, |_, y| {
+            if y == 0 {
+                color3(1.0, 0.0, 0.0)
+            } else {
+                color3(0.0, 0.0, 1.0)
+            }
+        }));
+        assert_eq!(light.emit(&Ray::new(ZERO_VEC3, Y_VEC3)), color3(1.0, 0.0, 0.0));
+        assert_eq!(light.emit(&Ray::new(ZERO_VEC3, -Y_VEC3)), color3(0.0, 0.0, 1.0));
+    }
+
+    #[test]
+    fn importance_sampling() {
+        let light = EnvironmentLight::new(studio());
+        let arena = Arena::new();
+        let n = vec3(1.0, 0.5, 0.2).normalize();
+        let cnt = 100000;
+
+        // Irradiance on a plane, by sampling the light and by quadrature
+        let sams = light.sample_to(cnt, ZERO_VEC3, &arena);
+        let by_light = sams
+            .iter()
+            .map(|s| {
+                let pdf = light.pdf_to(s.ray.clone(), ZERO_VEC3);
+                assert!(pdf > 0.0);
+                luminance(s.color) * dot(-s.ray.d, n).max(0.0) / pdf
+            })
+            .sum::<Real>()
+            / sams.len() as Real;
+        // Midpoint rule over the image, dw = 2 pi^2 sin(theta) du dv
+        let (nu, nv) = (512, 256);
+        let mut expected = 0.0;
+        for j in 0..nv {
+            for i in 0..nu {
+                let uv = vec2(
+                    (i as Real + 0.5) / nu as Real,
+                    (j as Real + 0.5) / nv as Real,
+                );
+                let d = equirect_uv_to_dir(uv);
+                expected += luminance(light.emit(&Ray::new(ZERO_VEC3, d)))
+                    * dot(d, n).max(0.0)
+                    * 2.0
+                    * REAL_PI
+                    * REAL_PI
+                    * (uv.y * REAL_PI).sin()
+                    / (nu * nv) as Real;
+            }
+        }
+        assert!((by_light - expected).abs() < 0.03 * expected);
+
+        // Bright directions are more likely
+        let bright = Ray::new(ZERO_VEC3, -equirect_uv_to_dir(vec2(12.5 / 16.0, 3.5 / 8.0)));
+        let dim = Ray::new(ZERO_VEC3, -equirect_uv_to_dir(vec2(2.5 / 16.0, 3.5 / 8.0)));
+        assert!(light.pdf_to(bright, ZERO_VEC3) > 10.0 * light.pdf_to(dim, ZERO_VEC3));
+    }
+
+    #[test]
+    fn load_hdr() {
+        let buf = studio();
+        let pixels: Vec<Rgb<f32>> = (0..8)
+            .flat_map(|y| (0..16).map(move |x| (x, y)))
+            .map(|(x, y)| {
+                let c = buf[(x, y)];
+                Rgb {
+                    data: [c.x as f32, c.y as f32, c.z as f32],
+                }
+            })
+            .collect();
+        let path = env::temp_dir().join("renderer_environment_studio.hdr");
+        HDREncoder::new(fs::File::create(&path).unwrap())
+            .encode(&pixels, 16, 8)
+            .unwrap();
+        let mut light = EnvironmentLight::load(path.to_str().unwrap()).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        light.set_scale(2.0);
+        let toward_patch = Ray::new(ZERO_VEC3, equirect_uv_to_dir(vec2(12.5 / 16.0, 3.5 / 8.0)));
+        let c = light.emit(&toward_patch);
+        assert!(c.relative_eq(&color3(100.0, 80.0, 60.0), 1e-6, 0.02));
+    }
+}
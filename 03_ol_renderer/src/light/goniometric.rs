@@ -0,0 +1,226 @@
+//! Point light with a measured (goniometric) intensity distribution
+
+use arena::Arena;
+use light::*;
+use math::*;
+
+/// Luminous intensity distribution of a luminaire in type C photometry, as
+/// read from IESNA LM-63 files by `loader::parse_ies`.
+///
+/// Vertical angles (in degrees) go from 0 at the nadir to 180 at the zenith of
+/// the luminaire, horizontal ones counterclockwise from its front seen from above.
+/// Horizontal angles ending at 0, 90 or 180 mean a distribution symmetric around the
+/// nadir, in each quadrant or on both sides of the 0-180 plane.
+#[derive(Clone, Debug, Default)]
+pub struct IesProfile {
+    /// Increasing vertical angles
+    pub vertical_angles: Vec<Real>,
+    /// Increasing horizontal angles
+    pub horizontal_angles: Vec<Real>,
+    /// Intensity in cd at each vertical angle, for each horizontal angle in turn
+    pub candela: Vec<Real>,
+}
+
+impl IesProfile {
+    /// Intensity in cd at the given angles in degrees, bilinearly interpolated.
+    /// Zero outside the measured vertical angles.
+    pub fn candela_at(&self, vertical: Real, horizontal: Real) -> Real {
+        let (v0, fv) = match interval(&self.vertical_angles, vertical) {
+            Some(v) => v,
+            None => return 0.0,
+        };
+        let horizontal = self.fold_horizontal(horizontal.rem_euclid(360.0));
+        let (h0, fh) = interval(&self.horizontal_angles, horizontal).unwrap_or((0, 0.0));
+
+        let nv = self.vertical_angles.len();
+        let at = |h: usize, v: usize| {
+            let (h, v) = (h.min(self.horizontal_angles.len() - 1), v.min(nv - 1));
+            self.candela[h * nv + v]
+        };
+        let row = |h: usize| at(h, v0) * (1.0 - fv) + at(h, v0 + 1) * fv;
+        row(h0) * (1.0 - fh) + row(h0 + 1) * fh
+    }
+
+    /// Maximum intensity in cd
+    pub fn max_candela(&self) -> Real {
+        self.candela.iter().cloned().fold(0.0, Real::max)
+    }
+
+    /// Horizontal angle in `[0, 360)` mapped into the measured range by symmetry
+    fn fold_horizontal(&self, h: Real) -> Real {
+        let first = self.horizontal_angles[0];
+        let last = self.horizontal_angles[self.horizontal_angles.len() - 1];
+        if last == 0.0 {
+            0.0
+        } else if last == 90.0 {
+            let h = h % 180.0;
+            if h > 90.0 {
+                180.0 - h
+            } else {
+                h
+            }
+        } else if last == 180.0 && h > 180.0 {
+            360.0 - h
+        } else if first == 90.0 && last == 270.0 && !(90.0..=270.0).contains(&h) {
+            (540.0 - h) % 360.0
+        } else {
+            h
+        }
+    }
+}
+
+/// Index `i` and interpolation factor of `x` between `values[i]` and `values[i + 1]`,
+/// None when outside `values`
+fn interval(values: &[Real], x: Real) -> Option<(usize, Real)> {
+    let last = values.len() - 1;
+    if x < values[0] || x > values[last] {
+        return None;
+    }
+    if last == 0 {
+        return Some((0, 0.0));
+    }
+    let i = match values.iter().position(|&v| v > x) {
+        Some(i) => i - 1,
+        None => last - 1,
+    };
+    let w = values[i + 1] - values[i];
+    let f = if w > 0.0 { (x - values[i]) / w } else { 0.0 };
+    Some((i, f.clamp(0.0, 1.0)))
+}
+
+/// `PointLight` whose emission in each direction is scaled by the candela of a
+/// luminaire's profile.
+///
+/// The luminaire points its nadir down (-y) and its front to +x by default.
+pub struct GoniometricLight {
+    point: PointLight,
+    profile: IesProfile,
+    down: Vec3f,
+    front: Vec3f,
+}
+
+impl Light for GoniometricLight {
+    fn sample<'a>(&self, n: u32, arena: &'a Arena) -> &'a [LightSample] {
+        let sams = self.point.sample(n, arena);
+        arena.alloc_iter(sams.iter().map(|s| self.scale_sample(s)))
+    }
+
+    fn pdf(&self, ray: Ray) -> Real {
+        self.point.pdf(ray)
+    }
+
+    fn sample_to<'a>(&self, n: u32, dst_pnt: Vec3f, arena: &'a Arena) -> &'a [LightSample] {
+        let sams = self.point.sample_to(n, dst_pnt, arena);
+        arena.alloc_iter(sams.iter().map(|s| self.scale_sample(s)))
+    }
+
+    fn pdf_to(&self, ray: Ray, dst_pnt: Vec3f) -> Real {
+        self.point.pdf_to(ray, dst_pnt)
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+impl GoniometricLight {
+    /// Light emitting `color` multiplied by the candela of `profile`
+    pub fn new(pos: Vec3f, color: Color3f, profile: IesProfile) -> GoniometricLight {
+        assert!(!profile.vertical_angles.is_empty() && !profile.horizontal_angles.is_empty());
+        assert_eq!(
+            profile.candela.len(),
+            profile.vertical_angles.len() * profile.horizontal_angles.len()
+        );
+        GoniometricLight {
+            point: PointLight::new(pos, color),
+            profile,
+            down: -Y_VEC3,
+            front: X_VEC3,
+        }
+    }
+
+    /// Orient the luminaire with its nadir along `down` and its front (horizontal
+    /// angle 0) toward `front`
+    pub fn set_orientation(&mut self, down: Vec3f, front: Vec3f) -> &mut Self {
+        self.down = down.normalize();
+        self.front = (front - dot(front, self.down) * self.down).normalize();
+        self
+    }
+
+    pub fn get_profile(&self) -> &IesProfile {
+        &self.profile
+    }
+
+    /// Candela emitted along normalized direction `dir`
+    pub fn candela(&self, dir: Vec3f) -> Real {
+        let side = self.front.cross(self.down);
+        let vertical = dot(dir, self.down).clamp(-1.0, 1.0).acos().to_degrees();
+        let horizontal = dot(dir, side).atan2(dot(dir, self.front)).to_degrees();
+        self.profile.candela_at(vertical, horizontal)
+    }
+
+    fn scale_sample(&self, s: &LightSample) -> LightSample {
+        LightSample {
+            light_normal: s.light_normal,
+            ray: s.ray.clone(),
+            color: s.color * self.candela(s.ray.d),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Brighter toward the front than the back, dark above the horizon
+    fn downlight() -> IesProfile {
+        IesProfile {
+            vertical_angles: vec![0.0, 45.0, 90.0],
+            horizontal_angles: vec![0.0, 90.0, 180.0],
+            candela: vec![
+                100.0, 80.0, 0.0, //
+                100.0, 60.0, 0.0, //
+                100.0, 40.0, 0.0,
+            ],
+        }
+    }
+
+    #[test]
+    fn interpolation() {
+        let p = downlight();
+        assert_eq!(p.candela_at(0.0, 123.0), 100.0);
+        assert!((p.candela_at(22.5, 0.0) - 90.0).abs() < 1e-9);
+        assert!((p.candela_at(45.0, 45.0) - 70.0).abs() < 1e-9);
+        // Bilateral symmetry
+        assert!((p.candela_at(45.0, 315.0) - 70.0).abs() < 1e-9);
+        assert_eq!(p.candela_at(120.0, 0.0), 0.0);
+        assert_eq!(p.max_candela(), 100.0);
+
+        let quadrant = IesProfile {
+            horizontal_angles: vec![0.0, 90.0],
+            candela: p.candela[..6].to_vec(),
+            ..p
+        };
+        assert!((quadrant.candela_at(45.0, 135.0) - 70.0).abs() < 1e-9);
+        assert!((quadrant.candela_at(30.0, 270.0) - 220.0 / 3.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn oriented_light() {
+        let mut light = GoniometricLight::new(vec3(0.0, 2.0, 0.0), WHITE, downlight());
+        let arena = Arena::new();
+        let sam = &light.sample_to(1, ZERO_VEC3, &arena)[0];
+        assert!(sam.color.relative_eq(&(100.0 * WHITE), 1e-9, 1e-9));
+        let sam = &light.sample_to(1, vec3(2.0, 0.0, 0.0), &arena)[0];
+        assert!(sam.color.relative_eq(&(80.0 * WHITE), 1e-9, 1e-9));
+        let sam = &light.sample_to(1, vec3(-2.0, 0.0, 0.0), &arena)[0];
+        assert!(sam.color.relative_eq(&(40.0 * WHITE), 1e-9, 1e-9));
+
+        // Pointing toward +x with its front up
+        light.set_orientation(X_VEC3, Y_VEC3);
+        let toward = |p: Vec3f| light.sample_to(1, p, &arena)[0].color.x;
+        assert!((toward(vec3(5.0, 2.0, 0.0)) - 100.0).abs() < 1e-9);
+        assert!((toward(vec3(2.0, 4.0, 0.0)) - 80.0).abs() < 1e-9);
+        assert_eq!(toward(vec3(-1.0, 2.0, 0.0)), 0.0);
+    }
+}
@@ -1,13 +1,30 @@
 //! Light sources
 
+pub mod area;
+pub mod environment;
+pub mod goniometric;
+pub mod directional;
 pub mod point;
+pub mod sky;
+pub mod spot;
 
 pub mod light {}
 
 pub mod prelude {
+    pub use super::area::*;
+    pub use super::environment::*;
+    pub use super::goniometric::*;
+    pub use super::directional::*;
     pub use super::point::*;
+    pub use super::sky::*;
+    pub use super::spot::*;
+    use arena::Arena;
     use math::*;
 
+    /// Distance to the points standing for lights at infinity, e.g. the origins
+    /// of their samples. Scenes shall fit well within it.
+    pub const INFINITE_DISTANCE: Real = 1e5;
+
     pub struct LightSample {
         pub light_normal: Vec3f,
         pub ray: Ray,
@@ -15,11 +32,27 @@ pub mod prelude {
     }
 
     pub trait Light: Sync {
-        fn sample(&self, n: u32) -> Vec<LightSample>;
+        fn sample<'a>(&self, n: u32, arena: &'a Arena) -> &'a [LightSample];
         fn pdf(&self, ray: Ray) -> Real;
 
-        fn sample_to(&self, n: u32, dst_pnt: Vec3f) -> Vec<LightSample>;
+        fn sample_to<'a>(&self, n: u32, dst_pnt: Vec3f, arena: &'a Arena) -> &'a [LightSample];
+
+        /// Density of `sample_to` casting `ray` from the light to `dst_pnt`, with respect
+        /// to the solid angle at `dst_pnt`. Lights which can be hit by rays return zero
+        /// for rays not starting on them.
         fn pdf_to(&self, ray: Ray, dst_pnt: Vec3f) -> Real;
+
+        /// Is the light a point or direction which no ray can hit by chance.
+        /// `pdf_to` of such a light is relative to its discrete choices.
+        fn is_delta(&self) -> bool {
+            false
+        }
+
+        /// Radiance arriving along `r` from a light at infinity, for rays leaving
+        /// the scene. Lights which are entities of the scene return black.
+        fn emit(&self, _r: &Ray) -> Color3f {
+            BLACK
+        }
     }
 }
 
@@ -2,6 +2,7 @@
 
 extern crate rand;
 
+use arena::Arena;
 use light::*;
 use math::*;
 
@@ -11,37 +12,37 @@ pub struct PointLight {
 }
 
 impl Light for PointLight {
-    fn sample(&self, n: u32) -> Vec<LightSample> {
-        (0..n)
-            .map(|_| {
-                let dir = sphere_uniform();
-                LightSample {
-                    light_normal: dir,
-                    ray: Ray::new(self.pos, dir),
-                    color: self.color,
-                }
-            })
-            .collect()
+    fn sample<'a>(&self, n: u32, arena: &'a Arena) -> &'a [LightSample] {
+        arena.alloc_iter((0..n).map(|_| {
+            let dir = sphere_uniform();
+            LightSample {
+                light_normal: dir,
+                ray: Ray::new(self.pos, dir),
+                color: self.color,
+            }
+        }))
     }
 
     fn pdf(&self, _ray: Ray) -> Real {
         1.0 / (4.0 * REAL_PI)
     }
 
-    fn sample_to(&self, n: u32, dst_pnt: Vec3f) -> Vec<LightSample> {
+    fn sample_to<'a>(&self, n: u32, dst_pnt: Vec3f, arena: &'a Arena) -> &'a [LightSample] {
         let dir = (dst_pnt - self.pos).normalize();
-        (0..n)
-            .map(|_| LightSample {
-                light_normal: dir,
-                ray: Ray::new(self.pos, dir),
-                color: self.color,
-            })
-            .collect()
+        arena.alloc_iter((0..n).map(|_| LightSample {
+            light_normal: dir,
+            ray: Ray::new(self.pos, dir),
+            color: self.color,
+        }))
     }
 
     fn pdf_to(&self, _ray: Ray, _dst_pnt: Vec3f) -> Real {
         1.0
     }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
 }
 
 impl PointLight {
@@ -0,0 +1,251 @@
+//! Analytic daylight: Preetham sky and sun
+//!
+//! See Preetham, A. J., Shirley, P., & Smits, B. (1999).
+//! A practical analytic model for daylight. Proceedings of SIGGRAPH 99, 91-100.
+
+use buf::Buf2D;
+use light::*;
+use math::*;
+
+/// Angular diameter of the sun in radians
+pub const SUN_ANGULAR_DIAMETER: Real = 0.0093;
+
+/// Illuminance of the sun outside the atmosphere in klx
+const SUN_EXTRATERRESTRIAL_ILLUMINANCE: Real = 128.0;
+
+/// Clear sky lit by the sun, with +y up.
+///
+/// The sky and the sun are lights at infinity seen by escaped rays, and replace the
+/// constant background of renderers, which should then be black:
+///
+/// ```
+/// use renderer::*;
+///
+/// let sky = PreethamSky::new(0.6, 0.3, 3.0);
+/// let lights: Vec<Box<Light>> = vec![
+///     Box::new(sky.to_environment(256, 128)),
+///     Box::new(sky.sun()),
+/// ];
+/// let tracer = PathTracer::new(Vec::new(), lights, BLACK, 4, 1);
+/// ```
+///
+/// The sky radiance is in kcd/m^2 and the sun irradiance in klx, both multiplied by
+/// a scale of 0.02 by default so that a white surface under the noon sun is about 1.
+/// The sky is black below the horizon.
+#[derive(Clone, Debug)]
+pub struct PreethamSky {
+    /// Direction toward the sun
+    sun_dir: Vec3f,
+    /// Angle between the zenith and the sun
+    sun_theta: Real,
+    turbidity: Real,
+    /// Perez coefficients (A, B, C, D, E) of Y, x and y
+    perez: [[Real; 5]; 3],
+    /// Zenith values of Y, x and y
+    zenith: [Real; 3],
+    scale: Real,
+}
+
+impl PreethamSky {
+    /// Sky with the sun `sun_elevation` radians above the horizon, at azimuth
+    /// `sun_azimuth` radians from -z toward +x. `turbidity` from 2 (very clear) to
+    /// 10 (hazy) is the haziness of the atmosphere.
+    pub fn new(sun_elevation: Real, sun_azimuth: Real, turbidity: Real) -> PreethamSky {
+        assert!((0.0..=REAL_PI / 2.0).contains(&sun_elevation));
+        assert!((2.0..=10.0).contains(&turbidity));
+        let t = turbidity;
+        let sun_theta = REAL_PI / 2.0 - sun_elevation;
+        let sun_dir = vec3(
+            sun_elevation.cos() * sun_azimuth.sin(),
+            sun_elevation.sin(),
+            -sun_elevation.cos() * sun_azimuth.cos(),
+        );
+
+        let perez = [
+            [
+                0.1787 * t - 1.4630,
+                -0.3554 * t + 0.4275,
+                -0.0227 * t + 5.3251,
+                0.1206 * t - 2.5771,
+                -0.0670 * t + 0.3703,
+            ],
+            [
+                -0.0193 * t - 0.2592,
+                -0.0665 * t + 0.0008,
+                -0.0004 * t + 0.2125,
+                -0.0641 * t - 0.8989,
+                -0.0033 * t + 0.0452,
+            ],
+            [
+                -0.0167 * t - 0.2608,
+                -0.0950 * t + 0.0092,
+                -0.0079 * t + 0.2102,
+                -0.0441 * t - 1.6537,
+                -0.0109 * t + 0.0529,
+            ],
+        ];
+
+        let chi = (4.0 / 9.0 - t / 120.0) * (REAL_PI - 2.0 * sun_theta);
+        let zenith_y = (4.0453 * t - 4.9710) * chi.tan() - 0.2155 * t + 2.4192;
+        let chromaticity = |m: [[Real; 4]; 3]| {
+            let th = [sun_theta.powi(3), sun_theta.powi(2), sun_theta, 1.0];
+            let tu = [t * t, t, 1.0];
+            let mut ret = 0.0;
+            for i in 0..3 {
+                for j in 0..4 {
+                    ret += tu[i] * m[i][j] * th[j];
+                }
+            }
+            ret
+        };
+        let zenith_x = chromaticity([
+            [0.00166, -0.00375, 0.00209, 0.0],
+            [-0.02903, 0.06377, -0.03202, 0.00394],
+            [0.11693, -0.21196, 0.06052, 0.25886],
+        ]);
+        let zenith_yc = chromaticity([
+            [0.00275, -0.00610, 0.00317, 0.0],
+            [-0.04214, 0.08970, -0.04153, 0.00516],
+            [0.15346, -0.26756, 0.06670, 0.26688],
+        ]);
+
+        PreethamSky {
+            sun_dir,
+            sun_theta,
+            turbidity,
+            perez,
+            zenith: [zenith_y, zenith_x, zenith_yc],
+            scale: 0.02,
+        }
+    }
+
+    /// Multiply the radiance of the sky and the irradiance of the sun by `scale`
+    pub fn set_scale(&mut self, scale: Real) -> &mut Self {
+        assert!(scale >= 0.0);
+        self.scale = scale;
+        self
+    }
+
+    pub fn get_scale(&self) -> Real {
+        self.scale
+    }
+
+    /// Normalized direction toward the sun
+    pub fn get_sun_dir(&self) -> Vec3f {
+        self.sun_dir
+    }
+
+    pub fn get_turbidity(&self) -> Real {
+        self.turbidity
+    }
+
+    /// Linear RGB radiance of the sky seen along normalized direction `dir`
+    pub fn radiance(&self, dir: Vec3f) -> Color3f {
+        if dir.y <= 0.0 {
+            return BLACK;
+        }
+        let theta = dir.y.min(1.0).acos();
+        let gamma = dot(dir, self.sun_dir).clamp(-1.0, 1.0).acos();
+        let mut xyy = [0.0; 3];
+        for (i, v) in xyy.iter_mut().enumerate() {
+            *v = self.zenith[i] * self.perez_f(i, theta, gamma)
+                / self.perez_f(i, 0.0, self.sun_theta);
+        }
+        self.scale * xyy_to_rgb(xyy[1], xyy[2], xyy[0])
+    }
+
+    /// The sky baked into a `w * h` environment light, for importance sampling
+    pub fn to_environment(&self, w: u32, h: u32) -> EnvironmentLight {
+        let buf = Buf2D::from_fn(w, h, |x, y| {
+            let uv = vec2((x as Real + 0.5) / w as Real, (y as Real + 0.5) / h as Real);
+            self.radiance(equirect_uv_to_dir(uv))
+        });
+        EnvironmentLight::new(buf)
+    }
+
+    /// Sun light of the sky, attenuated by the atmosphere
+    pub fn sun(&self) -> DirectionalLight {
+        let mut sun = DirectionalLight::new(
+            -self.sun_dir,
+            self.scale * SUN_EXTRATERRESTRIAL_ILLUMINANCE * self.sun_transmittance(),
+        );
+        sun.set_angular_diameter(SUN_ANGULAR_DIAMETER);
+        sun
+    }
+
+    /// Perez distribution of value `i` (Y, x or y)
+    fn perez_f(&self, i: usize, theta: Real, gamma: Real) -> Real {
+        let [a, b, c, d, e] = self.perez[i];
+        let cos_theta = theta.cos().max(1e-4);
+        (1.0 + a * (b / cos_theta).exp())
+            * (1.0 + c * (d * gamma).exp() + e * gamma.cos() * gamma.cos())
+    }
+
+    /// Rayleigh and aerosol transmittance of the atmosphere toward the sun,
+    /// at the wavelengths of the red, green and blue primaries
+    fn sun_transmittance(&self) -> Color3f {
+        let theta_deg = self.sun_theta.to_degrees();
+        let mass = 1.0 / (self.sun_theta.cos() + 0.15 * (93.885 - theta_deg).powf(-1.253));
+        let beta = 0.04608365822 * self.turbidity - 0.04586025928;
+        let tau = |lambda_um: Real| {
+            let rayleigh = -0.008735 * lambda_um.powf(-4.08) * mass;
+            let aerosol = -beta * lambda_um.powf(-1.3) * mass;
+            (rayleigh + aerosol).exp()
+        };
+        color3(tau(0.65), tau(0.57), tau(0.475))
+    }
+}
+
+/// Linear sRGB color of CIE xyY color `(x, y, lum)`
+fn xyy_to_rgb(x: Real, y: Real, lum: Real) -> Color3f {
+    let cx = x / y * lum;
+    let cz = (1.0 - x - y) / y * lum;
+    color3(
+        3.2406 * cx - 1.5372 * lum - 0.4986 * cz,
+        -0.9689 * cx + 1.8758 * lum + 0.0415 * cz,
+        0.0557 * cx - 0.2040 * lum + 1.0570 * cz,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arena::Arena;
+
+    #[test]
+    fn daylight() {
+        let noon = PreethamSky::new(1.2, 0.0, 3.0);
+        assert!(noon.get_sun_dir().relative_eq(
+            &vec3(0.0, 1.2f64.sin(), -1.2f64.cos()),
+            1e-9,
+            1e-9
+        ));
+
+        // Zenith luminance of the model, brighter toward the sun, black below the horizon
+        let zenith = noon.radiance(Y_VEC3);
+        assert!((luminance(zenith) - 0.02 * noon.zenith[0]).abs() < 1e-3 * luminance(zenith));
+        assert!(zenith.z > zenith.x);
+        let toward_sun = noon.radiance(vec3(0.0, 0.5, -1.0).normalize());
+        let away = noon.radiance(vec3(0.0, 0.5, 1.0).normalize());
+        assert!(luminance(toward_sun) > luminance(away));
+        assert_eq!(noon.radiance(-Y_VEC3), BLACK);
+
+        // Sunset is dimmer and redder
+        let sunset = PreethamSky::new(0.05, 0.0, 3.0);
+        let (high, low) = (noon.sun(), sunset.sun());
+        let irradiance = |sun: &DirectionalLight| {
+            let arena = Arena::new();
+            let s = &sun.sample_to(1, ZERO_VEC3, &arena)[0];
+            s.color / sun.pdf_to(s.ray.clone(), ZERO_VEC3)
+        };
+        let (e_high, e_low) = (irradiance(&high), irradiance(&low));
+        assert!(luminance(e_high) > 60.0 * 0.02 && luminance(e_high) < 128.0 * 0.02);
+        assert!(luminance(e_low) < luminance(e_high));
+        assert!(e_low.x / e_low.z > e_high.x / e_high.z);
+
+        let env = noon.to_environment(64, 32);
+        let dir = equirect_uv_to_dir(vec2(20.5 / 64.0, 10.5 / 32.0));
+        let r = Ray::new(ZERO_VEC3, dir);
+        assert!(env.emit(&r).relative_eq(&noon.radiance(dir), 1e-9, 1e-9));
+    }
+}
@@ -0,0 +1,118 @@
+//! Spot light source
+
+extern crate rand;
+
+use arena::Arena;
+use light::*;
+use math::*;
+
+/// Point light shining within a cone.
+///
+/// The intensity is full inside the inner cone and falls off smoothly to zero at
+/// the outer one. Like `PointLight`, it doesn't fall off with distance.
+pub struct SpotLight {
+    pos: Vec3f,
+    dir: Vec3f,
+    cos_inner: Real,
+    cos_outer: Real,
+    color: Color3f,
+}
+
+impl Light for SpotLight {
+    fn sample<'a>(&self, n: u32, arena: &'a Arena) -> &'a [LightSample] {
+        let frame = local_frame(self.dir);
+        arena.alloc_iter((0..n).map(|_| {
+            let u = vec2(rand::random::<Real>(), rand::random::<Real>());
+            let dir = frame * cone_uniform(self.cos_outer, u);
+            LightSample {
+                light_normal: dir,
+                ray: Ray::new(self.pos, dir),
+                color: self.color * self.falloff(dir),
+            }
+        }))
+    }
+
+    fn pdf(&self, ray: Ray) -> Real {
+        if dot(ray.d, self.dir) < self.cos_outer {
+            return 0.0;
+        }
+        cone_uniform_pdf(self.cos_outer)
+    }
+
+    fn sample_to<'a>(&self, n: u32, dst_pnt: Vec3f, arena: &'a Arena) -> &'a [LightSample] {
+        let dir = (dst_pnt - self.pos).normalize();
+        let falloff = self.falloff(dir);
+        if falloff <= 0.0 {
+            return &[];
+        }
+        arena.alloc_iter((0..n).map(|_| LightSample {
+            light_normal: dir,
+            ray: Ray::new(self.pos, dir),
+            color: self.color * falloff,
+        }))
+    }
+
+    fn pdf_to(&self, _ray: Ray, _dst_pnt: Vec3f) -> Real {
+        1.0
+    }
+
+    fn is_delta(&self) -> bool {
+        true
+    }
+}
+
+impl SpotLight {
+    /// Light at `pos` shining toward `dir`. Cone angles are half angles in radians,
+    /// with `0 <= inner_angle <= outer_angle <= pi`.
+    pub fn new(
+        pos: Vec3f,
+        dir: Vec3f,
+        inner_angle: Real,
+        outer_angle: Real,
+        color: Color3f,
+    ) -> SpotLight {
+        assert!(0.0 <= inner_angle && inner_angle <= outer_angle && outer_angle <= REAL_PI);
+        SpotLight {
+            pos,
+            dir: dir.normalize(),
+            cos_inner: inner_angle.cos(),
+            cos_outer: outer_angle.cos(),
+            color,
+        }
+    }
+
+    /// Fraction of the intensity emitted along normalized `dir`
+    pub fn falloff(&self, dir: Vec3f) -> Real {
+        let cos = dot(dir, self.dir);
+        if cos >= self.cos_inner {
+            return 1.0;
+        }
+        if cos <= self.cos_outer {
+            return 0.0;
+        }
+        // Smoothstep between the cones
+        let x = (cos - self.cos_outer) / (self.cos_inner - self.cos_outer);
+        x * x * (3.0 - 2.0 * x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cone() {
+        let light = SpotLight::new(Y_VEC3, -Y_VEC3, 0.2, 0.4, WHITE);
+        let arena = Arena::new();
+        let color_to = |p: Vec3f| light.sample_to(1, p, &arena).first().map(|s| s.color);
+
+        assert_eq!(color_to(vec3(0.1, -1.0, 0.0)), Some(WHITE));
+        assert_eq!(color_to(vec3(1.0, 0.0, 0.0)), None);
+        let mid = color_to(vec3((0.3 as Real).tan(), 0.0, 0.0)).unwrap();
+        assert!(mid.x > 0.5 && mid.x < 0.75);
+
+        for sam in light.sample(100, &arena) {
+            assert!(dot(sam.ray.d, -Y_VEC3) >= (0.4 as Real).cos() - 1e-9);
+        }
+    }
+}
@@ -0,0 +1,423 @@
+//! glTF 2.0 scene importer
+//!
+//! Loads `.gltf` (with external or embedded buffers) and `.glb` files from the local
+//! file system. The default scene is flattened into world space:
+//!
+//! * triangle primitives become `TriangleMesh` entities
+//! * perspective cameras become `PerspectiveCamera`s
+//! * `KHR_lights_punctual` point, spot and directional lights become `PointLight`s,
+//!   `SpotLight`s and `DirectionalLight`s
+//!
+//! Metallic-roughness materials are approximated with two `Phong` lobes added by
+//! `AddBxDF`. With the base color (factor, texture and vertex color) and metallic
+//! factor `m`, the diffuse lobe has color `base * (1 - m)` and shininess 1, and the
+//! specular lobe has color `mix(0.04, base, m)` and the shininess converted from
+//! roughness. Emissive materials become `DiffuseLight`s.
+
+extern crate gltf;
+
+use std::error;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+use self::gltf::khr_lights_punctual::Kind as LightKind;
+use buf::Buf2D;
+use camera::*;
+use entity::*;
+use light::*;
+use material::*;
+use math::*;
+
+#[derive(Debug)]
+pub enum GltfError {
+    Gltf(gltf::Error),
+    /// Valid glTF using features the importer cannot map
+    Unsupported(String),
+}
+
+impl fmt::Display for GltfError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            GltfError::Gltf(ref e) => write!(f, "gltf error: {}", e),
+            GltfError::Unsupported(ref msg) => write!(f, "unsupported gltf content: {}", msg),
+        }
+    }
+}
+
+impl error::Error for GltfError {
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            GltfError::Gltf(ref e) => Some(e),
+            GltfError::Unsupported(_) => None,
+        }
+    }
+}
+
+impl From<gltf::Error> for GltfError {
+    fn from(e: gltf::Error) -> GltfError {
+        GltfError::Gltf(e)
+    }
+}
+
+/// Everything imported from a glTF file, in world space
+pub struct GltfScene {
+    pub entities: Vec<Box<Entity>>,
+    pub lights: Vec<Box<Light>>,
+    pub cameras: Vec<PerspectiveCamera>,
+}
+
+fn to_mat4(m: [[f32; 4]; 4]) -> Mat4f {
+    let col = |c: [f32; 4]| vec4(c[0] as Real, c[1] as Real, c[2] as Real, c[3] as Real);
+    Mat4f::from_cols(col(m[0]), col(m[1]), col(m[2]), col(m[3]))
+}
+
+fn to_vec3(v: [f32; 3]) -> Vec3f {
+    vec3(v[0] as Real, v[1] as Real, v[2] as Real)
+}
+
+fn transform_point(m: &Mat4f, p: Vec3f) -> Vec3f {
+    (m * vec4(p.x, p.y, p.z, 1.0)).xyz()
+}
+
+fn transform_dir(m: &Mat4f, d: Vec3f) -> Vec3f {
+    (m * vec4(d.x, d.y, d.z, 0.0)).xyz()
+}
+
+fn to_texture(img: &gltf::image::Data, srgb: bool) -> ImageTexture {
+    use self::gltf::image::Format;
+    let (channels, bytes) = match img.format {
+        Format::R8 => (1, 1),
+        Format::R8G8 => (2, 1),
+        Format::R8G8B8 => (3, 1),
+        Format::R8G8B8A8 => (4, 1),
+        Format::R16 => (1, 2),
+        Format::R16G16 => (2, 2),
+        Format::R16G16B16 => (3, 2),
+        Format::R16G16B16A16 => (4, 2),
+        Format::R32G32B32FLOAT => (3, 4),
+        Format::R32G32B32A32FLOAT => (4, 4),
+    };
+    let px = &img.pixels;
+    let channel = |offset: usize| -> Real {
+        let v = match bytes {
+            1 => px[offset] as Real / 255.0,
+            2 => u16::from_ne_bytes([px[offset], px[offset + 1]]) as Real / 65535.0,
+            _ => f32::from_ne_bytes([px[offset], px[offset + 1], px[offset + 2], px[offset + 3]])
+                as Real,
+        };
+        if srgb {
+            srgb_to_linear(v)
+        } else {
+            v
+        }
+    };
+    let width = img.width;
+    ImageTexture::new(Buf2D::from_fn(img.width, img.height, |x, y| {
+        let base = (y * width + x) as usize * channels * bytes;
+        if channels < 3 {
+            let v = channel(base);
+            color3(v, v, v)
+        } else {
+            color3(channel(base), channel(base + bytes), channel(base + 2 * bytes))
+        }
+    }))
+}
+
+/// Blinn-Phong exponent matching GGX roughness, see
+/// http://simonstechblog.blogspot.com/2011/12/microfacet-brdf.html
+fn roughness_to_shininess(roughness: Real) -> Real {
+    let alpha = roughness * roughness;
+    (2.0 / (alpha * alpha).max(1e-4) - 2.0).max(1.0)
+}
+
+struct Importer {
+    buffers: Vec<gltf::buffer::Data>,
+    images: Vec<gltf::image::Data>,
+    /// Color textures converted on first use, indexed by image index
+    textures: Vec<Option<Arc<ImageTexture>>>,
+    scene: GltfScene,
+}
+
+impl Importer {
+    fn color_texture(&mut self, info: Option<gltf::texture::Info>) -> Option<Arc<ImageTexture>> {
+        let img = info?.texture().source().index();
+        if self.textures[img].is_none() {
+            self.textures[img] = Some(Arc::new(to_texture(&self.images[img], true)));
+        }
+        self.textures[img].clone()
+    }
+
+    // `usize::is_multiple_of` needs Rust 1.87
+    #[allow(clippy::manual_is_multiple_of)]
+    fn add_primitive(&mut self, prim: &gltf::Primitive, world: &Mat4f) -> Result<(), GltfError> {
+        if prim.mode() != gltf::mesh::Mode::Triangles {
+            return Err(GltfError::Unsupported(
+                "primitive mode other than triangles".to_string(),
+            ));
+        }
+
+        let buffers = &self.buffers;
+        let reader = prim.reader(|b| Some(&buffers[b.index()]));
+        let positions: Vec<Vec3f> = match reader.read_positions() {
+            Some(p) => p.map(|p| transform_point(world, to_vec3(p))).collect(),
+            None => return Err(GltfError::Unsupported("primitive without positions".to_string())),
+        };
+
+        let upper = Mat3f::from_cols(world.x.xyz(), world.y.xyz(), world.z.xyz());
+        let normal_mat = upper.invert().unwrap_or(upper).transpose();
+        let mut data = MeshData {
+            normals: reader
+                .read_normals()
+                .map(|ns| ns.map(|n| (normal_mat * to_vec3(n)).normalize()).collect())
+                .unwrap_or_default(),
+            uvs: reader
+                .read_tex_coords(0)
+                .map(|uvs| {
+                    uvs.into_f32()
+                        .map(|uv| vec2(uv[0] as Real, uv[1] as Real))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            tangents: reader
+                .read_tangents()
+                .map(|ts| {
+                    ts.map(|t| upper * vec3(t[0] as Real, t[1] as Real, t[2] as Real))
+                        .collect()
+                })
+                .unwrap_or_default(),
+            colors: reader
+                .read_colors(0)
+                .map(|cs| cs.into_rgb_f32().map(to_vec3).collect())
+                .unwrap_or_default(),
+            indices: Vec::new(),
+            positions,
+        };
+        let indices: Vec<u32> = match reader.read_indices() {
+            Some(i) => i.into_u32().collect(),
+            None => (0..data.positions.len() as u32).collect(),
+        };
+        if indices.len() % 3 != 0 || indices.iter().any(|&i| i as usize >= data.positions.len()) {
+            return Err(GltfError::Unsupported("invalid triangle indices".to_string()));
+        }
+        data.indices = indices.chunks(3).map(|f| [f[0], f[1], f[2]]).collect();
+
+        let mtl = prim.material();
+        let emissive = to_vec3(mtl.emissive_factor());
+        if emissive != BLACK {
+            let tex = self.color_texture(mtl.emissive_texture());
+            self.scene.entities.push(Box::new(TriangleMesh::new(
+                data,
+                Box::new(move |_, _, ly, u, v, _| {
+                    let color = match tex {
+                        Some(ref t) => emissive.mul_element_wise(t.sample(u, v)),
+                        None => emissive,
+                    };
+                    DiffuseLight::new(ly, color)
+                }),
+            )));
+            return Ok(());
+        }
+
+        let pbr = mtl.pbr_metallic_roughness();
+        let base = pbr.base_color_factor();
+        let base = vec3(base[0] as Real, base[1] as Real, base[2] as Real);
+        let tex = self.color_texture(pbr.base_color_texture());
+        let metallic = pbr.metallic_factor() as Real;
+        let shininess = roughness_to_shininess(pbr.roughness_factor() as Real);
+        self.scene.entities.push(Box::new(TriangleMesh::new(
+            data,
+            Box::new(move |_, lx, ly, u, v, vtx_color: Color3f| {
+                let color = base.mul_element_wise(vtx_color);
+                let color = match tex {
+                    Some(ref t) => color.mul_element_wise(t.sample(u, v)),
+                    None => color,
+                };
+                // Dielectrics reflect about 4% at normal incidence
+                let dielectric = color3(0.04, 0.04, 0.04);
+                AddBxDF::new(
+                    Phong::new(BLACK, color * (1.0 - metallic), lx, ly, 1.0),
+                    Phong::new(BLACK, dielectric.lerp(color, metallic), lx, ly, shininess),
+                )
+            }),
+        )));
+        Ok(())
+    }
+
+    fn add_node(&mut self, node: &gltf::Node, parent: &Mat4f) -> Result<(), GltfError> {
+        let world = parent * to_mat4(node.transform().matrix());
+
+        if let Some(mesh) = node.mesh() {
+            for prim in mesh.primitives() {
+                self.add_primitive(&prim, &world)?;
+            }
+        }
+
+        if let Some(cam) = node.camera() {
+            // Cameras look at -z with +y up in their local space
+            if let gltf::camera::Projection::Perspective(p) = cam.projection() {
+                let eye = transform_point(&world, ZERO_VEC3);
+                let dir = transform_dir(&world, -Z_VEC3);
+                let up = transform_dir(&world, Y_VEC3);
+                self.scene.cameras.push(PerspectiveCamera::with_fov(
+                    eye,
+                    eye + dir,
+                    up,
+                    p.yfov() as Real,
+                    p.aspect_ratio().unwrap_or(1.0) as Real,
+                    p.znear() as Real,
+                ));
+            }
+        }
+
+        if let Some(light) = node.light() {
+            let color = to_vec3(light.color()) * light.intensity() as Real;
+            match light.kind() {
+                LightKind::Point => {
+                    let pos = transform_point(&world, ZERO_VEC3);
+                    self.scene.lights.push(Box::new(PointLight::new(pos, color)));
+                }
+                LightKind::Spot {
+                    inner_cone_angle,
+                    outer_cone_angle,
+                } => {
+                    let pos = transform_point(&world, ZERO_VEC3);
+                    let dir = transform_dir(&world, -Z_VEC3);
+                    self.scene.lights.push(Box::new(SpotLight::new(
+                        pos,
+                        dir,
+                        inner_cone_angle as Real,
+                        outer_cone_angle as Real,
+                        color,
+                    )));
+                }
+                LightKind::Directional => {
+                    let dir = transform_dir(&world, -Z_VEC3);
+                    self.scene.lights.push(Box::new(DirectionalLight::new(dir, color)));
+                }
+            }
+        }
+
+        for child in node.children() {
+            self.add_node(&child, &world)?;
+        }
+        Ok(())
+    }
+}
+
+/// Import the default (or the first) scene of a glTF file
+pub fn load_gltf<P: AsRef<Path>>(path: P) -> Result<GltfScene, GltfError> {
+    let (doc, buffers, images) = gltf::import(path)?;
+    let scene = match doc.default_scene().or_else(|| doc.scenes().next()) {
+        Some(s) => s,
+        None => return Err(GltfError::Unsupported("no scene".to_string())),
+    };
+
+    let mut importer = Importer {
+        buffers,
+        textures: vec![None; images.len()],
+        images,
+        scene: GltfScene {
+            entities: Vec::new(),
+            lights: Vec::new(),
+            cameras: Vec::new(),
+        },
+    };
+    let identity = Mat4f::identity();
+    for node in scene.nodes() {
+        importer.add_node(&node, &identity)?;
+    }
+    Ok(importer.scene)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arena::Arena;
+    use std::env;
+    use std::fs;
+
+    /// One triangle (positions + u16 indices in an embedded buffer) translated by
+    /// (0, 0, -2), a point light, a spot light pointing down, a sun and a camera
+    const TRIANGLE_GLTF: &str = r#"{
+        "asset": { "version": "2.0" },
+        "extensionsUsed": ["KHR_lights_punctual"],
+        "extensions": { "KHR_lights_punctual": { "lights": [
+            { "type": "point", "color": [1.0, 0.5, 0.0], "intensity": 2.0 },
+            { "type": "spot", "spot": { "innerConeAngle": 0.2, "outerConeAngle": 0.4 } },
+            { "type": "directional", "intensity": 3.0 }
+        ] } },
+        "scene": 0,
+        "scenes": [ { "nodes": [0, 1, 2, 3, 4] } ],
+        "nodes": [
+            { "mesh": 0, "translation": [0.0, 0.0, -2.0] },
+            { "camera": 0, "translation": [0.0, 0.0, 1.0] },
+            { "extensions": { "KHR_lights_punctual": { "light": 0 } },
+              "translation": [0.0, 3.0, 0.0] },
+            { "extensions": { "KHR_lights_punctual": { "light": 1 } },
+              "translation": [0.0, 0.0, 1.0], "rotation": [0.0, 0.0, 0.0, 1.0] },
+            { "extensions": { "KHR_lights_punctual": { "light": 2 } },
+              "rotation": [-0.7071068, 0.0, 0.0, 0.7071068] }
+        ],
+        "cameras": [ { "type": "perspective",
+                       "perspective": { "yfov": 1.0, "znear": 0.1, "aspectRatio": 1.5 } } ],
+        "meshes": [ { "primitives": [ {
+            "attributes": { "POSITION": 0 }, "indices": 1, "material": 0
+        } ] } ],
+        "materials": [ { "pbrMetallicRoughness": {
+            "baseColorFactor": [0.2, 0.4, 0.6, 1.0], "metallicFactor": 0.25,
+            "roughnessFactor": 1.0
+        } } ],
+        "accessors": [
+            { "bufferView": 0, "componentType": 5126, "count": 3, "type": "VEC3",
+              "min": [-1.0, -1.0, 0.0], "max": [1.0, 1.0, 0.0] },
+            { "bufferView": 1, "componentType": 5123, "count": 3, "type": "SCALAR" }
+        ],
+        "bufferViews": [
+            { "buffer": 0, "byteOffset": 0, "byteLength": 36 },
+            { "buffer": 0, "byteOffset": 36, "byteLength": 6 }
+        ],
+        "buffers": [ { "byteLength": 44,
+            "uri": "data:application/octet-stream;base64,AACAvwAAgL8AAAAAAACAPwAAgL8AAAAAAAAAAAAAgD8AAAAAAAABAAIAAAA=" } ]
+    }"#;
+
+    #[test]
+    fn triangle_scene() {
+        let path = env::temp_dir().join("renderer_gltf_triangle_scene.gltf");
+        fs::write(&path, TRIANGLE_GLTF).unwrap();
+        let scene = load_gltf(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(scene.entities.len(), 1);
+        assert_eq!(scene.lights.len(), 3);
+        assert_eq!(scene.cameras.len(), 1);
+
+        let r = scene.cameras[0].scr_to_ray(vec2(0.0, 0.0));
+        assert!(r.d.relative_eq(&-Z_VEC3, 1e-9, 1e-9));
+        let inct = scene.entities[0].inct(r).unwrap();
+        assert!(inct.position.relative_eq(&vec3(0.0, 0.0, -2.0), 1e-6, 1e-6));
+
+        // Mirror directions, where both lobes have their maximum
+        let arena = Arena::new();
+        let mtl = inct.material.build(&arena);
+        let vin = vec3(1.0, 0.0, 1.0).normalize();
+        let vout = vec3(-1.0, 0.0, 1.0).normalize();
+        let expected = color3(0.2, 0.4, 0.6) * 0.75 + color3(0.08, 0.13, 0.18);
+        assert!(mtl.f(vin, vout).relative_eq(&expected, 1e-6, 1e-6));
+
+        let sam = scene.lights[0].sample_to(1, inct.position, &arena);
+        assert!(sam[0].ray.p.relative_eq(&vec3(0.0, 3.0, 0.0), 1e-6, 1e-6));
+        assert!(sam[0].color.relative_eq(&color3(2.0, 1.0, 0.0), 1e-6, 1e-6));
+        // The spot light at the camera shines along its -z onto the triangle
+        let sam = scene.lights[1].sample_to(1, inct.position, &arena);
+        assert!(sam[0].color.relative_eq(&WHITE, 1e-6, 1e-6));
+        // The sun is rotated to shine straight down
+        let sam = scene.lights[2].sample_to(1, inct.position, &arena);
+        assert!(sam[0].ray.d.relative_eq(&-Y_VEC3, 1e-6, 1e-6));
+        assert!(sam[0].color.relative_eq(&color3(3.0, 3.0, 3.0), 1e-6, 1e-6));
+
+        let bounding = scene.entities[0].bounding();
+        assert!(bounding.get_lower().relative_eq(&vec3(-1.0, -1.0, -2.0), 1e-6, 1e-6));
+        assert!(bounding.get_upper().relative_eq(&vec3(1.0, 1.0, -2.0), 1e-6, 1e-6));
+    }
+}
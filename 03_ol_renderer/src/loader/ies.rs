@@ -0,0 +1,219 @@
+//! IESNA LM-63 photometric data importer
+//!
+//! Reads type C photometry from `.ies` files of the 1986, 1991, 1995 and 2002
+//! versions of the standard. Keyword lines are skipped, as well as lamp tilt
+//! data, which is only meaningful for tilted luminaires.
+
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use light::*;
+use math::*;
+
+#[derive(Debug)]
+pub enum IesError {
+    Io(io::Error),
+    /// Malformed or unsupported photometric data
+    Format(String),
+}
+
+impl fmt::Display for IesError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            IesError::Io(ref e) => write!(f, "io error: {}", e),
+            IesError::Format(ref msg) => write!(f, "invalid ies file: {}", msg),
+        }
+    }
+}
+
+impl error::Error for IesError {
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            IesError::Io(ref e) => Some(e),
+            IesError::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for IesError {
+    fn from(e: io::Error) -> IesError {
+        IesError::Io(e)
+    }
+}
+
+fn format_err<T>(msg: &str) -> Result<T, IesError> {
+    Err(IesError::Format(msg.to_string()))
+}
+
+/// Numbers following the `TILT=` line, separated by white spaces or commas
+struct Numbers<'a> {
+    tokens: Vec<&'a str>,
+    pos: usize,
+}
+
+impl<'a> Numbers<'a> {
+    fn remaining(&self) -> usize {
+        self.tokens.len() - self.pos
+    }
+
+    fn next(&mut self, what: &str) -> Result<Real, IesError> {
+        match self.tokens.get(self.pos) {
+            Some(t) => {
+                self.pos += 1;
+                t.parse::<Real>()
+                    .or_else(|_| format_err(&format!("invalid {}: {}", what, t)))
+            }
+            None => format_err(&format!("missing {}", what)),
+        }
+    }
+
+    /// Count of values following in the file, thus not more than the numbers left
+    fn next_count(&mut self, what: &str) -> Result<usize, IesError> {
+        let v = self.next(what)?;
+        if v < 1.0 || v.fract() != 0.0 || v > self.remaining() as Real {
+            return format_err(&format!("invalid {}: {}", what, v));
+        }
+        Ok(v as usize)
+    }
+
+    fn next_n(&mut self, n: usize, what: &str) -> Result<Vec<Real>, IesError> {
+        if n > self.remaining() {
+            return format_err(&format!("missing {}", what));
+        }
+        (0..n).map(|_| self.next(what)).collect()
+    }
+}
+
+fn is_increasing(values: &[Real]) -> bool {
+    values.windows(2).all(|w| w[0] < w[1])
+}
+
+/// Parse photometric data. Candela values are multiplied by the candela
+/// multiplier and the ballast factors of the file.
+pub fn parse_ies<R: BufRead>(reader: R) -> Result<IesProfile, IesError> {
+    let mut lines = reader.lines();
+    let tilt = loop {
+        match lines.next() {
+            Some(line) => {
+                let line = line?;
+                if let Some(tilt) = line.trim().strip_prefix("TILT=") {
+                    break tilt.trim().to_string();
+                }
+            }
+            None => return format_err("missing TILT line"),
+        }
+    };
+    let mut body = String::new();
+    for line in lines {
+        body.push_str(&line?);
+        body.push('\n');
+    }
+    let mut nums = Numbers {
+        tokens: body
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|t| !t.is_empty())
+            .collect(),
+        pos: 0,
+    };
+
+    if tilt == "INCLUDE" {
+        nums.next("lamp to luminaire geometry")?;
+        let n = nums.next_count("tilt angle count")?;
+        match n.checked_mul(2) {
+            Some(len) => nums.next_n(len, "tilt data")?,
+            None => return format_err("too many tilt angles"),
+        };
+    }
+
+    nums.next("lamp count")?;
+    nums.next("lumens per lamp")?;
+    let multiplier = nums.next("candela multiplier")?;
+    let nv = nums.next_count("vertical angle count")?;
+    let nh = nums.next_count("horizontal angle count")?;
+    if nums.next("photometric type")? != 1.0 {
+        return format_err("only type C photometry is supported");
+    }
+    nums.next_n(4, "luminous opening")?;
+    let ballast = nums.next("ballast factor")?;
+    let ballast_lamp = nums.next("ballast-lamp photometric factor")?;
+    nums.next("input watts")?;
+
+    let vertical_angles = nums.next_n(nv, "vertical angle")?;
+    let horizontal_angles = nums.next_n(nh, "horizontal angle")?;
+    let scale = multiplier * ballast * ballast_lamp;
+    let count = match nv.checked_mul(nh) {
+        Some(count) => count,
+        None => return format_err("too many candela values"),
+    };
+    let candela = nums
+        .next_n(count, "candela value")?
+        .into_iter()
+        .map(|c| c * scale)
+        .collect();
+
+    if !is_increasing(&vertical_angles) || !is_increasing(&horizontal_angles) {
+        return format_err("angles are not increasing");
+    }
+    if vertical_angles[0] < 0.0 || vertical_angles[nv - 1] > 180.0 {
+        return format_err("vertical angle out of range");
+    }
+    if horizontal_angles[0] < 0.0 || horizontal_angles[nh - 1] > 360.0 {
+        return format_err("horizontal angle out of range");
+    }
+    Ok(IesProfile {
+        vertical_angles,
+        horizontal_angles,
+        candela,
+    })
+}
+
+/// Load the photometric data of an `.ies` file
+pub fn load_ies<P: AsRef<Path>>(path: P) -> Result<IesProfile, IesError> {
+    parse_ies(BufReader::new(File::open(path)?))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DOWNLIGHT: &str = "IESNA:LM-63-2002
+[TEST] downlight
+[MANUFAC] nobody
+TILT=INCLUDE
+1
+2
+0 90
+1.0 0.5
+1 1000 2.0 3 2 1 1 0.1 0.1 0.0
+0.5 1.0 20
+0 45.0 90
+0, 180
+100 80 0
+100 40
+0
+";
+
+    #[test]
+    fn downlight() {
+        let p = parse_ies(DOWNLIGHT.as_bytes()).unwrap();
+        assert_eq!(p.vertical_angles, vec![0.0, 45.0, 90.0]);
+        assert_eq!(p.horizontal_angles, vec![0.0, 180.0]);
+        assert_eq!(p.candela, vec![100.0, 80.0, 0.0, 100.0, 40.0, 0.0]);
+        assert!((p.candela_at(45.0, 90.0) - 60.0).abs() < 1e-9);
+
+        let type_b = DOWNLIGHT.replace("3 2 1 1", "3 2 2 1");
+        assert!(parse_ies(type_b.as_bytes()).is_err());
+        let truncated = &DOWNLIGHT[..DOWNLIGHT.len() - 8];
+        assert!(parse_ies(truncated.as_bytes()).is_err());
+        assert!(parse_ies("IESNA91\n1 2 3\n".as_bytes()).is_err());
+
+        // Counts beyond the data, whose product would overflow
+        let huge = DOWNLIGHT.replace("3 2 1 1", "1e19 1e19 1 1");
+        assert!(parse_ies(huge.as_bytes()).is_err());
+        let huge_tilt = DOWNLIGHT.replace("\n2\n0 90", "\n1e300\n0 90");
+        assert!(parse_ies(huge_tilt.as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,15 @@
+//! Importers turning model and photometric files into entities and lights
+
+pub mod gltf;
+pub mod ies;
+pub mod obj;
+pub mod ply;
+
+pub mod prelude {
+    pub use super::gltf::*;
+    pub use super::ies::*;
+    pub use super::obj::*;
+    pub use super::ply::*;
+}
+
+pub use self::prelude::*;
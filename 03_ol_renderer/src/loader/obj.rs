@@ -0,0 +1,494 @@
+//! Wavefront OBJ & MTL importer
+//!
+//! Supported statements: `v`, `vt`, `vn`, `f`, `usemtl`, `mtllib` in OBJ files and
+//! `newmtl`, `Ka`, `Kd`, `Ks`, `Ns`, `Ke` in MTL files. Other statements are ignored.
+
+use std::collections::HashMap;
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::path::Path;
+
+use entity::*;
+use material::*;
+use math::*;
+
+#[derive(Debug)]
+pub enum ObjError {
+    Io(io::Error),
+    /// Malformed statement at given line (1-based) of given file
+    Syntax {
+        file: String,
+        line: usize,
+        msg: String,
+    },
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            ObjError::Io(ref e) => write!(f, "io error: {}", e),
+            ObjError::Syntax {
+                ref file,
+                line,
+                ref msg,
+            } => write!(f, "{}:{}: {}", file, line, msg),
+        }
+    }
+}
+
+impl error::Error for ObjError {
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            ObjError::Io(ref e) => Some(e),
+            ObjError::Syntax { .. } => None,
+        }
+    }
+}
+
+impl From<io::Error> for ObjError {
+    fn from(e: io::Error) -> ObjError {
+        ObjError::Io(e)
+    }
+}
+
+/// Material parameters read from an MTL file
+#[derive(Clone, Debug, PartialEq)]
+pub struct MtlMaterial {
+    pub ambient: Color3f,
+    pub diffuse: Color3f,
+    pub specular: Color3f,
+    pub shininess: Real,
+    pub emission: Color3f,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> MtlMaterial {
+        MtlMaterial {
+            ambient: BLACK,
+            diffuse: color3(0.8, 0.8, 0.8),
+            specular: BLACK,
+            shininess: 1.0,
+            emission: BLACK,
+        }
+    }
+}
+
+impl MtlMaterial {
+    /// Map onto available BxDFs:
+    ///
+    /// * nonzero `Ke` gives a `DiffuseLight`
+    /// * otherwise `Kd` gives a `Phong` with shininess 1, and nonzero `Ks` a `Phong`
+    ///   with `Ns` as shininess, added together by `AddBxDF` when both are nonzero
+    pub fn to_entity(&self, data: MeshData) -> Box<Entity> {
+        if self.emission != BLACK {
+            let color = self.emission;
+            return Box::new(TriangleMesh::new(
+                data,
+                Box::new(move |_, _, ly, _, _, _| DiffuseLight::new(ly, color)),
+            ));
+        }
+
+        let (ambient, diffuse) = (self.ambient, self.diffuse);
+        let (specular, shininess) = (self.specular, self.shininess);
+        if specular == BLACK {
+            return Box::new(TriangleMesh::new(
+                data,
+                Box::new(move |_, lx, ly, _, _, _| Phong::new(ambient, diffuse, lx, ly, 1.0)),
+            ));
+        }
+        if diffuse == BLACK {
+            return Box::new(TriangleMesh::new(
+                data,
+                Box::new(move |_, lx, ly, _, _, _| {
+                    Phong::new(ambient, specular, lx, ly, shininess)
+                }),
+            ));
+        }
+        Box::new(TriangleMesh::new(
+            data,
+            Box::new(move |_, lx, ly, _, _, _| {
+                AddBxDF::new(
+                    Phong::new(ambient, diffuse, lx, ly, 1.0),
+                    Phong::new(BLACK, specular, lx, ly, shininess),
+                )
+            }),
+        ))
+    }
+}
+
+/// Faces sharing the same material
+pub struct ObjMesh {
+    pub material: MtlMaterial,
+    pub data: MeshData,
+}
+
+fn syntax_err<T>(file: &str, line: usize, msg: &str) -> Result<T, ObjError> {
+    Err(ObjError::Syntax {
+        file: file.to_string(),
+        line,
+        msg: msg.to_string(),
+    })
+}
+
+fn parse_reals<'a, I>(tokens: I, min_n: usize, max_n: usize) -> Option<Vec<Real>>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let ret = tokens
+        .map(|t| t.parse::<Real>().ok())
+        .collect::<Option<Vec<Real>>>()?;
+    if ret.len() < min_n || ret.len() > max_n {
+        return None;
+    }
+    Some(ret)
+}
+
+fn parse_color<'a, I>(tokens: I) -> Option<Color3f>
+where
+    I: Iterator<Item = &'a str>,
+{
+    let c = parse_reals(tokens, 1, 3)?;
+    if c.len() == 1 {
+        Some(color3(c[0], c[0], c[0]))
+    } else if c.len() == 3 {
+        Some(color3(c[0], c[1], c[2]))
+    } else {
+        None
+    }
+}
+
+/// Parse an MTL file into `(name, material)` pairs
+pub fn parse_mtl<R: BufRead>(reader: R, file: &str) -> Result<Vec<(String, MtlMaterial)>, ObjError> {
+    let mut ret: Vec<(String, MtlMaterial)> = Vec::new();
+
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_no = line_idx + 1;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) if !k.starts_with('#') => k,
+            _ => continue,
+        };
+
+        if keyword == "newmtl" {
+            let name = tokens.collect::<Vec<&str>>().join(" ");
+            if name.is_empty() {
+                return syntax_err(file, line_no, "missing material name");
+            }
+            ret.push((name, MtlMaterial::default()));
+            continue;
+        }
+
+        let mtl = match ret.last_mut() {
+            Some(m) => &mut m.1,
+            None => match keyword {
+                "Ka" | "Kd" | "Ks" | "Ke" | "Ns" => {
+                    return syntax_err(file, line_no, "material property before newmtl")
+                }
+                _ => continue,
+            },
+        };
+
+        match keyword {
+            "Ka" | "Kd" | "Ks" | "Ke" => {
+                let c = match parse_color(tokens) {
+                    Some(c) => c,
+                    None => return syntax_err(file, line_no, "invalid color"),
+                };
+                match keyword {
+                    "Ka" => mtl.ambient = c,
+                    "Kd" => mtl.diffuse = c,
+                    "Ks" => mtl.specular = c,
+                    _ => mtl.emission = c,
+                }
+            }
+            "Ns" => match parse_reals(tokens, 1, 1) {
+                Some(v) => mtl.shininess = v[0],
+                None => return syntax_err(file, line_no, "invalid shininess"),
+            },
+            _ => (),
+        }
+    }
+
+    Ok(ret)
+}
+
+/// Index tuple `v/vt/vn` of a face vertex, resolved to 0-based indices
+type VertexKey = (usize, Option<usize>, Option<usize>);
+
+struct MeshBuilder {
+    material: MtlMaterial,
+    faces: Vec<[VertexKey; 3]>,
+}
+
+impl MeshBuilder {
+    fn build(self, positions: &[Vec3f], uvs: &[Vec2f], normals: &[Vec3f]) -> ObjMesh {
+        // Attributes are kept only if every face vertex provides them
+        let has_uv = self.faces.iter().all(|f| f.iter().all(|k| k.1.is_some()));
+        let has_normal = self.faces.iter().all(|f| f.iter().all(|k| k.2.is_some()));
+
+        let mut data = MeshData::default();
+        let mut key_to_idx: HashMap<VertexKey, u32> = HashMap::new();
+        for face in &self.faces {
+            let mut tri = [0_u32; 3];
+            for (k, key) in face.iter().enumerate() {
+                let key = (
+                    key.0,
+                    if has_uv { key.1 } else { None },
+                    if has_normal { key.2 } else { None },
+                );
+                tri[k] = *key_to_idx.entry(key).or_insert_with(|| {
+                    data.positions.push(positions[key.0]);
+                    if has_uv {
+                        data.uvs.push(uvs[key.1.unwrap()]);
+                    }
+                    if has_normal {
+                        data.normals.push(normals[key.2.unwrap()]);
+                    }
+                    (data.positions.len() - 1) as u32
+                });
+            }
+            data.indices.push(tri);
+        }
+
+        ObjMesh {
+            material: self.material,
+            data,
+        }
+    }
+}
+
+/// Resolve a 1-based (or negative, relative to the end) OBJ index
+fn resolve_index(token: &str, count: usize) -> Option<usize> {
+    let idx = token.parse::<i64>().ok()?;
+    let ret = if idx > 0 {
+        idx - 1
+    } else if idx < 0 {
+        count as i64 + idx
+    } else {
+        return None;
+    };
+    if ret < 0 || ret >= count as i64 {
+        None
+    } else {
+        Some(ret as usize)
+    }
+}
+
+/// Parse an OBJ file. Materials are looked up in `mtllib` files relative to `base_dir`.
+///
+/// Faces are grouped by material, n-gons are triangulated as fans.
+pub fn parse_obj<R: BufRead>(reader: R, file: &str, base_dir: &Path) -> Result<Vec<ObjMesh>, ObjError> {
+    let mut positions: Vec<Vec3f> = Vec::new();
+    let mut uvs: Vec<Vec2f> = Vec::new();
+    let mut normals: Vec<Vec3f> = Vec::new();
+
+    let mut materials: HashMap<String, MtlMaterial> = HashMap::new();
+    let mut builders: Vec<MeshBuilder> = vec![MeshBuilder {
+        material: MtlMaterial::default(),
+        faces: Vec::new(),
+    }];
+    let mut builder_of_mtl: HashMap<String, usize> = HashMap::new();
+    let mut cur = 0;
+
+    for (line_idx, line) in reader.lines().enumerate() {
+        let line = line?;
+        let line_no = line_idx + 1;
+        let mut tokens = line.split_whitespace();
+        let keyword = match tokens.next() {
+            Some(k) if !k.starts_with('#') => k,
+            _ => continue,
+        };
+
+        match keyword {
+            "v" => match parse_reals(tokens, 3, 4) {
+                Some(v) => positions.push(vec3(v[0], v[1], v[2])),
+                None => return syntax_err(file, line_no, "invalid vertex position"),
+            },
+            "vt" => match parse_reals(tokens, 1, 3) {
+                Some(v) => uvs.push(vec2(v[0], if v.len() > 1 { v[1] } else { 0.0 })),
+                None => return syntax_err(file, line_no, "invalid texture coordinate"),
+            },
+            "vn" => match parse_reals(tokens, 3, 3) {
+                Some(v) => normals.push(vec3(v[0], v[1], v[2])),
+                None => return syntax_err(file, line_no, "invalid vertex normal"),
+            },
+            "f" => {
+                let mut keys: Vec<VertexKey> = Vec::new();
+                for token in tokens {
+                    let mut parts = token.split('/');
+                    let v = parts.next().and_then(|t| resolve_index(t, positions.len()));
+                    let vt = match parts.next() {
+                        None | Some("") => Some(None),
+                        Some(t) => resolve_index(t, uvs.len()).map(Some),
+                    };
+                    let vn = match parts.next() {
+                        None | Some("") => Some(None),
+                        Some(t) => resolve_index(t, normals.len()).map(Some),
+                    };
+                    match (v, vt, vn, parts.next()) {
+                        (Some(v), Some(vt), Some(vn), None) => keys.push((v, vt, vn)),
+                        _ => return syntax_err(file, line_no, "invalid face vertex"),
+                    }
+                }
+                if keys.len() < 3 {
+                    return syntax_err(file, line_no, "face with less than 3 vertices");
+                }
+                for k in 1..keys.len() - 1 {
+                    builders[cur].faces.push([keys[0], keys[k], keys[k + 1]]);
+                }
+            }
+            "mtllib" => {
+                for name in tokens {
+                    let path = base_dir.join(name);
+                    let reader = BufReader::new(File::open(&path)?);
+                    let mtls = parse_mtl(reader, &path.to_string_lossy())?;
+                    materials.extend(mtls);
+                }
+            }
+            "usemtl" => {
+                let name = tokens.collect::<Vec<&str>>().join(" ");
+                cur = match builder_of_mtl.get(&name) {
+                    Some(&idx) => idx,
+                    None => {
+                        let material = match materials.get(&name) {
+                            Some(m) => m.clone(),
+                            None => return syntax_err(file, line_no, "undefined material"),
+                        };
+                        builders.push(MeshBuilder {
+                            material,
+                            faces: Vec::new(),
+                        });
+                        builder_of_mtl.insert(name, builders.len() - 1);
+                        builders.len() - 1
+                    }
+                };
+            }
+            _ => (),
+        }
+    }
+
+    Ok(builders
+        .into_iter()
+        .filter(|b| !b.faces.is_empty())
+        .map(|b| b.build(&positions, &uvs, &normals))
+        .collect())
+}
+
+/// Load an OBJ file as one mesh entity per material
+pub fn load_obj<P: AsRef<Path>>(path: P) -> Result<Vec<Box<Entity>>, ObjError> {
+    let path = path.as_ref();
+    let reader = BufReader::new(File::open(path)?);
+    let base_dir = path.parent().unwrap_or_else(|| Path::new("."));
+    let meshes = parse_obj(reader, &path.to_string_lossy(), base_dir)?;
+    Ok(meshes
+        .into_iter()
+        .map(|m| m.material.to_entity(m.data))
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arena::Arena;
+
+    fn parse(src: &str) -> Result<Vec<ObjMesh>, ObjError> {
+        parse_obj(src.as_bytes(), "test.obj", Path::new("."))
+    }
+
+    #[test]
+    fn quad_and_negative_indices() {
+        let meshes = parse(
+            "# quad
+            v 0 0 0
+            v 1 0 0
+            v 1 1 0
+            v 0 1 0
+            vt 0 0
+            vt 1 0
+            vt 1 1
+            vt 0 1
+            vn 0 0 1
+            f -4/-4/1 -3/-3/1 -2/-2/1 -1/-1/1
+            ",
+        ).unwrap();
+        assert_eq!(meshes.len(), 1);
+        let data = &meshes[0].data;
+        assert_eq!(data.indices, vec![[0, 1, 2], [0, 2, 3]]);
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.uvs[2], vec2(1.0, 1.0));
+        assert_eq!(data.normals, vec![vec3(0.0, 0.0, 1.0); 4]);
+        assert_eq!(meshes[0].material, MtlMaterial::default());
+    }
+
+    #[test]
+    fn partial_attributes_dropped() {
+        let meshes = parse(
+            "v 0 0 0
+            v 1 0 0
+            v 0 1 0
+            v 0 0 1
+            vn 0 0 1
+            f 1//1 2//1 3//1
+            f 1 2 4
+            ",
+        ).unwrap();
+        let data = &meshes[0].data;
+        assert!(data.normals.is_empty());
+        assert!(data.uvs.is_empty());
+        assert_eq!(data.positions.len(), 4);
+    }
+
+    #[test]
+    fn mtl() {
+        let mtls = parse_mtl(
+            "newmtl red
+            Kd 1 0 0
+            Ks 0.5
+            Ns 20
+            illum 2
+            newmtl lamp
+            Ke 4 4 4
+            "
+                .as_bytes(),
+            "test.mtl",
+        ).unwrap();
+        assert_eq!(mtls.len(), 2);
+        assert_eq!(mtls[0].0, "red");
+        assert_eq!(mtls[0].1.diffuse, color3(1.0, 0.0, 0.0));
+        assert_eq!(mtls[0].1.specular, color3(0.5, 0.5, 0.5));
+        assert_eq!(mtls[0].1.shininess, 20.0);
+        assert_eq!(mtls[1].1.emission, color3(4.0, 4.0, 4.0));
+
+        // Both the diffuse and the specular colors are kept
+        let data = MeshData {
+            positions: vec![ZERO_VEC3, X_VEC3, Y_VEC3],
+            indices: vec![[0, 1, 2]],
+            ..MeshData::default()
+        };
+        let entity = mtls[0].1.to_entity(data);
+        let inct = entity
+            .inct(Ray::new(vec3(0.2, 0.2, 1.0), -Z_VEC3))
+            .unwrap();
+        let arena = Arena::new();
+        let material = inct.material.build(&arena);
+        let n = inct.normal;
+        assert!(material.f(n, n).relative_eq(&color3(1.5, 0.5, 0.5), 1e-9, 1e-9));
+    }
+
+    #[test]
+    fn malformed() {
+        let line_of = |src: &str| match parse(src) {
+            Err(ObjError::Syntax { line, .. }) => line,
+            _ => panic!("expected syntax error"),
+        };
+        assert_eq!(line_of("v 0 0\n"), 1);
+        assert_eq!(line_of("v 0 0 0\nv 1 0 0\nf 1 2\n"), 3);
+        assert_eq!(line_of("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n"), 4);
+        assert_eq!(line_of("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n"), 4);
+        assert_eq!(line_of("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1/1 2/1 3/1\n"), 4);
+        assert_eq!(line_of("usemtl missing\n"), 1);
+    }
+}
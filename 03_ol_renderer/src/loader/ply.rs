@@ -0,0 +1,568 @@
+//! Stanford PLY importer
+//!
+//! Reads ASCII and binary (little/big endian) files. Vertex positions, normals,
+//! texture coordinates and colors are read from the `vertex` element, polygons
+//! from the `vertex_indices` (or `vertex_index`) list of the `face` element.
+//! Other elements and properties are skipped.
+
+use std::error;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::Path;
+
+use entity::*;
+use material::*;
+use math::*;
+
+#[derive(Debug)]
+pub enum PlyError {
+    Io(io::Error),
+    /// Malformed header or body
+    Format(String),
+}
+
+impl fmt::Display for PlyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            PlyError::Io(ref e) => write!(f, "io error: {}", e),
+            PlyError::Format(ref msg) => write!(f, "invalid ply file: {}", msg),
+        }
+    }
+}
+
+impl error::Error for PlyError {
+    fn source(&self) -> Option<&(error::Error + 'static)> {
+        match *self {
+            PlyError::Io(ref e) => Some(e),
+            PlyError::Format(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for PlyError {
+    fn from(e: io::Error) -> PlyError {
+        PlyError::Io(e)
+    }
+}
+
+fn format_err<T>(msg: &str) -> Result<T, PlyError> {
+    Err(PlyError::Format(msg.to_string()))
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyType {
+    Int8,
+    UInt8,
+    Int16,
+    UInt16,
+    Int32,
+    UInt32,
+    Float32,
+    Float64,
+}
+
+impl PlyType {
+    fn from_name(name: &str) -> Option<PlyType> {
+        match name {
+            "char" | "int8" => Some(PlyType::Int8),
+            "uchar" | "uint8" => Some(PlyType::UInt8),
+            "short" | "int16" => Some(PlyType::Int16),
+            "ushort" | "uint16" => Some(PlyType::UInt16),
+            "int" | "int32" => Some(PlyType::Int32),
+            "uint" | "uint32" => Some(PlyType::UInt32),
+            "float" | "float32" => Some(PlyType::Float32),
+            "double" | "float64" => Some(PlyType::Float64),
+            _ => None,
+        }
+    }
+
+    fn is_integer(self) -> bool {
+        self != PlyType::Float32 && self != PlyType::Float64
+    }
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PlyFormat {
+    Ascii,
+    BinaryLittleEndian,
+    BinaryBigEndian,
+}
+
+enum PlyPropertyKind {
+    Scalar(PlyType),
+    /// (Type of element count, type of elements)
+    List(PlyType, PlyType),
+}
+
+struct PlyProperty {
+    name: String,
+    kind: PlyPropertyKind,
+}
+
+struct PlyElement {
+    name: String,
+    count: usize,
+    props: Vec<PlyProperty>,
+}
+
+struct PlyHeader {
+    format: PlyFormat,
+    elements: Vec<PlyElement>,
+}
+
+fn read_header<R: BufRead>(reader: &mut R) -> Result<PlyHeader, PlyError> {
+    let mut line = String::new();
+    let mut next_line = |line: &mut String| -> Result<(), PlyError> {
+        line.clear();
+        if reader.read_line(line)? == 0 {
+            return format_err("unexpected end of header");
+        }
+        Ok(())
+    };
+
+    next_line(&mut line)?;
+    if line.trim() != "ply" {
+        return format_err("missing magic number");
+    }
+
+    let mut format = None;
+    let mut elements: Vec<PlyElement> = Vec::new();
+    loop {
+        next_line(&mut line)?;
+        let tokens: Vec<&str> = line.split_whitespace().collect();
+        match tokens.first() {
+            None | Some(&"comment") | Some(&"obj_info") => (),
+            Some(&"end_header") => break,
+            Some(&"format") => {
+                if tokens.len() != 3 || tokens[2] != "1.0" {
+                    return format_err("unsupported format statement");
+                }
+                format = Some(match tokens[1] {
+                    "ascii" => PlyFormat::Ascii,
+                    "binary_little_endian" => PlyFormat::BinaryLittleEndian,
+                    "binary_big_endian" => PlyFormat::BinaryBigEndian,
+                    _ => return format_err("unknown format"),
+                });
+            }
+            Some(&"element") => {
+                let count = match tokens.get(2).and_then(|c| c.parse::<usize>().ok()) {
+                    Some(c) if tokens.len() == 3 => c,
+                    _ => return format_err("invalid element statement"),
+                };
+                elements.push(PlyElement {
+                    name: tokens[1].to_string(),
+                    count,
+                    props: Vec::new(),
+                });
+            }
+            Some(&"property") => {
+                let kind = match tokens.len() {
+                    3 => PlyType::from_name(tokens[1]).map(PlyPropertyKind::Scalar),
+                    5 if tokens[1] == "list" => {
+                        match (PlyType::from_name(tokens[2]), PlyType::from_name(tokens[3])) {
+                            (Some(c), Some(e)) if c.is_integer() => {
+                                Some(PlyPropertyKind::List(c, e))
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                let kind = match kind {
+                    Some(k) => k,
+                    None => return format_err("invalid property statement"),
+                };
+                match elements.last_mut() {
+                    Some(e) => e.props.push(PlyProperty {
+                        name: tokens[tokens.len() - 1].to_string(),
+                        kind,
+                    }),
+                    None => return format_err("property before element"),
+                }
+            }
+            Some(_) => return format_err("unknown header statement"),
+        }
+    }
+
+    match format {
+        Some(format) => Ok(PlyHeader { format, elements }),
+        None => format_err("missing format statement"),
+    }
+}
+
+/// Source of property values in the body
+trait PlyValueReader {
+    fn read(&mut self, ty: PlyType) -> Result<Real, PlyError>;
+}
+
+struct AsciiValueReader<'a> {
+    tokens: ::std::str::SplitWhitespace<'a>,
+}
+
+impl<'a> PlyValueReader for AsciiValueReader<'a> {
+    fn read(&mut self, _ty: PlyType) -> Result<Real, PlyError> {
+        match self.tokens.next().map(|t| t.parse::<Real>()) {
+            Some(Ok(v)) => Ok(v),
+            Some(Err(_)) => format_err("invalid number"),
+            None => format_err("unexpected end of body"),
+        }
+    }
+}
+
+struct BinaryValueReader<R: Read> {
+    reader: R,
+    big_endian: bool,
+}
+
+impl<R: Read> BinaryValueReader<R> {
+    fn read_bytes<'a>(&mut self, buf: &'a mut [u8; 8], n: usize) -> Result<&'a [u8], PlyError> {
+        let bytes = &mut buf[..n];
+        if let Err(e) = self.reader.read_exact(bytes) {
+            return match e.kind() {
+                io::ErrorKind::UnexpectedEof => format_err("unexpected end of body"),
+                _ => Err(PlyError::Io(e)),
+            };
+        }
+        if self.big_endian {
+            bytes.reverse();
+        }
+        Ok(bytes)
+    }
+}
+
+impl<R: Read> PlyValueReader for BinaryValueReader<R> {
+    fn read(&mut self, ty: PlyType) -> Result<Real, PlyError> {
+        // Bytes are converted to little endian by read_bytes
+        let mut buf = [0_u8; 8];
+        Ok(match ty {
+            PlyType::Int8 => self.read_bytes(&mut buf, 1)?[0] as i8 as Real,
+            PlyType::UInt8 => self.read_bytes(&mut buf, 1)?[0] as Real,
+            PlyType::Int16 => {
+                let b = self.read_bytes(&mut buf, 2)?;
+                i16::from_le_bytes([b[0], b[1]]) as Real
+            }
+            PlyType::UInt16 => {
+                let b = self.read_bytes(&mut buf, 2)?;
+                u16::from_le_bytes([b[0], b[1]]) as Real
+            }
+            PlyType::Int32 => {
+                let b = self.read_bytes(&mut buf, 4)?;
+                i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as Real
+            }
+            PlyType::UInt32 => {
+                let b = self.read_bytes(&mut buf, 4)?;
+                u32::from_le_bytes([b[0], b[1], b[2], b[3]]) as Real
+            }
+            PlyType::Float32 => {
+                let b = self.read_bytes(&mut buf, 4)?;
+                f32::from_le_bytes([b[0], b[1], b[2], b[3]]) as Real
+            }
+            PlyType::Float64 => {
+                self.read_bytes(&mut buf, 8)?;
+                f64::from_le_bytes(buf) as Real
+            }
+        })
+    }
+}
+
+/// What a vertex property is used for
+#[derive(Clone, Copy)]
+enum VertexRole {
+    Position(usize),
+    Normal(usize),
+    Uv(usize),
+    /// (Channel, scale to [0, 1])
+    Color(usize, Real),
+    Ignored,
+}
+
+fn vertex_role(prop: &PlyProperty) -> VertexRole {
+    let ty = match prop.kind {
+        PlyPropertyKind::Scalar(ty) => ty,
+        PlyPropertyKind::List(..) => return VertexRole::Ignored,
+    };
+    let color_scale = match ty {
+        PlyType::Int8 => 1.0 / 127.0,
+        PlyType::UInt8 => 1.0 / 255.0,
+        PlyType::Int16 => 1.0 / 32767.0,
+        PlyType::UInt16 => 1.0 / 65535.0,
+        _ => 1.0,
+    };
+    match prop.name.as_str() {
+        "x" => VertexRole::Position(0),
+        "y" => VertexRole::Position(1),
+        "z" => VertexRole::Position(2),
+        "nx" => VertexRole::Normal(0),
+        "ny" => VertexRole::Normal(1),
+        "nz" => VertexRole::Normal(2),
+        "u" | "s" | "texture_u" | "texture_s" => VertexRole::Uv(0),
+        "v" | "t" | "texture_v" | "texture_t" => VertexRole::Uv(1),
+        "red" | "r" => VertexRole::Color(0, color_scale),
+        "green" | "g" => VertexRole::Color(1, color_scale),
+        "blue" | "b" => VertexRole::Color(2, color_scale),
+        _ => VertexRole::Ignored,
+    }
+}
+
+fn read_body<V: PlyValueReader>(header: &PlyHeader, values: &mut V) -> Result<MeshData, PlyError> {
+    let mut data = MeshData::default();
+    let mut has_vertex = false;
+
+    for elem in &header.elements {
+        let roles: Vec<VertexRole> = elem.props.iter().map(vertex_role).collect();
+        let mut has_position = [false; 3];
+        let (mut has_normal, mut has_uv, mut has_color) = (false, false, false);
+        if elem.name == "vertex" {
+            if has_vertex {
+                return format_err("duplicated vertex element");
+            }
+            has_vertex = true;
+            for role in &roles {
+                match *role {
+                    VertexRole::Position(c) => has_position[c] = true,
+                    VertexRole::Normal(_) => has_normal = true,
+                    VertexRole::Uv(_) => has_uv = true,
+                    VertexRole::Color(..) => has_color = true,
+                    VertexRole::Ignored => (),
+                }
+            }
+            if !has_position.iter().all(|&b| b) {
+                return format_err("missing vertex position");
+            }
+        }
+
+        for _ in 0..elem.count {
+            let mut position = ZERO_VEC3;
+            let mut normal = ZERO_VEC3;
+            let mut uv = vec2(0.0, 0.0);
+            let mut color = BLACK;
+
+            for (prop, role) in elem.props.iter().zip(roles.iter()) {
+                match prop.kind {
+                    PlyPropertyKind::Scalar(ty) => {
+                        let v = values.read(ty)?;
+                        match *role {
+                            VertexRole::Position(c) => position[c] = v,
+                            VertexRole::Normal(c) => normal[c] = v,
+                            VertexRole::Uv(c) => uv[c] = v,
+                            VertexRole::Color(c, scale) => color[c] = v * scale,
+                            VertexRole::Ignored => (),
+                        }
+                    }
+                    PlyPropertyKind::List(count_ty, item_ty) => {
+                        let n = values.read(count_ty)?;
+                        if n < 0.0 {
+                            return format_err("negative list length");
+                        }
+                        let n = n as usize;
+                        let is_face_list = elem.name == "face"
+                            && (prop.name == "vertex_indices" || prop.name == "vertex_index");
+                        if !is_face_list {
+                            for _ in 0..n {
+                                values.read(item_ty)?;
+                            }
+                            continue;
+                        }
+                        if n < 3 {
+                            return format_err("face with less than 3 vertices");
+                        }
+                        // The count is untrusted, the list grows as its items are read
+                        let mut polygon = Vec::with_capacity(n.min(16));
+                        for _ in 0..n {
+                            let idx = values.read(item_ty)?;
+                            if idx < 0.0 {
+                                return format_err("negative vertex index");
+                            }
+                            polygon.push(idx as u32);
+                        }
+                        for k in 1..n - 1 {
+                            data.indices.push([polygon[0], polygon[k], polygon[k + 1]]);
+                        }
+                    }
+                }
+            }
+
+            if elem.name == "vertex" {
+                data.positions.push(position);
+                if has_normal {
+                    data.normals.push(normal);
+                }
+                if has_uv {
+                    data.uvs.push(uv);
+                }
+                if has_color {
+                    data.colors.push(color);
+                }
+            }
+        }
+    }
+
+    let vtx_cnt = data.positions.len();
+    if data
+        .indices
+        .iter()
+        .any(|f| f.iter().any(|&i| i as usize >= vtx_cnt))
+    {
+        return format_err("vertex index out of range");
+    }
+    Ok(data)
+}
+
+/// Parse a PLY file into mesh buffers
+pub fn parse_ply<R: BufRead>(mut reader: R) -> Result<MeshData, PlyError> {
+    let header = read_header(&mut reader)?;
+    match header.format {
+        PlyFormat::Ascii => {
+            let mut body = String::new();
+            reader.read_to_string(&mut body)?;
+            read_body(
+                &header,
+                &mut AsciiValueReader {
+                    tokens: body.split_whitespace(),
+                },
+            )
+        }
+        format => read_body(
+            &header,
+            &mut BinaryValueReader {
+                reader,
+                big_endian: format == PlyFormat::BinaryBigEndian,
+            },
+        ),
+    }
+}
+
+/// Load a PLY file as a mesh entity. Vertex colors are passed to `fm`.
+pub fn load_ply<P, M, FM>(path: P, fm: Box<FM>) -> Result<TriangleMesh<M, FM>, PlyError>
+where
+    P: AsRef<Path>,
+    M: BxDF + 'static,
+    FM: Sync + Fn(Vec3f, Vec3f, Vec3f, Real, Real, Color3f) -> M,
+{
+    let reader = BufReader::new(File::open(path)?);
+    Ok(TriangleMesh::new(parse_ply(reader)?, fm))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ASCII_QUAD: &str = "ply
+format ascii 1.0
+comment colored quad
+element vertex 4
+property float x
+property float y
+property float z
+property float nx
+property float ny
+property float nz
+property uchar red
+property uchar green
+property uchar blue
+element face 1
+property list uchar int vertex_indices
+end_header
+0 0 0 0 0 1 255 0 0
+1 0 0 0 0 1 0 255 0
+1 1 0 0 0 1 0 0 255
+0 1 0 0 0 1 255 255 255
+4 0 1 2 3
+";
+
+    fn check_quad(data: &MeshData) {
+        assert_eq!(data.positions.len(), 4);
+        assert_eq!(data.positions[2], vec3(1.0, 1.0, 0.0));
+        assert_eq!(data.normals, vec![vec3(0.0, 0.0, 1.0); 4]);
+        assert!(data.uvs.is_empty());
+        assert_eq!(data.colors[1], color3(0.0, 1.0, 0.0));
+        assert_eq!(data.colors[3], WHITE);
+        assert_eq!(data.indices, vec![[0, 1, 2], [0, 2, 3]]);
+    }
+
+    #[test]
+    fn ascii() {
+        check_quad(&parse_ply(ASCII_QUAD.as_bytes()).unwrap());
+    }
+
+    #[test]
+    fn signed_colors() {
+        let src = ASCII_QUAD
+            .replace("property uchar", "property char")
+            .replace(" 255", " 127");
+        check_quad(&parse_ply(src.as_bytes()).unwrap());
+    }
+
+    fn binary_quad(big_endian: bool) -> Vec<u8> {
+        let header = format!(
+            "ply\nformat {} 1.0\nelement vertex 4\n\
+             property float x\nproperty float y\nproperty float z\n\
+             property float nx\nproperty float ny\nproperty float nz\n\
+             property uchar red\nproperty uchar green\nproperty uchar blue\n\
+             element face 1\nproperty list uchar int vertex_indices\nend_header\n",
+            if big_endian {
+                "binary_big_endian"
+            } else {
+                "binary_little_endian"
+            }
+        );
+        let mut ret = header.into_bytes();
+        let f32_bytes = |v: f32| {
+            if big_endian {
+                v.to_be_bytes()
+            } else {
+                v.to_le_bytes()
+            }
+        };
+        let vertices: [([f32; 3], [u8; 3]); 4] = [
+            ([0.0, 0.0, 0.0], [255, 0, 0]),
+            ([1.0, 0.0, 0.0], [0, 255, 0]),
+            ([1.0, 1.0, 0.0], [0, 0, 255]),
+            ([0.0, 1.0, 0.0], [255, 255, 255]),
+        ];
+        for &(pos, col) in &vertices {
+            for &v in pos.iter().chain([0.0, 0.0, 1.0].iter()) {
+                ret.extend_from_slice(&f32_bytes(v));
+            }
+            ret.extend_from_slice(&col);
+        }
+        ret.push(4);
+        for i in 0..4_i32 {
+            if big_endian {
+                ret.extend_from_slice(&i.to_be_bytes());
+            } else {
+                ret.extend_from_slice(&i.to_le_bytes());
+            }
+        }
+        ret
+    }
+
+    #[test]
+    fn binary() {
+        check_quad(&parse_ply(&binary_quad(false)[..]).unwrap());
+        check_quad(&parse_ply(&binary_quad(true)[..]).unwrap());
+
+        let truncated = binary_quad(false);
+        match parse_ply(&truncated[..truncated.len() - 2]) {
+            Err(PlyError::Format(_)) => (),
+            _ => panic!("truncated body shall be rejected"),
+        }
+    }
+
+    #[test]
+    fn malformed() {
+        let is_format_err = |src: &str| match parse_ply(src.as_bytes()) {
+            Err(PlyError::Format(_)) => true,
+            _ => false,
+        };
+        assert!(is_format_err("plx\nformat ascii 1.0\nend_header\n"));
+        assert!(is_format_err("ply\nelement vertex 1\nproperty float x\nend_header\n0\n"));
+        assert!(is_format_err(&ASCII_QUAD.replace("4 0 1 2 3", "3 0 1 7")));
+        assert!(is_format_err(&ASCII_QUAD.replace("4 0 1 2 3", "4 0 1 2")));
+        let huge_face = ASCII_QUAD
+            .replace("list uchar int", "list uint int")
+            .replace("4 0 1 2 3", "4294967295 0 1 2 3");
+        assert!(is_format_err(&huge_face));
+    }
+}
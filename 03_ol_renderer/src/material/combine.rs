@@ -2,26 +2,19 @@
 
 extern crate rand;
 
+use arena::Arena;
 use material::*;
 use math::*;
 
-fn single_vec_to_option(v: Vec<Vec3f>) -> Option<Vec3f> {
-    if v.is_empty() {
-        None
-    } else {
-        Some(v[0])
-    }
-}
-
-pub struct MulBxDF {
-    a: Box<BxDF>,
-    b: Box<BxDF>,
+pub struct MulBxDF<A: BxDF, B: BxDF> {
+    a: A,
+    b: B,
     t: BxDFType,
     afac: Real,
     bfac: Real,
 }
 
-impl BxDF for MulBxDF {
+impl<A: BxDF, B: BxDF> BxDF for MulBxDF<A, B> {
     fn get_type(&self) -> BxDFType {
         self.t.clone()
     }
@@ -38,12 +31,8 @@ impl BxDF for MulBxDF {
         self.a.f(vin, vout).mul_element_wise(self.b.f(vin, vout))
     }
 
-    fn sample(&self, v: &Vec3f, n: u32) -> Vec<Vec3f> {
-        (0..n)
-            .map(|_| self.sample_once(v))
-            .filter(|s| s.is_some())
-            .map(|s| s.unwrap())
-            .collect()
+    fn sample<'a>(&self, v: &Vec3f, n: u32, arena: &'a Arena) -> &'a [Vec3f] {
+        arena.alloc_iter((0..n).filter_map(|_| self.sample_once(v, arena)))
     }
 
     fn pdf(&self, v: &Vec3f, vsample: &Vec3f) -> Real {
@@ -52,12 +41,8 @@ impl BxDF for MulBxDF {
         apdf + bpdf
     }
 
-    fn sample_upper(&self, v: &Vec3f, n: u32) -> Vec<Vec3f> {
-        (0..n)
-            .map(|_| self.sample_once_upper(v))
-            .filter(|s| s.is_some())
-            .map(|s| s.unwrap())
-            .collect()
+    fn sample_upper<'a>(&self, v: &Vec3f, n: u32, arena: &'a Arena) -> &'a [Vec3f] {
+        arena.alloc_iter((0..n).filter_map(|_| self.sample_once_upper(v, arena)))
     }
 
     fn pdf_upper(&self, v: &Vec3f, vsample: &Vec3f) -> Real {
@@ -67,24 +52,24 @@ impl BxDF for MulBxDF {
     }
 }
 
-impl MulBxDF {
-    fn sample_once(&self, v: &Vec3f) -> Option<Vec3f> {
-        let df = if rand::random::<Real>() < self.afac {
+impl<A: BxDF, B: BxDF> MulBxDF<A, B> {
+    fn sample_once(&self, v: &Vec3f, arena: &Arena) -> Option<Vec3f> {
+        let df: &BxDF = if rand::random::<Real>() < self.afac {
             &self.a
         } else {
             &self.b
         };
         match self.t {
-            BxDFType::BRDF => single_vec_to_option(df.sample_upper(v, 1)),
-            BxDFType::BSDF => single_vec_to_option(df.sample(v, 1)),
+            BxDFType::BRDF => df.sample_upper(v, 1, arena).first().cloned(),
+            BxDFType::BSDF => df.sample(v, 1, arena).first().cloned(),
         }
     }
 
-    fn sample_once_upper(&self, v: &Vec3f) -> Option<Vec3f> {
+    fn sample_once_upper(&self, v: &Vec3f, arena: &Arena) -> Option<Vec3f> {
         if rand::random::<Real>() < self.afac {
-            single_vec_to_option(self.a.sample_upper(v, 1))
+            self.a.sample_upper(v, 1, arena).first().cloned()
         } else {
-            single_vec_to_option(self.b.sample_upper(v, 1))
+            self.b.sample_upper(v, 1, arena).first().cloned()
         }
     }
 
@@ -96,7 +81,7 @@ impl MulBxDF {
         }
     }
 
-    pub fn new(a: Box<BxDF>, b: Box<BxDF>) -> MulBxDF {
+    pub fn new(a: A, b: B) -> MulBxDF<A, B> {
         let t = Self::mul_type(a.get_type(), b.get_type());
         MulBxDF {
             a,
@@ -108,15 +93,15 @@ impl MulBxDF {
     }
 }
 
-pub struct AddBxDF {
-    a: Box<BxDF>,
-    b: Box<BxDF>,
+pub struct AddBxDF<A: BxDF, B: BxDF> {
+    a: A,
+    b: B,
     t: BxDFType,
     afac: Real,
     bfac: Real,
 }
 
-impl BxDF for AddBxDF {
+impl<A: BxDF, B: BxDF> BxDF for AddBxDF<A, B> {
     fn get_type(&self) -> BxDFType {
         self.t.clone()
     }
@@ -133,12 +118,8 @@ impl BxDF for AddBxDF {
         self.a.f(vin, vout) + self.b.f(vin, vout)
     }
 
-    fn sample(&self, v: &Vec3f, n: u32) -> Vec<Vec3f> {
-        (0..n)
-            .map(|_| self.sample_once(v))
-            .filter(|s| s.is_some())
-            .map(|s| s.unwrap())
-            .collect()
+    fn sample<'a>(&self, v: &Vec3f, n: u32, arena: &'a Arena) -> &'a [Vec3f] {
+        arena.alloc_iter((0..n).filter_map(|_| self.sample_once(v, arena)))
     }
 
     fn pdf(&self, v: &Vec3f, vsample: &Vec3f) -> Real {
@@ -147,12 +128,8 @@ impl BxDF for AddBxDF {
         apdf + bpdf
     }
 
-    fn sample_upper(&self, v: &Vec3f, n: u32) -> Vec<Vec3f> {
-        (0..n)
-            .map(|_| self.sample_once_upper(v))
-            .filter(|s| s.is_some())
-            .map(|s| s.unwrap())
-            .collect()
+    fn sample_upper<'a>(&self, v: &Vec3f, n: u32, arena: &'a Arena) -> &'a [Vec3f] {
+        arena.alloc_iter((0..n).filter_map(|_| self.sample_once_upper(v, arena)))
     }
 
     fn pdf_upper(&self, v: &Vec3f, vsample: &Vec3f) -> Real {
@@ -162,24 +139,24 @@ impl BxDF for AddBxDF {
     }
 }
 
-impl AddBxDF {
-    fn sample_once(&self, v: &Vec3f) -> Option<Vec3f> {
-        let df = if rand::random::<Real>() < self.afac {
+impl<A: BxDF, B: BxDF> AddBxDF<A, B> {
+    fn sample_once(&self, v: &Vec3f, arena: &Arena) -> Option<Vec3f> {
+        let df: &BxDF = if rand::random::<Real>() < self.afac {
             &self.a
         } else {
             &self.b
         };
         match self.t {
-            BxDFType::BRDF => single_vec_to_option(df.sample_upper(v, 1)),
-            BxDFType::BSDF => single_vec_to_option(df.sample(v, 1)),
+            BxDFType::BRDF => df.sample_upper(v, 1, arena).first().cloned(),
+            BxDFType::BSDF => df.sample(v, 1, arena).first().cloned(),
         }
     }
 
-    fn sample_once_upper(&self, v: &Vec3f) -> Option<Vec3f> {
+    fn sample_once_upper(&self, v: &Vec3f, arena: &Arena) -> Option<Vec3f> {
         if rand::random::<Real>() < self.afac {
-            single_vec_to_option(self.a.sample_upper(v, 1))
+            self.a.sample_upper(v, 1, arena).first().cloned()
         } else {
-            single_vec_to_option(self.b.sample_upper(v, 1))
+            self.b.sample_upper(v, 1, arena).first().cloned()
         }
     }
 
@@ -191,9 +168,9 @@ impl AddBxDF {
         }
     }
 
-    pub fn new(a: Box<BxDF>, b: Box<BxDF>) -> MulBxDF {
+    pub fn new(a: A, b: B) -> AddBxDF<A, B> {
         let t = Self::mul_type(a.get_type(), b.get_type());
-        MulBxDF {
+        AddBxDF {
             a,
             b,
             t,
@@ -1,5 +1,6 @@
 //! Diffuse light source
 
+use arena::Arena;
 use material::*;
 use math::*;
 
@@ -29,16 +30,16 @@ impl BxDF for DiffuseLight {
         BLACK
     }
 
-    fn sample(&self, _: &Vec3f, _: u32) -> Vec<Vec3f> {
-        vec![]
+    fn sample<'a>(&self, _: &Vec3f, _: u32, _: &'a Arena) -> &'a [Vec3f] {
+        &[]
     }
 
     fn pdf(&self, _: &Vec3f, _: &Vec3f) -> Real {
         0.0
     }
 
-    fn sample_upper(&self, _: &Vec3f, _: u32) -> Vec<Vec3f> {
-        vec![]
+    fn sample_upper<'a>(&self, _: &Vec3f, _: u32, _: &'a Arena) -> &'a [Vec3f] {
+        &[]
     }
 
     fn pdf_upper(&self, _: &Vec3f, _: &Vec3f) -> Real {
@@ -3,11 +3,14 @@
 pub mod combine;
 pub mod diffuse_light;
 pub mod phong;
+pub mod texture;
 
 pub mod prelude {
     pub use super::combine::*;
     pub use super::diffuse_light::*;
     pub use super::phong::*;
+    pub use super::texture::*;
+    use arena::Arena;
     use math::*;
 
     #[derive(Clone, PartialEq, Eq)]
@@ -32,14 +35,14 @@ pub mod prelude {
         fn f(&self, vin: Vec3f, vout: Vec3f) -> Color3f;
 
         /// Sample directions
-        fn sample(&self, v: &Vec3f, n: u32) -> Vec<Vec3f>;
+        fn sample<'a>(&self, v: &Vec3f, n: u32, arena: &'a Arena) -> &'a [Vec3f];
 
         /// Probability density
         fn pdf(&self, v: &Vec3f, vsample: &Vec3f) -> Real;
 
         /// (For Combinator) Probability of sampling on upper hemisphere
-        fn sample_upper(&self, v: &Vec3f, n: u32) -> Vec<Vec3f> {
-            self.sample(v, n)
+        fn sample_upper<'a>(&self, v: &Vec3f, n: u32, arena: &'a Arena) -> &'a [Vec3f] {
+            self.sample(v, n, arena)
         }
 
         /// (For Combinator) Probability density on upper hemisphere
@@ -2,6 +2,7 @@
 
 extern crate rand;
 
+use arena::Arena;
 use material::*;
 use math::*;
 
@@ -39,12 +40,8 @@ impl BxDF for Phong {
         self.specular * alpha.powf(self.shininess)
     }
 
-    fn sample(&self, _v: &Vec3f, n: u32) -> Vec<Vec3f> {
-        let mut ret = Vec::with_capacity(n as usize);
-        for _ in 0..n {
-            ret.push(self.trans * hemisphere_uniform());
-        }
-        ret
+    fn sample<'a>(&self, _v: &Vec3f, n: u32, arena: &'a Arena) -> &'a [Vec3f] {
+        arena.alloc_iter((0..n).map(|_| self.trans * hemisphere_uniform()))
     }
 
     fn pdf(&self, _v: &Vec3f, _vsample: &Vec3f) -> Real {
@@ -0,0 +1,95 @@
+//! Image textures for material closures
+
+use buf::Buf2D;
+use math::*;
+
+/// Convert an sRGB encoded channel value to linear
+pub fn srgb_to_linear(c: Real) -> Real {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Texture backed by a 2D image.
+///
+/// `(u, v) = (0, 0)` is the top-left corner of the image, `(1, 1)` the bottom-right one.
+/// Coordinates outside `[0, 1]` repeat.
+#[derive(Clone)]
+pub struct ImageTexture {
+    buf: Buf2D<Color3f>,
+}
+
+impl ImageTexture {
+    pub fn new(buf: Buf2D<Color3f>) -> ImageTexture {
+        ImageTexture { buf }
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.buf.get_width()
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.buf.get_height()
+    }
+
+    /// Bilinear sampling
+    pub fn sample(&self, u: Real, v: Real) -> Color3f {
+        self.bilinear(u, v, false)
+    }
+
+    /// Bilinear sampling repeating along u only. Coordinates v outside `[0, 1]`
+    /// and the half pixels at the top and bottom edges read the edge rows, as
+    /// suits latitude-longitude maps.
+    pub fn sample_clamp_v(&self, u: Real, v: Real) -> Color3f {
+        self.bilinear(u, v, true)
+    }
+
+    fn bilinear(&self, u: Real, v: Real, clamp_v: bool) -> Color3f {
+        let (w, h) = (self.buf.get_width(), self.buf.get_height());
+        let x = (u - u.floor()) * w as Real - 0.5;
+        let y = if clamp_v {
+            v.clamp(0.0, 1.0) * h as Real - 0.5
+        } else {
+            (v - v.floor()) * h as Real - 0.5
+        };
+        let (x0, y0) = (x.floor(), y.floor());
+        let (fx, fy) = (x - x0, y - y0);
+
+        let wrap = |i: Real, n: u32| (i as i64).rem_euclid(n as i64) as u32;
+        let clamp = |i: Real, n: u32| (i as i64).max(0).min(n as i64 - 1) as u32;
+        let (x0, x1) = (wrap(x0, w), wrap(x0 + 1.0, w));
+        let (y0, y1) = if clamp_v {
+            (clamp(y0, h), clamp(y0 + 1.0, h))
+        } else {
+            (wrap(y0, h), wrap(y0 + 1.0, h))
+        };
+
+        let top = self.buf[(x0, y0)] * (1.0 - fx) + self.buf[(x1, y0)] * fx;
+        let bottom = self.buf[(x0, y1)] * (1.0 - fx) + self.buf[(x1, y1)] * fx;
+        top * (1.0 - fy) + bottom * fy
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bilinear() {
+        let tex = ImageTexture::new(Buf2D::from_fn(2, 2, |x, y| {
+            color3(x as Real, y as Real, 0.0)
+        }));
+        assert_eq!(tex.sample(0.25, 0.25), color3(0.0, 0.0, 0.0));
+        assert_eq!(tex.sample(0.75, 0.75), color3(1.0, 1.0, 0.0));
+        assert_eq!(tex.sample(0.5, 0.25), color3(0.5, 0.0, 0.0));
+        assert_eq!(tex.sample(1.25, -0.75), tex.sample(0.25, 0.25));
+
+        // Edge rows are not blended together
+        assert_eq!(tex.sample(0.25, 0.0), color3(0.0, 0.5, 0.0));
+        assert_eq!(tex.sample_clamp_v(0.25, 0.0), color3(0.0, 0.0, 0.0));
+        assert_eq!(tex.sample_clamp_v(0.25, 1.0), color3(0.0, 1.0, 0.0));
+        assert_eq!(tex.sample_clamp_v(1.5, 0.5), color3(0.5, 0.5, 0.0));
+    }
+}
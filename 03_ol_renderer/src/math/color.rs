@@ -12,6 +12,11 @@ pub fn max_elememt_wise_color3(a: Color3f, b: Color3f) -> Color3f {
     color3(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
 }
 
+/// Relative luminance of a linear (Rec. 709) color
+pub fn luminance(c: Color3f) -> Real {
+    0.2126 * c.x + 0.7152 * c.y + 0.0722 * c.z
+}
+
 pub trait ColorTrait3<T> {
     fn r(&self) -> T;
     fn g(&self) -> T;
@@ -0,0 +1,159 @@
+//! Piecewise-constant distributions for importance sampling
+//!
+//! See Pharr, M., Jakob, W., & Humphreys, G. (2016).
+//! Physically based rendering: From theory to implementation, 13.6.
+
+use math::*;
+
+/// Distribution over `[0, 1)` proportional to a step function with equally wide steps
+#[derive(Clone, Debug)]
+pub struct Distribution1D {
+    func: Vec<Real>,
+    /// `cdf[i]` is the probability of `[0, i / n)`, with `n + 1` entries
+    cdf: Vec<Real>,
+    func_int: Real,
+}
+
+impl Distribution1D {
+    /// Steps are given by the non-negative values of `func`.
+    /// An all-zero function is sampled uniformly.
+    pub fn new(func: &[Real]) -> Distribution1D {
+        assert!(!func.is_empty());
+        let n = func.len();
+        let mut cdf = Vec::with_capacity(n + 1);
+        cdf.push(0.0);
+        for i in 0..n {
+            assert!(func[i] >= 0.0);
+            let last = cdf[i];
+            cdf.push(last + func[i] / n as Real);
+        }
+        let func_int = cdf[n];
+        for (i, c) in cdf.iter_mut().enumerate() {
+            *c = if func_int > 0.0 {
+                *c / func_int
+            } else {
+                i as Real / n as Real
+            };
+        }
+        Distribution1D {
+            func: func.to_vec(),
+            cdf,
+            func_int,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.func.len()
+    }
+
+    /// Integral of the step function over `[0, 1)`
+    pub fn get_integral(&self) -> Real {
+        self.func_int
+    }
+
+    /// Map `u` in `[0, 1)` to a point of `[0, 1)`, returned with its density
+    /// and the index of its step
+    pub fn sample_continuous(&self, u: Real) -> (Real, Real, usize) {
+        // Last step whose cdf is not greater than u, skipping zero-probability ones
+        let mut lo = 0;
+        let mut hi = self.count();
+        while hi - lo > 1 {
+            let mid = (lo + hi) / 2;
+            if self.cdf[mid] <= u {
+                lo = mid;
+            } else {
+                hi = mid;
+            }
+        }
+        let width = self.cdf[lo + 1] - self.cdf[lo];
+        let du = if width > 0.0 {
+            ((u - self.cdf[lo]) / width).clamp(0.0, 1.0)
+        } else {
+            0.0
+        };
+        let x = ((lo as Real + du) / self.count() as Real).min(1.0 - 1e-12);
+        (x, self.pdf(x), lo)
+    }
+
+    /// Density of `sample_continuous` at `x` in `[0, 1)`
+    pub fn pdf(&self, x: Real) -> Real {
+        let n = self.count();
+        let i = ((x * n as Real) as usize).min(n - 1);
+        if self.func_int > 0.0 {
+            self.func[i] / self.func_int
+        } else {
+            1.0
+        }
+    }
+}
+
+/// Distribution over `[0, 1)^2` proportional to a function sampled on a grid,
+/// drawing `v` from the marginal density first and then `u` given `v`
+#[derive(Clone, Debug)]
+pub struct Distribution2D {
+    conditional: Vec<Distribution1D>,
+    marginal: Distribution1D,
+}
+
+impl Distribution2D {
+    /// `func` has `nu * nv` values, the row of `v` index `j` starting at `j * nu`
+    pub fn new(func: &[Real], nu: usize, nv: usize) -> Distribution2D {
+        assert!(nu > 0 && nv > 0 && func.len() == nu * nv);
+        let conditional: Vec<Distribution1D> = func.chunks(nu).map(Distribution1D::new).collect();
+        let marginal = Distribution1D::new(
+            &conditional
+                .iter()
+                .map(|d| d.get_integral())
+                .collect::<Vec<_>>(),
+        );
+        Distribution2D {
+            conditional,
+            marginal,
+        }
+    }
+
+    /// Map `u` in `[0, 1)^2` to a point of `[0, 1)^2`, returned with its density
+    pub fn sample_continuous(&self, u: Vec2f) -> (Vec2f, Real) {
+        let (v, pdf_v, row) = self.marginal.sample_continuous(u.y);
+        let (u, pdf_u, _) = self.conditional[row].sample_continuous(u.x);
+        (vec2(u, v), pdf_u * pdf_v)
+    }
+
+    /// Density of `sample_continuous` at `p` in `[0, 1)^2`
+    pub fn pdf(&self, p: Vec2f) -> Real {
+        let nv = self.conditional.len();
+        let row = ((p.y * nv as Real) as usize).min(nv - 1);
+        self.marginal.pdf(p.y) * self.conditional[row].pdf(p.x)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn steps() {
+        let d = Distribution1D::new(&[1.0, 0.0, 3.0]);
+        assert!((d.get_integral() - 4.0 / 3.0).abs() < 1e-9);
+        let (x, pdf, i) = d.sample_continuous(0.125);
+        assert!((x - 1.0 / 6.0).abs() < 1e-9 && (pdf - 0.75).abs() < 1e-9 && i == 0);
+        // The empty step is never chosen
+        let (x, pdf, i) = d.sample_continuous(0.25);
+        assert!((x - 2.0 / 3.0).abs() < 1e-9 && (pdf - 2.25).abs() < 1e-9 && i == 2);
+        assert_eq!(d.pdf(0.5), 0.0);
+
+        let uniform = Distribution1D::new(&[0.0, 0.0]);
+        assert_eq!(uniform.sample_continuous(0.75), (0.75, 1.0, 1));
+    }
+
+    #[test]
+    fn grid() {
+        let d = Distribution2D::new(&[1.0, 1.0, 0.0, 2.0], 2, 2);
+        let (p, pdf) = d.sample_continuous(vec2(0.3, 0.2));
+        assert!(p.y < 0.5 && (pdf - 1.0).abs() < 1e-9);
+        let (p, pdf) = d.sample_continuous(vec2(0.3, 0.7));
+        assert!(p.x >= 0.5 && p.y >= 0.5 && (pdf - 2.0).abs() < 1e-9);
+        assert!((d.pdf(p) - pdf).abs() < 1e-9);
+        assert_eq!(d.pdf(vec2(0.2, 0.8)), 0.0);
+    }
+}
@@ -0,0 +1,51 @@
+//! Floating-point rounding error bounds
+//!
+//! See Pharr, M., Jakob, W., & Humphreys, G. (2016).
+//! Physically based rendering: From theory to implementation, 3.9.
+
+use math::*;
+
+/// Half of the distance between 1 and the next representable `Real`
+pub const MACHINE_EPSILON: Real = Real::EPSILON * 0.5;
+
+/// Bound of the relative error accumulated by `n` floating-point operations
+pub fn gamma(n: u32) -> Real {
+    let n = n as Real * MACHINE_EPSILON;
+    n / (1.0 - n)
+}
+
+/// The smallest representable `Real` greater than `v`
+pub fn next_float_up(v: Real) -> Real {
+    if v.is_infinite() && v > 0.0 {
+        return v;
+    }
+    let v = if v == -0.0 { 0.0 } else { v };
+    let bits = v.to_bits();
+    Real::from_bits(if v >= 0.0 { bits + 1 } else { bits - 1 })
+}
+
+/// The greatest representable `Real` less than `v`
+pub fn next_float_down(v: Real) -> Real {
+    if v.is_infinite() && v < 0.0 {
+        return v;
+    }
+    let v = if v == 0.0 { -0.0 } else { v };
+    let bits = v.to_bits();
+    Real::from_bits(if v > 0.0 { bits - 1 } else { bits + 1 })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn next_float() {
+        assert!(next_float_up(1.0) > 1.0);
+        assert_eq!(next_float_up(1.0) - 1.0, 2.0 * MACHINE_EPSILON);
+        assert!(next_float_down(1.0) < 1.0);
+        assert!(next_float_up(0.0) > 0.0);
+        assert!(next_float_down(0.0) < 0.0);
+        assert!(next_float_up(-1.0) > -1.0);
+        assert_eq!(next_float_down(next_float_up(-2.5)), -2.5);
+    }
+}
@@ -0,0 +1,161 @@
+//! Keyframed affine transformations and spline interpolation
+
+use math::*;
+use std::ops::{Add, Mul, Sub};
+
+/// Affine transformation at a given time, decomposed into scale, rotation
+/// and translation (applied in this order)
+#[derive(Clone, Copy, Debug)]
+pub struct Keyframe {
+    pub time: Real,
+    pub translation: Vec3f,
+    pub rotation: Quatf,
+    pub scale: Vec3f,
+}
+
+impl Keyframe {
+    pub fn new(time: Real, translation: Vec3f, rotation: Quatf, scale: Vec3f) -> Keyframe {
+        Keyframe {
+            time,
+            translation,
+            rotation,
+            scale,
+        }
+    }
+
+    pub fn to_mat4(&self) -> Mat4f {
+        Mat4f::from_translation(self.translation)
+            * Mat4f::from(self.rotation)
+            * Mat4f::from_nonuniform_scale(self.scale.x, self.scale.y, self.scale.z)
+    }
+}
+
+/// Transformation interpolated between keyframes.
+///
+/// Translation and scale are interpolated linearly, rotation spherically.
+/// Before the first (after the last) keyframe the transformation stays constant.
+#[derive(Clone)]
+pub struct KeyframeTrack {
+    keys: Vec<Keyframe>,
+}
+
+impl KeyframeTrack {
+    pub fn new(mut keys: Vec<Keyframe>) -> KeyframeTrack {
+        assert!(!keys.is_empty());
+        keys.sort_by(|a, b| a.time.partial_cmp(&b.time).unwrap());
+        KeyframeTrack { keys }
+    }
+
+    pub fn get_keyframes(&self) -> &[Keyframe] {
+        &self.keys
+    }
+
+    /// Interpolated keyframe at `time`
+    pub fn keyframe_at(&self, time: Real) -> Keyframe {
+        let first = &self.keys[0];
+        let last = &self.keys[self.keys.len() - 1];
+        if time <= first.time {
+            return Keyframe { time, ..*first };
+        }
+        if time >= last.time {
+            return Keyframe { time, ..*last };
+        }
+
+        let i = self.keys.iter().position(|k| k.time > time).unwrap();
+        let (k0, k1) = (&self.keys[i - 1], &self.keys[i]);
+        let s = (time - k0.time) / (k1.time - k0.time);
+        // Take the shorter arc
+        let r1 = if k0.rotation.dot(k1.rotation) < 0.0 {
+            -k1.rotation
+        } else {
+            k1.rotation
+        };
+        Keyframe {
+            time,
+            translation: k0.translation + s * (k1.translation - k0.translation),
+            rotation: k0.rotation.slerp(r1, s).normalize(),
+            scale: k0.scale + s * (k1.scale - k0.scale),
+        }
+    }
+
+    /// Interpolated transformation matrix at `time`
+    pub fn at(&self, time: Real) -> Mat4f {
+        self.keyframe_at(time).to_mat4()
+    }
+}
+
+/// Value at `time` of the Catmull-Rom spline passing `values` at the increasing `times`.
+///
+/// Tangents are estimated from the neighboring keys, accounting for their spacing
+/// in time. Before the first (after the last) key the value stays constant.
+pub fn catmull_rom<T>(times: &[Real], values: &[T], time: Real) -> T
+where
+    T: Copy + Add<Output = T> + Sub<Output = T> + Mul<Real, Output = T>,
+{
+    let n = times.len();
+    assert!(n > 0 && n == values.len());
+    if time <= times[0] {
+        return values[0];
+    }
+    if time >= times[n - 1] {
+        return values[n - 1];
+    }
+
+    let i = times.iter().position(|&t| t > time).unwrap();
+    let tangent = |k: usize| {
+        let (a, b) = (k.saturating_sub(1), (k + 1).min(n - 1));
+        (values[b] - values[a]) * (1.0 / (times[b] - times[a]))
+    };
+    let h = times[i] - times[i - 1];
+    let s = (time - times[i - 1]) / h;
+    let (s2, s3) = (s * s, s * s * s);
+    values[i - 1] * (2.0 * s3 - 3.0 * s2 + 1.0)
+        + tangent(i - 1) * ((s3 - 2.0 * s2 + s) * h)
+        + values[i] * (3.0 * s2 - 2.0 * s3)
+        + tangent(i) * ((s3 - s2) * h)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spline() {
+        // Quadratic functions sampled uniformly are reproduced exactly
+        let times = [0.0, 1.0, 2.0, 3.0];
+        let values: Vec<Real> = times.iter().map(|t| t * t).collect();
+        assert!((catmull_rom(&times, &values, 1.5) - 2.25).abs() < 1e-9);
+        assert!((catmull_rom(&times, &values, 2.0) - 4.0).abs() < 1e-9);
+        assert_eq!(catmull_rom(&times, &values, 5.0), 9.0);
+
+        let points = [ZERO_VEC3, X_VEC3];
+        let p = catmull_rom(&times[..2], &points, 0.25);
+        assert!(p.relative_eq(&vec3(0.25, 0.0, 0.0), 1e-9, 1e-9));
+    }
+
+    #[test]
+    fn interpolate() {
+        let track = KeyframeTrack::new(vec![
+            Keyframe::new(
+                1.0,
+                vec3(2.0, 0.0, 0.0),
+                Quatf::from_angle_y(Deg(90.0)),
+                vec3(3.0, 3.0, 3.0),
+            ),
+            Keyframe::new(
+                0.0,
+                ZERO_VEC3,
+                Quatf::from_angle_y(Deg(0.0)),
+                vec3(1.0, 1.0, 1.0),
+            ),
+        ]);
+        let k = track.keyframe_at(0.5);
+        assert!(k.translation.relative_eq(&vec3(1.0, 0.0, 0.0), 1e-9, 1e-9));
+        assert!(k.scale.relative_eq(&vec3(2.0, 2.0, 2.0), 1e-9, 1e-9));
+        let expected = Mat4f::from_translation(vec3(1.0, 0.0, 0.0))
+            * Mat4f::from_angle_y(Deg(45.0))
+            * Mat4f::from_scale(2.0);
+        assert!(track.at(0.5).relative_eq(&expected, 1e-9, 1e-9));
+        assert!(track.at(-1.0).relative_eq(&Mat4f::identity(), 1e-9, 1e-9));
+    }
+}
@@ -8,6 +8,7 @@ use math::{Clamp, Real};
 pub use self::cgmath::ElementWise;
 pub use self::cgmath::InnerSpace;
 pub use self::cgmath::Matrix;
+pub use self::cgmath::Rotation3;
 pub use self::cgmath::SquareMatrix;
 
 pub use self::cgmath::{dot, vec2, vec3, vec4};
@@ -20,6 +21,8 @@ pub type Vec2f = Vector2<Real>;
 pub type Vec3f = Vector3<Real>;
 pub type Vec4f = Vector4<Real>;
 
+pub type Quatf = Quaternion<Real>;
+
 pub const ZERO_VEC3: Vec3f = Vec3f {
     x: 0.0,
     y: 0.0,
@@ -52,6 +55,29 @@ pub fn max_elememt_wise_vec3(a: Vec3f, b: Vec3f) -> Vec3f {
     vec3(a.x.max(b.x), a.y.max(b.y), a.z.max(b.z))
 }
 
+/// Element-wise absolute value
+pub fn abs_vec3(v: Vec3f) -> Vec3f {
+    vec3(v.x.abs(), v.y.abs(), v.z.abs())
+}
+
+/// An arbitrary unit vector perpendicular to `v`
+pub fn perpendicular_vec3(v: Vec3f) -> Vec3f {
+    let a = if v.x.abs() > 0.9 { Y_VEC3 } else { X_VEC3 };
+    v.cross(a).normalize()
+}
+
+/// Orthonormal basis with `y` (normalized) as its second axis, as the columns of a
+/// matrix mapping the samples on the hemisphere facing positive y around `y`
+pub fn local_frame(y: Vec3f) -> Mat3f {
+    let y = y.normalize();
+    let x = perpendicular_vec3(y);
+    Mat3f::from_cols(x, y, x.cross(y))
+}
+
+pub fn min_elememt_wise_vec3(a: Vec3f, b: Vec3f) -> Vec3f {
+    vec3(a.x.min(b.x), a.y.min(b.y), a.z.min(b.z))
+}
+
 impl Clamp<Real> for Vec2f {
     fn clamp(&self, min_v: Real, max_v: Real) -> Self {
         Vec2f {
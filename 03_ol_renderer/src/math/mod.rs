@@ -3,8 +3,12 @@
 extern crate cgmath;
 
 pub mod color;
+pub mod distribution;
+pub mod float;
+pub mod keyframe;
 pub mod mat;
 pub mod model;
+pub mod poly;
 pub mod sample;
 
 pub mod real {
@@ -34,8 +38,12 @@ pub mod prelude {
     pub use super::cgmath::{Angle, ApproxEq, Deg, Rad};
     pub use super::clamp::*;
     pub use super::color::*;
+    pub use super::distribution::*;
+    pub use super::float::*;
+    pub use super::keyframe::*;
     pub use super::mat::*;
     pub use super::model::ray::*;
+    pub use super::poly::*;
     pub use super::real::*;
     pub use super::sample::*;
 }
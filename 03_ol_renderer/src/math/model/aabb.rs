@@ -1,5 +1,6 @@
 //! AABB bounding box
 
+use super::Ray;
 use math::*;
 
 /// Axis-aligned bounding box
@@ -48,4 +49,111 @@ impl AABB {
     pub fn to_aabb_bounding(&self) -> AABB {
         self.clone()
     }
+
+    /// Bounding box of a single point
+    pub fn from_point(p: Vec3f) -> AABB {
+        AABB { lower: p, upper: p }
+    }
+
+    /// Smallest box containing both `self` and `other`
+    pub fn union(&self, other: &AABB) -> AABB {
+        AABB {
+            lower: min_elememt_wise_vec3(self.lower, other.lower),
+            upper: max_elememt_wise_vec3(self.upper, other.upper),
+        }
+    }
+
+    /// Smallest box containing both `self` and `p`
+    pub fn union_point(&self, p: Vec3f) -> AABB {
+        self.union(&AABB::from_point(p))
+    }
+
+    pub fn centroid(&self) -> Vec3f {
+        0.5 * (self.lower + self.upper)
+    }
+
+    pub fn diagonal(&self) -> Vec3f {
+        self.upper - self.lower
+    }
+
+    /// Index of the longest axis (0 for x, 1 for y, 2 for z)
+    pub fn max_extent(&self) -> usize {
+        let d = self.diagonal();
+        if d.x > d.y && d.x > d.z {
+            0
+        } else if d.y > d.z {
+            1
+        } else {
+            2
+        }
+    }
+
+    pub fn surface_area(&self) -> Real {
+        let d = self.diagonal();
+        2.0 * (d.x * d.y + d.y * d.z + d.z * d.x)
+    }
+
+    /// Slab test. Returns `(t_near, t_far)` of the ray segment inside the box,
+    /// where `t_near` is clamped to 0 when the ray starts inside.
+    pub fn is_intersected(&self, r: &Ray) -> Option<(Real, Real)> {
+        let mut t0 = r.t_min;
+        let mut t1 = r.t_max;
+        for axis in 0..3 {
+            let inv_d = 1.0 / r.d[axis];
+            let mut t_near = (self.lower[axis] - r.p[axis]) * inv_d;
+            let mut t_far = (self.upper[axis] - r.p[axis]) * inv_d;
+            if t_near > t_far {
+                ::std::mem::swap(&mut t_near, &mut t_far);
+            }
+            // NaN (ray lying on a slab plane) leaves the interval unchanged
+            t0 = t0.max(t_near);
+            t1 = t1.min(t_far);
+            if t0 > t1 {
+                return None;
+            }
+        }
+        Some((t0, t1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn union_and_area() {
+        let a = AABB::new(vec3(0.0, 0.0, 0.0), vec3(1.0, 1.0, 1.0));
+        let b = AABB::new(vec3(-2.0, 0.5, 0.0), vec3(0.5, 2.0, 1.0));
+        let u = a.union(&b);
+        assert_eq!(*u.get_lower(), vec3(-2.0, 0.0, 0.0));
+        assert_eq!(*u.get_upper(), vec3(1.0, 2.0, 1.0));
+        assert_eq!(a.surface_area(), 6.0);
+        assert_eq!(u.surface_area(), 2.0 * (3.0 * 2.0 + 2.0 * 1.0 + 1.0 * 3.0));
+        assert_eq!(u.max_extent(), 0);
+    }
+
+    #[test]
+    fn slab() {
+        let b = AABB::new(vec3(-1.0, -1.0, -1.0), vec3(1.0, 1.0, 1.0));
+
+        let (t0, t1) = b
+            .is_intersected(&Ray::new(vec3(-5.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0)))
+            .unwrap();
+        assert!(t0.relative_eq(&4.0, 1e-9, 1e-9));
+        assert!(t1.relative_eq(&6.0, 1e-9, 1e-9));
+
+        let (t0, _) = b
+            .is_intersected(&Ray::new(vec3(0.0, 0.0, 0.0), vec3(1.0, 1.0, 0.0)))
+            .unwrap();
+        assert_eq!(t0, 0.0);
+
+        assert!(
+            b.is_intersected(&Ray::new(vec3(-5.0, 2.0, 0.0), vec3(1.0, 0.0, 0.0)))
+                .is_none()
+        );
+        assert!(
+            b.is_intersected(&Ray::new(vec3(5.0, 0.0, 0.0), vec3(1.0, 0.0, 0.0)))
+                .is_none()
+        );
+    }
 }
@@ -0,0 +1,219 @@
+//! Cone along y-axis
+
+use super::{Ray, AABB};
+use math::*;
+
+/// Cone with base disk centred at `base` and apex at `base + height * y`.
+/// Other orientations can be made with `entity::InstanceEntity`.
+#[derive(Clone)]
+pub struct Cone {
+    base: Vec3f,
+    radius: Real,
+    height: Real,
+    capped: bool,
+}
+
+impl Cone {
+    pub fn new(base: Vec3f, radius: Real, height: Real, capped: bool) -> Cone {
+        assert!(radius > 0.0 && height > 0.0);
+        Cone {
+            base,
+            radius,
+            height,
+            capped,
+        }
+    }
+
+    pub fn get_base(&self) -> &Vec3f {
+        &self.base
+    }
+
+    pub fn get_radius(&self) -> Real {
+        self.radius
+    }
+
+    pub fn get_height(&self) -> Real {
+        self.height
+    }
+
+    /// Is the cone a closed surface
+    pub fn is_closed(&self) -> bool {
+        self.capped
+    }
+
+    pub fn to_aabb_bounding(&self) -> AABB {
+        AABB::new(
+            self.base - vec3(self.radius, 0.0, self.radius),
+            self.base + vec3(self.radius, self.height, self.radius),
+        )
+    }
+
+    /// All `t` where the ray crosses the surface, in any order
+    pub fn all_incts(&self, r: &Ray) -> Vec<Real> {
+        // x^2 + z^2 = k^2 (h - y)^2 in local space
+        let q = r.p - self.base;
+        let d = r.d;
+        let k = self.radius / self.height;
+        let k2 = k * k;
+        let hy = self.height - q.y;
+        let mut ret = Vec::with_capacity(3);
+
+        let a = d.x * d.x + d.z * d.z - k2 * d.y * d.y;
+        let b = 2.0 * (q.x * d.x + q.z * d.z + k2 * hy * d.y);
+        let c = q.x * q.x + q.z * q.z - k2 * hy * hy;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for &t in &[t0, t1] {
+                let y = q.y + t * d.y;
+                if y >= 0.0 && y <= self.height {
+                    ret.push(t);
+                }
+            }
+            if t0 == t1 {
+                ret.pop();
+            }
+        }
+
+        if self.capped && d.y != 0.0 {
+            let t = -q.y / d.y;
+            let (x, z) = (q.x + t * d.x, q.z + t * d.z);
+            if x * x + z * z <= self.radius * self.radius {
+                ret.push(t);
+            }
+        }
+        ret
+    }
+
+    /// Spans `(t_in, t_out)` of the whole line `r.p + t * r.d` inside the cone.
+    /// An uncapped cone encloses no volume, and has no spans.
+    pub fn inct_intervals(&self, r: &Ray) -> Vec<(Real, Real)> {
+        if !self.capped {
+            return vec![];
+        }
+        let ts = self.all_incts(r);
+        if ts.len() < 2 {
+            return vec![];
+        }
+        let t_in = ts.iter().cloned().fold(REAL_MAX, Real::min);
+        let t_out = ts.iter().cloned().fold(-REAL_MAX, Real::max);
+        if t_in < t_out {
+            vec![(t_in, t_out)]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Compute the nearest intersection with given ray
+    pub fn nearest_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        let t = self
+            .all_incts(&r)
+            .into_iter()
+            .filter(|&t| r.is_in_range(t))
+            .fold(None, |acc: Option<Real>, t| {
+                Some(acc.map_or(t, |a| a.min(t)))
+            })?;
+        Some((t, r.t_to_point(t)))
+    }
+
+    fn is_on_cap(&self, p: Vec3f) -> bool {
+        if !self.capped {
+            return false;
+        }
+        let q = p - self.base;
+        let rho = (q.x * q.x + q.z * q.z).sqrt();
+        let side_rho = self.radius * (1.0 - q.y / self.height);
+        q.y.abs() < (rho - side_rho).abs()
+    }
+
+    /// Intersection point at `t` along `r` and a bound of its absolute error
+    pub fn inct_point(&self, r: &Ray, t: Real) -> (Vec3f, Vec3f) {
+        let p = r.t_to_point(t);
+        (p, gamma(7) * abs_vec3(p - self.base) + gamma(1) * abs_vec3(p))
+    }
+
+    /// u: angle around the axis / 2pi.
+    /// v: height / `height` on the side, distance to axis / `radius` on the cap.
+    pub fn inct_to_uv(&self, p: Vec3f) -> (Real, Real) {
+        let q = p - self.base;
+        let u = q.x.atan2(q.z) / (2.0 * REAL_PI) + 0.5;
+        if self.is_on_cap(p) {
+            (u, (q.x * q.x + q.z * q.z).sqrt() / self.radius)
+        } else {
+            (u, q.y / self.height)
+        }
+    }
+
+    /// Partial derivatives of the point with respect to `inct_to_uv`
+    pub fn inct_to_dpduv(&self, p: Vec3f) -> (Vec3f, Vec3f) {
+        let q = p - self.base;
+        let phi = q.x.atan2(q.z);
+        let (sin_phi, cos_phi) = (phi.sin(), phi.cos());
+        let dpdu = 2.0 * REAL_PI * vec3(q.z, 0.0, -q.x);
+        if self.is_on_cap(p) {
+            (dpdu, self.radius * vec3(sin_phi, 0.0, cos_phi))
+        } else {
+            (dpdu, vec3(-self.radius * sin_phi, self.height, -self.radius * cos_phi))
+        }
+    }
+
+    /// Tangent around the axis on the side
+    pub fn inct_to_local_x(&self, p: Vec3f) -> Vec3f {
+        let q = p - self.base;
+        let t = vec3(-q.z, 0.0, q.x);
+        if self.is_on_cap(p) || t.magnitude2() < 1e-24 {
+            X_VEC3
+        } else {
+            t.normalize()
+        }
+    }
+
+    /// Outward normal
+    pub fn inct_to_local_y(&self, p: Vec3f) -> Vec3f {
+        if self.is_on_cap(p) {
+            return -Y_VEC3;
+        }
+        let q = p - self.base;
+        let rho = (q.x * q.x + q.z * q.z).sqrt();
+        if rho < 1e-12 {
+            return Y_VEC3;
+        }
+        vec3(
+            self.height * q.x / rho,
+            self.radius,
+            self.height * q.z / rho,
+        )
+        .normalize()
+    }
+
+    pub fn inct_to_local_z(&self, p: Vec3f) -> Vec3f {
+        self.inct_to_local_x(p).cross(self.inct_to_local_y(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inct() {
+        // 45 degrees cone
+        let cone = Cone::new(vec3(0.0, 0.0, 0.0), 1.0, 1.0, true);
+
+        let (t, p) = cone
+            .nearest_inct(Ray::new(vec3(-3.0, 0.5, 0.0), vec3(1.0, 0.0, 0.0)))
+            .unwrap();
+        assert!(t.relative_eq(&2.5, 1e-9, 1e-9));
+        let n: Vec3f = vec3(-1.0, 1.0, 0.0).normalize();
+        assert!(cone.inct_to_local_y(p).relative_eq(&n, 1e-9, 1e-9));
+
+        let (t, p) = cone
+            .nearest_inct(Ray::new(vec3(0.5, -1.0, 0.0), vec3(0.0, 1.0, 0.0)))
+            .unwrap();
+        assert!(t.relative_eq(&1.0, 1e-9, 1e-9));
+        assert_eq!(cone.inct_to_local_y(p), -Y_VEC3);
+
+        // Passes over the apex
+        assert!(cone
+            .nearest_inct(Ray::new(vec3(-3.0, 1.1, 0.0), vec3(1.0, 0.0, 0.0)))
+            .is_none());
+    }
+}
@@ -0,0 +1,234 @@
+//! Cylinder along y-axis
+
+use super::{Ray, AABB};
+use math::*;
+
+/// Cylinder from `base` (centre of the bottom) to `base + height * y`.
+/// Other orientations can be made with `entity::InstanceEntity`.
+#[derive(Clone)]
+pub struct Cylinder {
+    base: Vec3f,
+    radius: Real,
+    height: Real,
+    capped: bool,
+}
+
+#[derive(Clone, Copy, PartialEq, Debug)]
+enum CylinderPart {
+    Side,
+    Bottom,
+    Top,
+}
+
+impl Cylinder {
+    pub fn new(base: Vec3f, radius: Real, height: Real, capped: bool) -> Cylinder {
+        assert!(radius > 0.0 && height > 0.0);
+        Cylinder {
+            base,
+            radius,
+            height,
+            capped,
+        }
+    }
+
+    pub fn get_base(&self) -> &Vec3f {
+        &self.base
+    }
+
+    pub fn get_radius(&self) -> Real {
+        self.radius
+    }
+
+    pub fn get_height(&self) -> Real {
+        self.height
+    }
+
+    /// Is the cylinder a closed surface
+    pub fn is_closed(&self) -> bool {
+        self.capped
+    }
+
+    pub fn to_aabb_bounding(&self) -> AABB {
+        AABB::new(
+            self.base - vec3(self.radius, 0.0, self.radius),
+            self.base + vec3(self.radius, self.height, self.radius),
+        )
+    }
+
+    /// All `t` where the ray crosses the surface, in any order
+    pub fn all_incts(&self, r: &Ray) -> Vec<Real> {
+        let q = r.p - self.base;
+        let d = r.d;
+        let mut ret = Vec::with_capacity(4);
+
+        let a = d.x * d.x + d.z * d.z;
+        let b = 2.0 * (q.x * d.x + q.z * d.z);
+        let c = q.x * q.x + q.z * q.z - self.radius * self.radius;
+        if let Some((t0, t1)) = solve_quadratic(a, b, c) {
+            for &t in &[t0, t1] {
+                let y = q.y + t * d.y;
+                if y >= 0.0 && y <= self.height {
+                    ret.push(t);
+                }
+            }
+        }
+
+        if self.capped && d.y != 0.0 {
+            for &cap_y in &[0.0, self.height] {
+                let t = (cap_y - q.y) / d.y;
+                let (x, z) = (q.x + t * d.x, q.z + t * d.z);
+                if x * x + z * z <= self.radius * self.radius {
+                    ret.push(t);
+                }
+            }
+        }
+        ret
+    }
+
+    /// Spans `(t_in, t_out)` of the whole line `r.p + t * r.d` inside the cylinder.
+    /// An uncapped cylinder encloses no volume, and has no spans.
+    pub fn inct_intervals(&self, r: &Ray) -> Vec<(Real, Real)> {
+        if !self.capped {
+            return vec![];
+        }
+        let ts = self.all_incts(r);
+        if ts.len() < 2 {
+            return vec![];
+        }
+        let t_in = ts.iter().cloned().fold(REAL_MAX, Real::min);
+        let t_out = ts.iter().cloned().fold(-REAL_MAX, Real::max);
+        if t_in < t_out {
+            vec![(t_in, t_out)]
+        } else {
+            vec![]
+        }
+    }
+
+    /// Compute the nearest intersection with given ray
+    pub fn nearest_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        let t = self
+            .all_incts(&r)
+            .into_iter()
+            .filter(|&t| r.is_in_range(t))
+            .fold(None, |acc: Option<Real>, t| {
+                Some(acc.map_or(t, |a| a.min(t)))
+            })?;
+        Some((t, r.t_to_point(t)))
+    }
+
+    fn part_of(&self, p: Vec3f) -> CylinderPart {
+        if !self.capped {
+            return CylinderPart::Side;
+        }
+        let q = p - self.base;
+        let side_dis = ((q.x * q.x + q.z * q.z).sqrt() - self.radius).abs();
+        if q.y.abs() < side_dis {
+            CylinderPart::Bottom
+        } else if (q.y - self.height).abs() < side_dis {
+            CylinderPart::Top
+        } else {
+            CylinderPart::Side
+        }
+    }
+
+    /// Intersection point at `t` along `r`, projected back onto the surface,
+    /// and a bound of its absolute error
+    pub fn inct_point(&self, r: &Ray, t: Real) -> (Vec3f, Vec3f) {
+        let p = r.t_to_point(t);
+        let q = p - self.base;
+        let q = match self.part_of(p) {
+            CylinderPart::Side => {
+                let s = self.radius / (q.x * q.x + q.z * q.z).sqrt();
+                vec3(q.x * s, q.y, q.z * s)
+            }
+            CylinderPart::Bottom => vec3(q.x, 0.0, q.z),
+            CylinderPart::Top => vec3(q.x, self.height, q.z),
+        };
+        let p = self.base + q;
+        (p, gamma(5) * abs_vec3(q) + gamma(1) * abs_vec3(p))
+    }
+
+    /// u: angle around the axis / 2pi.
+    /// v: height / `height` on the side, distance to axis / `radius` on caps.
+    pub fn inct_to_uv(&self, p: Vec3f) -> (Real, Real) {
+        let q = p - self.base;
+        let u = q.x.atan2(q.z) / (2.0 * REAL_PI) + 0.5;
+        match self.part_of(p) {
+            CylinderPart::Side => (u, q.y / self.height),
+            _ => (u, (q.x * q.x + q.z * q.z).sqrt() / self.radius),
+        }
+    }
+
+    /// Partial derivatives of the point with respect to `inct_to_uv`
+    pub fn inct_to_dpduv(&self, p: Vec3f) -> (Vec3f, Vec3f) {
+        let q = p - self.base;
+        let phi = q.x.atan2(q.z);
+        let dpdu = 2.0 * REAL_PI * vec3(q.z, 0.0, -q.x);
+        match self.part_of(p) {
+            CylinderPart::Side => (dpdu, vec3(0.0, self.height, 0.0)),
+            _ => (dpdu, self.radius * vec3(phi.sin(), 0.0, phi.cos())),
+        }
+    }
+
+    /// Tangent around the axis on the side
+    pub fn inct_to_local_x(&self, p: Vec3f) -> Vec3f {
+        match self.part_of(p) {
+            CylinderPart::Side => {
+                let q = p - self.base;
+                vec3(-q.z, 0.0, q.x).normalize()
+            }
+            _ => X_VEC3,
+        }
+    }
+
+    /// Outward normal
+    pub fn inct_to_local_y(&self, p: Vec3f) -> Vec3f {
+        match self.part_of(p) {
+            CylinderPart::Side => {
+                let q = p - self.base;
+                vec3(q.x, 0.0, q.z).normalize()
+            }
+            CylinderPart::Bottom => -Y_VEC3,
+            CylinderPart::Top => Y_VEC3,
+        }
+    }
+
+    pub fn inct_to_local_z(&self, p: Vec3f) -> Vec3f {
+        self.inct_to_local_x(p).cross(self.inct_to_local_y(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inct() {
+        let cyl = Cylinder::new(vec3(0.0, 1.0, 0.0), 1.0, 2.0, true);
+
+        let (t, p) = cyl
+            .nearest_inct(Ray::new(vec3(-3.0, 2.0, 0.0), vec3(1.0, 0.0, 0.0)))
+            .unwrap();
+        assert!(t.relative_eq(&2.0, 1e-9, 1e-9));
+        assert!(cyl.inct_to_local_y(p).relative_eq(&-X_VEC3, 1e-9, 1e-9));
+        assert!(dot(cyl.inct_to_local_x(p), cyl.inct_to_local_y(p)).abs() < 1e-9);
+
+        let (t, p) = cyl
+            .nearest_inct(Ray::new(vec3(0.5, 5.0, 0.0), vec3(0.0, -1.0, 0.0)))
+            .unwrap();
+        assert!(t.relative_eq(&2.0, 1e-9, 1e-9));
+        assert_eq!(cyl.inct_to_local_y(p), Y_VEC3);
+        assert!(cyl.inct_to_uv(p).1.relative_eq(&0.5, 1e-9, 1e-9));
+
+        // Uncapped: the ray passes through the open top and hits the inside
+        let open = Cylinder::new(vec3(0.0, 1.0, 0.0), 1.0, 2.0, false);
+        let (_, p) = open
+            .nearest_inct(Ray::new(vec3(0.0, 5.0, 0.0), vec3(0.5, -1.0, 0.0)))
+            .unwrap();
+        assert!(p.x.relative_eq(&1.0, 1e-9, 1e-9));
+
+        assert!(cyl
+            .nearest_inct(Ray::new(vec3(-3.0, 3.5, 0.0), vec3(1.0, 0.0, 0.0)))
+            .is_none());
+    }
+}
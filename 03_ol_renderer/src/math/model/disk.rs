@@ -0,0 +1,120 @@
+//! Disk
+
+use super::{Plane, Ray, AABB};
+use math::*;
+
+/// Disk with given centre, unit normal and radius
+#[derive(Clone)]
+pub struct Disk {
+    plane: Plane,
+    radius: Real,
+}
+
+impl Disk {
+    pub fn new(centre: Vec3f, normal: Vec3f, radius: Real) -> Disk {
+        Disk {
+            plane: Plane::new(centre, normal),
+            radius,
+        }
+    }
+
+    pub fn get_centre(&self) -> &Vec3f {
+        self.plane.get_point()
+    }
+
+    pub fn get_normal(&self) -> &Vec3f {
+        self.plane.get_normal()
+    }
+
+    pub fn get_radius(&self) -> Real {
+        self.radius
+    }
+
+    pub fn get_plane(&self) -> &Plane {
+        &self.plane
+    }
+
+    /// Does point `p` of the plane lie within the disk
+    pub fn contains(&self, p: Vec3f) -> bool {
+        (p - self.get_centre()).magnitude2() <= self.radius * self.radius
+    }
+
+    pub fn to_aabb_bounding(&self) -> AABB {
+        // Extent along each axis is radius * sin<axis, normal>
+        let n = self.get_normal();
+        let ext = vec3(
+            (1.0 - n.x * n.x).max(0.0).sqrt(),
+            (1.0 - n.y * n.y).max(0.0).sqrt(),
+            (1.0 - n.z * n.z).max(0.0).sqrt(),
+        ) * self.radius;
+        AABB::new(self.get_centre() - ext, self.get_centre() + ext)
+    }
+
+    /// Compute the nearest intersection with given ray
+    pub fn nearest_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        let t = self.plane.nearest_t(&r)?;
+        let p = r.t_to_point(t);
+        if !self.contains(p) {
+            return None;
+        }
+        Some((t, p))
+    }
+
+    /// Intersection point at `t` along `r` and a bound of its absolute error
+    pub fn inct_point(&self, r: &Ray, t: Real) -> (Vec3f, Vec3f) {
+        self.plane.inct_point(r, t)
+    }
+
+    /// u: angle around the normal / 2pi, v: distance to centre / radius
+    pub fn inct_to_uv(&self, p: Vec3f) -> (Real, Real) {
+        let (x, z) = self.plane.inct_to_uv(p);
+        let phi = z.atan2(x);
+        let phi = if phi < 0.0 { phi + 2.0 * REAL_PI } else { phi };
+        (phi / (2.0 * REAL_PI), (x * x + z * z).sqrt() / self.radius)
+    }
+
+    /// Partial derivatives of the point with respect to `inct_to_uv`
+    pub fn inct_to_dpduv(&self, p: Vec3f) -> (Vec3f, Vec3f) {
+        let (x, z) = self.plane.inct_to_uv(p);
+        let (lx, lz) = (self.inct_to_local_x(p), self.inct_to_local_z(p));
+        let phi = z.atan2(x);
+        (
+            2.0 * REAL_PI * (x * lz - z * lx),
+            self.radius * (phi.cos() * lx + phi.sin() * lz),
+        )
+    }
+
+    pub fn inct_to_local_x(&self, p: Vec3f) -> Vec3f {
+        self.plane.inct_to_local_x(p)
+    }
+
+    pub fn inct_to_local_y(&self, p: Vec3f) -> Vec3f {
+        self.plane.inct_to_local_y(p)
+    }
+
+    pub fn inct_to_local_z(&self, p: Vec3f) -> Vec3f {
+        self.plane.inct_to_local_z(p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inct() {
+        let disk = Disk::new(vec3(0.0, 1.0, 0.0), vec3(0.0, 1.0, 0.0), 2.0);
+        let b = disk.to_aabb_bounding();
+        assert!(b.get_lower().relative_eq(&vec3(-2.0, 1.0, -2.0), 1e-9, 1e-9));
+        assert!(b.get_upper().relative_eq(&vec3(2.0, 1.0, 2.0), 1e-9, 1e-9));
+
+        let (_, p) = disk
+            .nearest_inct(Ray::new(vec3(1.0, 3.0, 0.0), vec3(0.0, -1.0, 0.0)))
+            .unwrap();
+        let (_, v) = disk.inct_to_uv(p);
+        assert!(v.relative_eq(&0.5, 1e-9, 1e-9));
+        assert!(disk
+            .nearest_inct(Ray::new(vec3(2.5, 3.0, 0.0), vec3(0.0, -1.0, 0.0)))
+            .is_none());
+    }
+}
@@ -14,14 +14,26 @@
 //!
 
 pub mod aabb;
+pub mod cone;
+pub mod cylinder;
+pub mod disk;
+pub mod plane;
 pub mod ray;
+pub mod rectangle;
 pub mod sphere;
+pub mod torus;
 pub mod triangle;
 
 pub mod prelude {
     pub use super::aabb::*;
+    pub use super::cone::*;
+    pub use super::cylinder::*;
+    pub use super::disk::*;
+    pub use super::plane::*;
     pub use super::ray::*;
+    pub use super::rectangle::*;
     pub use super::sphere::*;
+    pub use super::torus::*;
     pub use super::triangle::*;
 }
 
@@ -0,0 +1,91 @@
+//! Infinite plane
+
+use super::{Ray, AABB};
+use math::*;
+
+/// Infinite plane through `point` with unit normal `normal`
+#[derive(Clone)]
+pub struct Plane {
+    point: Vec3f,
+    normal: Vec3f,
+    local_x: Vec3f,
+}
+
+impl Plane {
+    pub fn new(point: Vec3f, normal: Vec3f) -> Plane {
+        let normal = normal.normalize();
+        Plane {
+            point,
+            normal,
+            local_x: perpendicular_vec3(normal),
+        }
+    }
+
+    pub fn get_point(&self) -> &Vec3f {
+        &self.point
+    }
+
+    pub fn get_normal(&self) -> &Vec3f {
+        &self.normal
+    }
+
+    /// Unbounded box. Planes are better kept out of BVHs.
+    pub fn to_aabb_bounding(&self) -> AABB {
+        AABB::new(
+            vec3(-REAL_MAX, -REAL_MAX, -REAL_MAX),
+            vec3(REAL_MAX, REAL_MAX, REAL_MAX),
+        )
+    }
+
+    /// `t` of the intersection with given ray, ignoring rays parallel to the plane
+    pub fn nearest_t(&self, r: &Ray) -> Option<Real> {
+        let denom = dot(r.d, self.normal);
+        if denom.abs() < 1e-12 {
+            return None;
+        }
+        let t = dot(self.point - r.p, self.normal) / denom;
+        if r.is_in_range(t) {
+            Some(t)
+        } else {
+            None
+        }
+    }
+
+    /// Compute the nearest intersection with given ray
+    pub fn nearest_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        let t = self.nearest_t(&r)?;
+        Some((t, r.t_to_point(t)))
+    }
+
+    /// Intersection point at `t` along `r`, projected back onto the plane,
+    /// and a bound of its absolute error
+    pub fn inct_point(&self, r: &Ray, t: Real) -> (Vec3f, Vec3f) {
+        let local = r.t_to_point(t) - self.point;
+        let local = local - dot(local, self.normal) * self.normal;
+        let p = self.point + local;
+        (p, gamma(5) * abs_vec3(local) + gamma(1) * abs_vec3(p))
+    }
+
+    /// Coordinates of a point on the plane along local x and z axes
+    pub fn inct_to_uv(&self, p: Vec3f) -> (Real, Real) {
+        let d = p - self.point;
+        (dot(d, self.local_x), dot(d, self.inct_to_local_z(p)))
+    }
+
+    /// Partial derivatives of the point with respect to `inct_to_uv`
+    pub fn inct_to_dpduv(&self, p: Vec3f) -> (Vec3f, Vec3f) {
+        (self.local_x, self.inct_to_local_z(p))
+    }
+
+    pub fn inct_to_local_x(&self, _p: Vec3f) -> Vec3f {
+        self.local_x
+    }
+
+    pub fn inct_to_local_y(&self, _p: Vec3f) -> Vec3f {
+        self.normal
+    }
+
+    pub fn inct_to_local_z(&self, _p: Vec3f) -> Vec3f {
+        self.local_x.cross(self.normal)
+    }
+}
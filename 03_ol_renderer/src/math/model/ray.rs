@@ -1,65 +1,126 @@
 //! Implementation of p = p0 + t * d
 
+use math::*;
 use std::ops::Mul;
-use math::{mat::*, Clamp, Real};
 
-/// Start point & direction: p0 + t * d
+/// Start point & direction: p0 + t * d.
+///
+/// Only `t` in the open interval `(t_min, t_max)` counts when intersecting the ray.
 #[derive(Clone)]
 pub struct Ray {
     pub p: Vec3f,
     pub d: Vec3f,
+    /// Moment the ray is cast at, used to intersect moving entities
+    pub time: Real,
+    pub t_min: Real,
+    pub t_max: Real,
 }
 
-/// Ray and end point
-/// End point = if t < 0.0 { r.p + t * r.d } else { Non-existent }
-#[derive(Clone)]
-pub struct RayT {
-    pub r: Ray,
-    pub t: Real,
-}
+/// Relative distance to the target point left out of shadow rays
+pub const SHADOW_EPSILON: Real = 1e-4;
 
 impl Mul<Ray> for Mat4f {
     type Output = Ray;
 
     fn mul(self, r: Ray) -> Ray {
-        Ray::new(
-            (self * vec4(r.p.x, r.p.y, r.p.z, 1.0)).xyz(),
-            (self * vec4(r.d.x, r.d.y, r.d.z, 0.0)).xyz(),
-        )
+        // The range is scaled along with the direction
+        let d = (self * vec4(r.d.x, r.d.y, r.d.z, 0.0)).xyz();
+        let scale = d.magnitude();
+        Ray::with_time((self * vec4(r.p.x, r.p.y, r.p.z, 1.0)).xyz(), d, r.time)
+            .with_range(r.t_min * scale, r.t_max * scale)
+    }
+}
+
+/// Offset a point `p` with absolute error bound `p_error` along the normal `n`,
+/// to the side of `w`, so that rays leaving the result don't hit the surface again.
+///
+/// See Pharr, M., Jakob, W., & Humphreys, G. (2016).
+/// Physically based rendering: From theory to implementation, 3.9.5.
+pub fn offset_ray_origin(p: Vec3f, p_error: Vec3f, n: Vec3f, w: Vec3f) -> Vec3f {
+    let dis = dot(abs_vec3(n), p_error);
+    let offset = if dot(w, n) < 0.0 { -dis * n } else { dis * n };
+    let mut po = p + offset;
+    // Round away from p
+    for i in 0..3 {
+        if offset[i] > 0.0 {
+            po[i] = next_float_up(po[i]);
+        } else if offset[i] < 0.0 {
+            po[i] = next_float_down(po[i]);
+        }
     }
+    po
 }
 
 impl Ray {
     pub fn new(p: Vec3f, d: Vec3f) -> Ray {
+        Ray::with_time(p, d, 0.0)
+    }
+
+    pub fn with_time(p: Vec3f, d: Vec3f, time: Real) -> Ray {
         Ray {
             p,
             d: d.normalize(),
+            time,
+            t_min: 0.0,
+            t_max: REAL_MAX,
         }
     }
 
+    pub fn with_range(self, t_min: Real, t_max: Real) -> Ray {
+        Ray {
+            t_min,
+            t_max,
+            ..self
+        }
+    }
+
+    /// Ray leaving surface point `p` (with error bound `p_error` and normal `n`) along `d`
+    pub fn spawn(p: Vec3f, p_error: Vec3f, n: Vec3f, d: Vec3f, time: Real) -> Ray {
+        Ray::with_time(offset_ray_origin(p, p_error, n, d), d, time)
+    }
+
+    /// Ray leaving surface point `p` (with error bound `p_error` and normal `n`)
+    /// towards `target`, ending just before it
+    pub fn spawn_to(p: Vec3f, p_error: Vec3f, n: Vec3f, target: Vec3f, time: Real) -> Ray {
+        let o = offset_ray_origin(p, p_error, n, target - p);
+        let d = target - o;
+        Ray::with_time(o, d, time).with_range(0.0, d.magnitude() * (1.0 - SHADOW_EPSILON))
+    }
+
     pub fn t_to_point(&self, t: Real) -> Vec3f {
         self.p + t * self.d
     }
-}
 
-impl Clamp<Real> for RayT {
-    fn clamp(&self, min_v: Real, max_v: Real) -> RayT {
-        RayT {
-            t: self.t.clamp(min_v, max_v),
-            ..self.clone()
-        }
+    /// Is `t` in `(t_min, t_max)`
+    pub fn is_in_range(&self, t: Real) -> bool {
+        t > self.t_min && t < self.t_max
     }
 }
 
-impl RayT {
-    pub fn new(p: Vec3f, d: Vec3f, t: Real) -> RayT {
-        RayT {
-            r: Ray::new(p, d),
-            t,
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn offset() {
+        let p = vec3(1e6, 1.0, -3.0);
+        let err = vec3(1e-9, 1e-9, 1e-9);
+        let po = offset_ray_origin(p, err, Y_VEC3, vec3(0.3, 1.0, 0.0));
+        assert!(po.y > p.y + 1e-9);
+        assert_eq!((po.x, po.z), (p.x, p.z));
+        let po = offset_ray_origin(p, err, Y_VEC3, vec3(0.3, -1.0, 0.0));
+        assert!(po.y < p.y - 1e-9);
+
+        let r = Ray::spawn_to(p, err, Y_VEC3, p + vec3(0.0, 2.0, 0.0), 0.5);
+        assert!(r.is_in_range(1.9) && !r.is_in_range(2.0));
+        assert_eq!(r.time, 0.5);
     }
 
-    pub fn from_ray(r: Ray, t: Real) -> RayT {
-        RayT { r, t }
+    #[test]
+    fn transform_range() {
+        let r = Ray::new(ZERO_VEC3, X_VEC3).with_range(1.0, 2.0);
+        let r = Mat4f::from_scale(3.0) * r;
+        assert!(r.t_min.relative_eq(&3.0, 1e-12, 1e-12));
+        assert!(r.t_max.relative_eq(&6.0, 1e-12, 1e-12));
     }
 }
@@ -0,0 +1,132 @@
+//! Rectangle (parallelogram)
+
+use super::{Plane, Ray, AABB};
+use math::*;
+
+/// Parallelogram `corner + u * edge_u + v * edge_v` for `u, v` in `[0, 1]`
+#[derive(Clone)]
+pub struct Rectangle {
+    corner: Vec3f,
+    edge_u: Vec3f,
+    edge_v: Vec3f,
+    plane: Plane,
+}
+
+impl Rectangle {
+    pub fn new(corner: Vec3f, edge_u: Vec3f, edge_v: Vec3f) -> Rectangle {
+        Rectangle {
+            corner,
+            edge_u,
+            edge_v,
+            plane: Plane::new(corner, edge_u.cross(edge_v)),
+        }
+    }
+
+    pub fn get_corner(&self) -> &Vec3f {
+        &self.corner
+    }
+
+    pub fn get_edge_u(&self) -> &Vec3f {
+        &self.edge_u
+    }
+
+    pub fn get_edge_v(&self) -> &Vec3f {
+        &self.edge_v
+    }
+
+    pub fn get_normal(&self) -> &Vec3f {
+        self.plane.get_normal()
+    }
+
+    pub fn get_plane(&self) -> &Plane {
+        &self.plane
+    }
+
+    /// Does point `p` of the plane lie within the parallelogram
+    pub fn contains(&self, p: Vec3f) -> bool {
+        let (u, v) = self.inct_to_uv(p);
+        (0.0..=1.0).contains(&u) && (0.0..=1.0).contains(&v)
+    }
+
+    pub fn area(&self) -> Real {
+        self.edge_u.cross(self.edge_v).magnitude()
+    }
+
+    pub fn to_aabb_bounding(&self) -> AABB {
+        AABB::from_point(self.corner)
+            .union_point(self.corner + self.edge_u)
+            .union_point(self.corner + self.edge_v)
+            .union_point(self.corner + self.edge_u + self.edge_v)
+    }
+
+    /// Compute the nearest intersection with given ray
+    pub fn nearest_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        let t = self.plane.nearest_t(&r)?;
+        let p = r.t_to_point(t);
+        if !self.contains(p) {
+            return None;
+        }
+        Some((t, p))
+    }
+
+    /// Intersection point at `t` along `r` and a bound of its absolute error
+    pub fn inct_point(&self, r: &Ray, t: Real) -> (Vec3f, Vec3f) {
+        self.plane.inct_point(r, t)
+    }
+
+    /// Solve `p = corner + u * edge_u + v * edge_v`
+    pub fn inct_to_uv(&self, p: Vec3f) -> (Real, Real) {
+        let d = p - self.corner;
+        let (uu, uv, vv) = (
+            self.edge_u.magnitude2(),
+            dot(self.edge_u, self.edge_v),
+            self.edge_v.magnitude2(),
+        );
+        let (du, dv) = (dot(d, self.edge_u), dot(d, self.edge_v));
+        let det = uu * vv - uv * uv;
+        ((du * vv - dv * uv) / det, (dv * uu - du * uv) / det)
+    }
+
+    /// Partial derivatives of the point with respect to `inct_to_uv`
+    pub fn inct_to_dpduv(&self, _p: Vec3f) -> (Vec3f, Vec3f) {
+        (self.edge_u, self.edge_v)
+    }
+
+    /// Along `edge_u`
+    pub fn inct_to_local_x(&self, _p: Vec3f) -> Vec3f {
+        self.edge_u.normalize()
+    }
+
+    pub fn inct_to_local_y(&self, p: Vec3f) -> Vec3f {
+        self.plane.inct_to_local_y(p)
+    }
+
+    pub fn inct_to_local_z(&self, p: Vec3f) -> Vec3f {
+        self.inct_to_local_x(p).cross(self.inct_to_local_y(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inct() {
+        let rect = Rectangle::new(vec3(0.0, 0.0, 0.0), vec3(2.0, 0.0, 0.0), vec3(1.0, 0.0, 1.0));
+        let (t, p) = rect
+            .nearest_inct(Ray::new(vec3(1.5, 2.0, 0.5), vec3(0.0, -1.0, 0.0)))
+            .unwrap();
+        assert!(t.relative_eq(&2.0, 1e-9, 1e-9));
+        let (u, v) = rect.inct_to_uv(p);
+        assert!(u.relative_eq(&0.5, 1e-9, 1e-9));
+        assert!(v.relative_eq(&0.5, 1e-9, 1e-9));
+        assert!(dot(rect.inct_to_local_x(p), rect.inct_to_local_y(p)).abs() < 1e-9);
+
+        assert!(rect
+            .nearest_inct(Ray::new(vec3(0.2, 2.0, 0.5), vec3(0.0, -1.0, 0.0)))
+            .is_none());
+        assert!(rect
+            .nearest_inct(Ray::new(vec3(1.5, 2.0, 0.5), vec3(0.0, 1.0, 0.0)))
+            .is_none());
+    }
+}
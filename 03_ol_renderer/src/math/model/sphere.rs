@@ -1,6 +1,6 @@
 //! Ideal sphere
 
-use super::Ray;
+use super::{Ray, AABB};
 use math::*;
 
 /// Ideal sphere model
@@ -33,6 +33,11 @@ impl Sphere {
         self
     }
 
+    pub fn to_aabb_bounding(&self) -> AABB {
+        let r = vec3(self.radius, self.radius, self.radius);
+        AABB::new(self.centre - r, self.centre + r)
+    }
+
     /// Is a given point in the sphere
     pub fn is_point_in(&self, p: Vec3f) -> bool {
         (self.centre - p).magnitude() < self.radius
@@ -68,16 +73,39 @@ impl Sphere {
         let t1 = (-b + delta) * recip_2_a;
         let t2 = (-b - delta) * recip_2_a;
 
-        if t1 <= 0.0 && t2 <= 0.0 {
+        let (t_near, t_far) = if t1 < t2 { (t1, t2) } else { (t2, t1) };
+        let t = if r.is_in_range(t_near) {
+            t_near
+        } else if r.is_in_range(t_far) {
+            t_far
+        } else {
             return None;
-        }
-
-        let t1 = if t1 < 0.0 { REAL_MAX } else { t1 };
-        let t = if t2 < 0.0 { t1 } else { t1.min(t2) };
+        };
 
         Some((t, r.t_to_point(t)))
     }
 
+    /// Intersection point at `t` along `r`, projected back onto the sphere,
+    /// and a bound of its absolute error
+    pub fn inct_point(&self, r: &Ray, t: Real) -> (Vec3f, Vec3f) {
+        let local = r.t_to_point(t) - self.centre;
+        let local = local * (self.radius / local.magnitude());
+        let p = self.centre + local;
+        (p, gamma(5) * abs_vec3(local) + gamma(1) * abs_vec3(p))
+    }
+
+    /// Spans `(t_in, t_out)` of the whole line `r.p + t * r.d` inside the sphere
+    pub fn inct_intervals(&self, r: &Ray) -> Vec<(Real, Real)> {
+        let p_c = r.p - self.centre;
+        let a = r.d.magnitude2();
+        let b = 2.0 * r.d.dot(p_c);
+        let c = p_c.magnitude2() - self.radius * self.radius;
+        match solve_quadratic(a, b, c) {
+            Some((t0, t1)) if t0 < t1 => vec![(t0, t1)],
+            _ => vec![],
+        }
+    }
+
     /// 将球面上的一点转为球面参数坐标
     pub fn inct_to_uv(&self, p: Vec3f) -> (Real, Real) {
         let nor = (p - self.centre).normalize();
@@ -95,6 +123,17 @@ impl Sphere {
         (u, v)
     }
 
+    /// Partial derivatives of the point with respect to `inct_to_uv`
+    pub fn inct_to_dpduv(&self, p: Vec3f) -> (Vec3f, Vec3f) {
+        let q = p - self.centre;
+        let phi = q.x.atan2(q.z);
+        let rho = (q.x * q.x + q.z * q.z).sqrt();
+        (
+            2.0 * REAL_PI * vec3(q.z, 0.0, -q.x),
+            REAL_PI * vec3(-q.y * phi.sin(), rho, -q.y * phi.cos()),
+        )
+    }
+
     pub fn inct_to_local_x(&self, p: Vec3f) -> Vec3f {
         self.inct_to_local_y(p).cross(self.inct_to_local_z(p))
     }
@@ -182,4 +221,18 @@ mod tests {
             Real::default_max_relative()
         ));
     }
+
+    #[test]
+    fn inct_to_dpduv() {
+        let sph = Sphere::new(vec3(1.0, 0.0, 0.0), 2.0);
+        let p = vec3(1.0, 0.0, 0.0) + 2.0 * vec3(1.0, 2.0, -3.0).normalize();
+        let (dpdu, dpdv) = sph.inct_to_dpduv(p);
+        let (u, v) = sph.inct_to_uv(p);
+        let h = 1e-6;
+        let (u1, v1) = sph.inct_to_uv(p + h * dpdu);
+        assert!(vec2((u1 - u) / h, (v1 - v) / h).relative_eq(&vec2(1.0, 0.0), 1e-4, 1e-4));
+        let (u1, v1) = sph.inct_to_uv(p + h * dpdv);
+        assert!(vec2((u1 - u) / h, (v1 - v) / h).relative_eq(&vec2(0.0, 1.0), 1e-4, 1e-4));
+        assert!(dpdu.cross(dpdv).normalize().relative_eq(&sph.inct_to_local_y(p), 1e-9, 1e-9));
+    }
 }
@@ -0,0 +1,201 @@
+//! Torus around y-axis
+
+use super::{Ray, AABB};
+use math::*;
+
+/// Torus centred at `centre` around y-axis.
+/// `major_radius` is the distance from `centre` to the tube centre,
+/// `minor_radius` the radius of the tube.
+#[derive(Clone)]
+pub struct Torus {
+    centre: Vec3f,
+    major_radius: Real,
+    minor_radius: Real,
+}
+
+impl Torus {
+    pub fn new(centre: Vec3f, major_radius: Real, minor_radius: Real) -> Torus {
+        assert!(major_radius > 0.0 && minor_radius > 0.0);
+        Torus {
+            centre,
+            major_radius,
+            minor_radius,
+        }
+    }
+
+    pub fn get_centre(&self) -> &Vec3f {
+        &self.centre
+    }
+
+    pub fn get_major_radius(&self) -> Real {
+        self.major_radius
+    }
+
+    pub fn get_minor_radius(&self) -> Real {
+        self.minor_radius
+    }
+
+    pub fn to_aabb_bounding(&self) -> AABB {
+        let rr = self.major_radius + self.minor_radius;
+        let ext = vec3(rr, self.minor_radius, rr);
+        AABB::new(self.centre - ext, self.centre + ext)
+    }
+
+    /// All `t` where the line `r.p + t * r.d` crosses the surface, in ascending order
+    pub fn all_incts(&self, r: &Ray) -> Vec<Real> {
+        // Start from the bounding sphere to keep the quartic well conditioned
+        let bound = self.major_radius + self.minor_radius;
+        let oc = r.p - self.centre;
+        let t_start =
+            match solve_quadratic(1.0, 2.0 * dot(oc, r.d), oc.magnitude2() - bound * bound) {
+                Some((t0, _)) => t0,
+                None => return vec![],
+            };
+        let o = oc + t_start * r.d;
+        let d = r.d;
+
+        // (|p|^2 + R^2 - r^2)^2 = 4R^2(x^2 + z^2), see Suffern, K. (2007).
+        // Ray tracing from the ground up, 19.
+        let rr2 = self.major_radius * self.major_radius;
+        let four_rr2 = 4.0 * rr2;
+        let e = o.magnitude2() - rr2 - self.minor_radius * self.minor_radius;
+        let f = dot(o, d);
+        let c4 = 1.0;
+        let c3 = 4.0 * f;
+        let c2 = 2.0 * e + 4.0 * f * f + four_rr2 * d.y * d.y;
+        let c1 = 4.0 * f * e + 2.0 * four_rr2 * o.y * d.y;
+        let c0 = e * e - four_rr2 * (self.minor_radius * self.minor_radius - o.y * o.y);
+
+        solve_quartic(c4, c3, c2, c1, c0)
+            .into_iter()
+            .map(|t| t + t_start)
+            .collect()
+    }
+
+    /// Spans `(t_in, t_out)` of the ray inside the torus
+    pub fn inct_intervals(&self, r: &Ray) -> Vec<(Real, Real)> {
+        // The ray enters where it goes against the outward normal. Grazing roots,
+        // which may come single or doubled, neither enter nor leave.
+        let d = r.d.normalize();
+        let mut ret = Vec::new();
+        let mut t_in = None;
+        for t in self.all_incts(r) {
+            let cos = dot(d, self.inct_to_local_y(r.t_to_point(t)));
+            if cos < -1e-6 {
+                t_in = t_in.or(Some(t));
+            } else if cos > 1e-6 {
+                match t_in.take() {
+                    Some(t_in) if t_in < t => ret.push((t_in, t)),
+                    _ => (),
+                }
+            }
+        }
+        ret
+    }
+
+    /// Compute the nearest intersection with given ray
+    pub fn nearest_inct(&self, r: Ray) -> Option<(Real, Vec3f)> {
+        let t = self.all_incts(&r).into_iter().find(|&t| r.is_in_range(t))?;
+        Some((t, r.t_to_point(t)))
+    }
+
+    /// Intersection point at `t` along `r` and a bound of its absolute error.
+    /// The quartic roots are less accurate than those of quadrics, so the bound is looser.
+    pub fn inct_point(&self, r: &Ray, t: Real) -> (Vec3f, Vec3f) {
+        let p = r.t_to_point(t);
+        (p, gamma(32) * abs_vec3(p - self.centre) + gamma(1) * abs_vec3(p))
+    }
+
+    /// u: angle around y-axis / 2pi, v: angle around the tube / 2pi
+    pub fn inct_to_uv(&self, p: Vec3f) -> (Real, Real) {
+        let q = p - self.centre;
+        let rho = (q.x * q.x + q.z * q.z).sqrt();
+        let u = q.x.atan2(q.z) / (2.0 * REAL_PI) + 0.5;
+        let v = q.y.atan2(rho - self.major_radius) / (2.0 * REAL_PI) + 0.5;
+        (u, v)
+    }
+
+    /// Partial derivatives of the point with respect to `inct_to_uv`
+    pub fn inct_to_dpduv(&self, p: Vec3f) -> (Vec3f, Vec3f) {
+        let q = p - self.centre;
+        let phi = q.x.atan2(q.z);
+        let rho = (q.x * q.x + q.z * q.z).sqrt();
+        (
+            2.0 * REAL_PI * vec3(q.z, 0.0, -q.x),
+            2.0 * REAL_PI * vec3(-q.y * phi.sin(), rho - self.major_radius, -q.y * phi.cos()),
+        )
+    }
+
+    /// Tangent around y-axis
+    pub fn inct_to_local_x(&self, p: Vec3f) -> Vec3f {
+        let q = p - self.centre;
+        vec3(-q.z, 0.0, q.x).normalize()
+    }
+
+    /// Outward normal
+    pub fn inct_to_local_y(&self, p: Vec3f) -> Vec3f {
+        let q = p - self.centre;
+        let core = vec3(q.x, 0.0, q.z).normalize() * self.major_radius;
+        (q - core).normalize()
+    }
+
+    pub fn inct_to_local_z(&self, p: Vec3f) -> Vec3f {
+        self.inct_to_local_x(p).cross(self.inct_to_local_y(p))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inct() {
+        let torus = Torus::new(vec3(0.0, 1.0, 0.0), 2.0, 0.5);
+
+        // Through the tube on both sides of the hole
+        let r = Ray::new(vec3(-10.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0));
+        let ts = torus.all_incts(&r);
+        assert_eq!(ts.len(), 4);
+        for (t, e) in ts.iter().zip([7.5, 8.5, 11.5, 12.5].iter()) {
+            assert!(t.relative_eq(e, 1e-7, 1e-7));
+        }
+        let (_, p) = torus.nearest_inct(r).unwrap();
+        assert!(torus.inct_to_local_y(p).relative_eq(&-X_VEC3, 1e-7, 1e-7));
+
+        // From inside the hole
+        let (t, _) = torus
+            .nearest_inct(Ray::new(vec3(0.0, 1.0, 0.0), vec3(0.0, 0.0, 1.0)))
+            .unwrap();
+        assert!(t.relative_eq(&1.5, 1e-7, 1e-7));
+
+        // Top of the tube
+        let (t, p) = torus
+            .nearest_inct(Ray::new(vec3(2.0, 5.0, 0.0), vec3(0.0, -1.0, 0.0)))
+            .unwrap();
+        assert!(t.relative_eq(&3.5, 1e-7, 1e-7));
+        assert!(torus.inct_to_local_y(p).relative_eq(&Y_VEC3, 1e-7, 1e-7));
+
+        assert!(torus
+            .nearest_inct(Ray::new(vec3(0.0, 5.0, 0.0), vec3(0.0, -1.0, 0.0)))
+            .is_none());
+    }
+
+    #[test]
+    fn intervals() {
+        let torus = Torus::new(vec3(0.0, 1.0, 0.0), 2.0, 0.5);
+        let r = Ray::new(vec3(-10.0, 1.0, 0.0), vec3(1.0, 0.0, 0.0));
+        let spans = torus.inct_intervals(&r);
+        assert_eq!(spans.len(), 2);
+        assert!(spans[0].0.relative_eq(&7.5, 1e-7, 1e-7));
+        assert!(spans[0].1.relative_eq(&8.5, 1e-7, 1e-7));
+        assert!(spans[1].0.relative_eq(&11.5, 1e-7, 1e-7));
+        assert!(spans[1].1.relative_eq(&12.5, 1e-7, 1e-7));
+
+        // Grazing the inner equator in the middle of a single span
+        let r = Ray::new(vec3(1.5, 1.0, -10.0), vec3(0.0, 0.0, 1.0));
+        let spans = torus.inct_intervals(&r);
+        assert_eq!(spans.len(), 1);
+        assert!(spans[0].0.relative_eq(&8.0, 1e-7, 1e-7));
+        assert!(spans[0].1.relative_eq(&12.0, 1e-7, 1e-7));
+    }
+}
@@ -1,6 +1,6 @@
 //! Triangle
 
-use super::Ray;
+use super::{Ray, AABB};
 use math::*;
 use std::ops::Index;
 use std::ops::IndexMut;
@@ -32,63 +32,60 @@ impl IndexMut<usize> for Triangle {
     }
 }
 
-impl Triangle {
-    fn deter_a(&self, r: &Ray) -> Real {
-        Mat3f::new(
-            self.vtx[0].x - self.vtx[1].x,
-            self.vtx[0].y - self.vtx[1].y,
-            self.vtx[0].z - self.vtx[1].z,
-            self.vtx[0].x - self.vtx[2].x,
-            self.vtx[0].y - self.vtx[2].y,
-            self.vtx[0].z - self.vtx[2].z,
-            r.d.x,
-            r.d.y,
-            r.d.z,
-        ).determinant()
-    }
-
-    fn deter_t(&self, r: &Ray) -> Real {
-        Mat3f::new(
-            self.vtx[0].x - self.vtx[1].x,
-            self.vtx[0].y - self.vtx[1].y,
-            self.vtx[0].z - self.vtx[1].z,
-            self.vtx[0].x - self.vtx[2].x,
-            self.vtx[0].y - self.vtx[2].y,
-            self.vtx[0].z - self.vtx[2].z,
-            self.vtx[0].x - r.p.x,
-            self.vtx[0].y - r.p.y,
-            self.vtx[0].z - r.p.z,
-        ).determinant()
-    }
-
-    fn deter_beta(&self, r: &Ray) -> Real {
-        Mat3f::new(
-            self.vtx[0].x - r.p.x,
-            self.vtx[0].y - r.p.y,
-            self.vtx[0].z - r.p.z,
-            self.vtx[0].x - self.vtx[2].x,
-            self.vtx[0].y - self.vtx[2].y,
-            self.vtx[0].z - self.vtx[2].z,
-            r.d.x,
-            r.d.y,
-            r.d.z,
-        ).determinant()
-    }
-
-    fn deter_gamma(&self, r: &Ray) -> Real {
-        Mat3f::new(
-            self.vtx[0].x - self.vtx[1].x,
-            self.vtx[0].y - self.vtx[1].y,
-            self.vtx[0].z - self.vtx[1].z,
-            self.vtx[0].x - r.p.x,
-            self.vtx[0].y - r.p.y,
-            self.vtx[0].z - r.p.z,
-            r.d.x,
-            r.d.y,
-            r.d.z,
-        ).determinant()
+/// Per-ray data of the watertight ray-triangle test.
+///
+/// See Woop, S., Benthin, C., & Wald, I. (2013).
+/// Watertight ray/triangle intersection. Journal of Computer Graphics Techniques, 2(1), 65-82.
+///
+/// The ray is translated to the origin and sheared so that it goes along +z.
+/// Build it once and reuse it for all triangles tested against the same ray.
+pub struct WatertightRay {
+    /// Dominant axis of the direction, which becomes z
+    kz: usize,
+    /// Permuted origin
+    o: Vec3f,
+    shear: Vec3f,
+    t_min: Real,
+    t_max: Real,
+}
+
+impl WatertightRay {
+    pub fn new(r: &Ray) -> WatertightRay {
+        let ad = vec3(r.d.x.abs(), r.d.y.abs(), r.d.z.abs());
+        let kz = if ad.x > ad.y && ad.x > ad.z {
+            0
+        } else if ad.y > ad.z {
+            1
+        } else {
+            2
+        };
+        let d = Self::permute(kz, r.d);
+        WatertightRay {
+            kz,
+            o: Self::permute(kz, r.p),
+            shear: vec3(-d.x / d.z, -d.y / d.z, 1.0 / d.z),
+            t_min: r.t_min,
+            t_max: r.t_max,
+        }
+    }
+
+    /// Cyclic permutation of axes making `kz` the last one
+    fn permute(kz: usize, v: Vec3f) -> Vec3f {
+        match kz {
+            0 => vec3(v.y, v.z, v.x),
+            1 => vec3(v.z, v.x, v.y),
+            _ => v,
+        }
+    }
+
+    /// Vertex in the sheared ray space, z not scaled yet
+    fn transform(&self, v: Vec3f) -> Vec3f {
+        let v = Self::permute(self.kz, v) - self.o;
+        vec3(v.x + self.shear.x * v.z, v.y + self.shear.y * v.z, v.z)
     }
+}
 
+impl Triangle {
     pub fn new(vtx: [Vec3f; 3]) -> Triangle {
         Triangle { vtx }
     }
@@ -97,52 +94,66 @@ impl Triangle {
         Triangle { vtx: [a, b, c] }
     }
 
+    pub fn to_aabb_bounding(&self) -> AABB {
+        AABB::from_point(self.vtx[0])
+            .union_point(self.vtx[1])
+            .union_point(self.vtx[2])
+    }
+
     pub fn is_intersected(&self, r: Ray) -> Option<Real> {
-        let a = self.deter_a(&r);
-        if a.relative_eq(&0.0, 1e-6, 1e-6) {
-            return None;
-        }
+        self.nearest_inct(r).map(|inct| inct.t)
+    }
 
-        let t = self.deter_t(&r) / a;
-        if t <= 0.0 {
-            return None;
-        }
+    pub fn nearest_inct(&self, r: Ray) -> Option<TriangleIntersection> {
+        self.watertight_inct(&WatertightRay::new(&r))
+    }
 
-        let beta = self.deter_beta(&r) / a;
-        if beta < 0.0 || beta > 1.0 {
-            return None;
-        }
+    /// Watertight intersection test. Rays through a shared edge or vertex hit
+    /// at least one of the adjacent triangles.
+    pub fn watertight_inct(&self, wr: &WatertightRay) -> Option<TriangleIntersection> {
+        let p0 = wr.transform(self.vtx[0]);
+        let p1 = wr.transform(self.vtx[1]);
+        let p2 = wr.transform(self.vtx[2]);
 
-        let gamma = self.deter_gamma(&r) / a;
-        if gamma < 0.0 || gamma > 1.0 - beta {
+        // Edge functions, the ray misses when any two of them differ in sign
+        let e0 = p1.x * p2.y - p1.y * p2.x;
+        let e1 = p2.x * p0.y - p2.y * p0.x;
+        if (e0 < 0.0 && e1 > 0.0) || (e0 > 0.0 && e1 < 0.0) {
             return None;
         }
-
-        Some(t)
-    }
-
-    pub fn nearest_inct(&self, r: Ray) -> Option<TriangleIntersection> {
-        let a = self.deter_a(&r);
-        if a.relative_eq(&0.0, 1e-6, 1e-6) {
+        let e2 = p0.x * p1.y - p0.y * p1.x;
+        if (e0 < 0.0 || e1 < 0.0 || e2 < 0.0) && (e0 > 0.0 || e1 > 0.0 || e2 > 0.0) {
             return None;
         }
-
-        let t = self.deter_t(&r) / a;
-        if t <= 0.0 {
+        let det = e0 + e1 + e2;
+        if det == 0.0 {
             return None;
         }
 
-        let beta = self.deter_beta(&r) / a;
-        if beta < 0.0 || beta > 1.0 {
+        let t_scaled = wr.shear.z * (e0 * p0.z + e1 * p1.z + e2 * p2.z);
+        if (det < 0.0 && t_scaled >= 0.0) || (det > 0.0 && t_scaled <= 0.0) {
             return None;
         }
 
-        let gamma = self.deter_gamma(&r) / a;
-        if gamma < 0.0 || gamma > 1.0 - beta {
+        let inv_det = 1.0 / det;
+        let t = t_scaled * inv_det;
+        if t <= wr.t_min || t >= wr.t_max {
             return None;
         }
+        Some(TriangleIntersection {
+            t,
+            beta: e1 * inv_det,
+            gamma: e2 * inv_det,
+        })
+    }
 
-        Some(TriangleIntersection { t, beta, gamma })
+    /// Intersection point from barycentric coordinates, and a bound of its absolute error
+    pub fn inct_point(&self, inct: &TriangleIntersection) -> (Vec3f, Vec3f) {
+        let b0 = (1.0 - inct.beta - inct.gamma) * self.vtx[0];
+        let b1 = inct.beta * self.vtx[1];
+        let b2 = inct.gamma * self.vtx[2];
+        let err = abs_vec3(b0) + abs_vec3(b1) + abs_vec3(b2);
+        (b0 + b1 + b2, gamma(7) * err)
     }
 
     /// r: ray intersecting with self.
@@ -158,3 +169,202 @@ impl Triangle {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::hint::black_box;
+    use std::time::{Duration, Instant};
+
+    /// The former Cramer's rule solve, kept as a reference
+    fn cramer_inct(tri: &Triangle, r: &Ray) -> Option<TriangleIntersection> {
+        let (v0, v1, v2) = (tri[0], tri[1], tri[2]);
+        let (c0, c1, c2) = (v0 - v1, v0 - v2, v0 - r.p);
+        let a = Mat3f::from_cols(c0, c1, r.d).determinant();
+        if a.relative_eq(&0.0, 1e-6, 1e-6) {
+            return None;
+        }
+        let t = Mat3f::from_cols(c0, c1, c2).determinant() / a;
+        if t <= 0.0 {
+            return None;
+        }
+        let beta = Mat3f::from_cols(c2, c1, r.d).determinant() / a;
+        if beta < 0.0 || beta > 1.0 {
+            return None;
+        }
+        let gamma = Mat3f::from_cols(c0, c2, r.d).determinant() / a;
+        if gamma < 0.0 || gamma > 1.0 - beta {
+            return None;
+        }
+        Some(TriangleIntersection { t, beta, gamma })
+    }
+
+    /// Deterministic pseudo random numbers in [0, 1)
+    fn xorshift(state: &mut u64) -> Real {
+        *state ^= *state << 13;
+        *state ^= *state >> 7;
+        *state ^= *state << 17;
+        (*state >> 11) as Real / (1u64 << 53) as Real
+    }
+
+    /// Bumpy height field of `n * n` quads, each split into two triangles
+    fn height_field(n: usize, state: &mut u64) -> (Vec<Vec3f>, Vec<Triangle>) {
+        let mut vtx = Vec::new();
+        for i in 0..=n {
+            for j in 0..=n {
+                let h = 0.05 * xorshift(state);
+                vtx.push(vec3(i as Real / n as Real, h, j as Real / n as Real));
+            }
+        }
+        let idx = |i: usize, j: usize| i * (n + 1) + j;
+        let mut tris = Vec::new();
+        for i in 0..n {
+            for j in 0..n {
+                let (a, b) = (vtx[idx(i, j)], vtx[idx(i + 1, j)]);
+                let (c, d) = (vtx[idx(i + 1, j + 1)], vtx[idx(i, j + 1)]);
+                tris.push(Triangle::new_abc(a, b, c));
+                tris.push(Triangle::new_abc(a, c, d));
+            }
+        }
+        (vtx, tris)
+    }
+
+    #[test]
+    fn inct() {
+        let tri = Triangle::new_abc(ZERO_VEC3, X_VEC3, Z_VEC3);
+        let inct = tri
+            .nearest_inct(Ray::new(vec3(0.25, 1.0, 0.5), -Y_VEC3))
+            .unwrap();
+        assert!(inct.t.relative_eq(&1.0, 1e-12, 1e-12));
+        assert!(inct.beta.relative_eq(&0.25, 1e-12, 1e-12));
+        assert!(inct.gamma.relative_eq(&0.5, 1e-12, 1e-12));
+        assert!(tri
+            .is_intersected(Ray::new(vec3(0.25, 1.0, 0.5), Y_VEC3))
+            .is_none());
+        assert!(tri
+            .is_intersected(Ray::new(vec3(0.75, 1.0, 0.5), -Y_VEC3))
+            .is_none());
+    }
+
+    #[test]
+    fn watertight_shared_edges() {
+        let n = 8;
+        let mut state = 0x9E37_79B9_7F4A_7C15;
+        let (vtx, tris) = height_field(n, &mut state);
+        let idx = |i: usize, j: usize| i * (n + 1) + j;
+
+        // Aim at points on shared edges and at shared vertices of the inner grid
+        let mut targets = Vec::new();
+        for i in 1..n {
+            for j in 1..n {
+                let v = vtx[idx(i, j)];
+                targets.push(v);
+                for &(a, b) in &[
+                    (v, vtx[idx(i + 1, j)]),
+                    (v, vtx[idx(i, j + 1)]),
+                    (v, vtx[idx(i + 1, j + 1)]),
+                ] {
+                    for k in 1..8 {
+                        let s = k as Real / 8.0;
+                        targets.push(a + s * (b - a));
+                    }
+                }
+            }
+        }
+
+        for target in targets {
+            for _ in 0..8 {
+                // Steeper than the surface, so each ray crosses it at the target
+                let o = target
+                    + vec3(
+                        xorshift(&mut state) - 0.5,
+                        1.0 + 2.0 * xorshift(&mut state),
+                        xorshift(&mut state) - 0.5,
+                    );
+                let r = Ray::new(o, target - o);
+                let wr = WatertightRay::new(&r);
+                assert!(
+                    tris.iter().any(|tri| tri.watertight_inct(&wr).is_some()),
+                    "ray from {:?} to {:?} leaks through the mesh",
+                    o,
+                    target
+                );
+            }
+        }
+    }
+
+    /// Height field and rays cast down onto it
+    fn cramer_scene() -> (Vec<Triangle>, Vec<Ray>) {
+        let mut state = 0x2545_F491_4F6C_DD1D;
+        let (_, tris) = height_field(16, &mut state);
+        let rays: Vec<Ray> = (0..64)
+            .map(|_| {
+                let o = vec3(xorshift(&mut state), 2.0, xorshift(&mut state));
+                let t = vec3(xorshift(&mut state), 0.0, xorshift(&mut state));
+                Ray::new(o, t - o)
+            })
+            .collect();
+        (tris, rays)
+    }
+
+    #[test]
+    fn same_as_cramer() {
+        // Same answers away from the edges
+        let (tris, rays) = cramer_scene();
+        for r in &rays {
+            for tri in &tris {
+                if let (Some(a), Some(b)) = (tri.nearest_inct(r.clone()), cramer_inct(tri, r)) {
+                    assert!(a.t.relative_eq(&b.t, 1e-9, 1e-9));
+                    assert!(a.beta.relative_eq(&b.beta, 1e-9, 1e-9));
+                    assert!(a.gamma.relative_eq(&b.gamma, 1e-9, 1e-9));
+                }
+            }
+        }
+    }
+
+    /// Timing comparison, to be run in release builds on an idle machine:
+    /// `cargo test --release -- --ignored faster_than_cramer`
+    #[test]
+    #[ignore]
+    fn faster_than_cramer() {
+        let (tris, rays) = cramer_scene();
+
+        // Best of a few runs of testing all rays against all triangles
+        let bench = |run: &Fn() -> usize| {
+            (0..5)
+                .map(|_| {
+                    let start = Instant::now();
+                    black_box(run());
+                    start.elapsed()
+                })
+                .min()
+                .unwrap_or(Duration::from_secs(0))
+        };
+        // Per-ray data is shared by all triangles as in `TriangleMesh`
+        let watertight = bench(&|| {
+            rays.iter()
+                .map(|r| {
+                    let wr = WatertightRay::new(r);
+                    tris.iter()
+                        .filter(|tri| black_box(tri.watertight_inct(&wr)).is_some())
+                        .count()
+                })
+                .sum()
+        });
+        let cramer = bench(&|| {
+            rays.iter()
+                .map(|r| {
+                    tris.iter()
+                        .filter(|tri| black_box(cramer_inct(tri, r)).is_some())
+                        .count()
+                })
+                .sum()
+        });
+        assert!(
+            watertight < cramer,
+            "watertight: {:?}, cramer: {:?}",
+            watertight,
+            cramer
+        );
+    }
+}
@@ -0,0 +1,180 @@
+//! Real roots of low degree polynomials
+//!
+//! Cubic and quartic solvers follow Schwarze, J. (1990).
+//! Cubic and quartic roots. Graphics Gems, 404-407.
+
+use math::*;
+
+const EPS: Real = 1e-9;
+
+fn is_zero(x: Real) -> bool {
+    x.abs() < EPS
+}
+
+/// Real roots of `a * x^2 + b * x + c = 0` in ascending order
+pub fn solve_quadratic(a: Real, b: Real, c: Real) -> Option<(Real, Real)> {
+    if a == 0.0 {
+        if b == 0.0 {
+            return None;
+        }
+        let x = -c / b;
+        return Some((x, x));
+    }
+    let delta = b * b - 4.0 * a * c;
+    if delta < 0.0 {
+        return None;
+    }
+    // Avoid cancellation between -b and sqrt(delta)
+    let q = -0.5 * (b + b.signum() * delta.sqrt());
+    let (x0, x1) = if q == 0.0 { (0.0, 0.0) } else { (q / a, c / q) };
+    Some(if x0 < x1 { (x0, x1) } else { (x1, x0) })
+}
+
+/// Real roots of `c3 * x^3 + c2 * x^2 + c1 * x + c0 = 0`, `c3` shall be nonzero
+pub fn solve_cubic(c3: Real, c2: Real, c1: Real, c0: Real) -> Vec<Real> {
+    // Normal form x^3 + Ax^2 + Bx + C = 0
+    let a = c2 / c3;
+    let b = c1 / c3;
+    let c = c0 / c3;
+
+    // Substitute x = y - A/3 to eliminate the quadric term: y^3 + 3py + 2q = 0
+    let sq_a = a * a;
+    let p = (-sq_a / 3.0 + b) / 3.0;
+    let q = (2.0 / 27.0 * a * sq_a - a * b / 3.0 + c) / 2.0;
+
+    let cb_p = p * p * p;
+    let d = q * q + cb_p;
+
+    let mut ret = if is_zero(d) {
+        if is_zero(q) {
+            vec![0.0]
+        } else {
+            let u = (-q).cbrt();
+            vec![2.0 * u, -u]
+        }
+    } else if d < 0.0 {
+        // Three real solutions
+        let phi = (-q / (-cb_p).sqrt()).clamp(-1.0, 1.0).acos() / 3.0;
+        let t = 2.0 * (-p).sqrt();
+        vec![
+            t * phi.cos(),
+            -t * (phi + REAL_PI / 3.0).cos(),
+            -t * (phi - REAL_PI / 3.0).cos(),
+        ]
+    } else {
+        let sqrt_d = d.sqrt();
+        vec![(sqrt_d - q).cbrt() - (sqrt_d + q).cbrt()]
+    };
+
+    for x in &mut ret {
+        *x -= a / 3.0;
+    }
+    ret
+}
+
+/// Real roots of `c4 * x^4 + c3 * x^3 + c2 * x^2 + c1 * x + c0 = 0` in ascending order,
+/// `c4` shall be nonzero
+pub fn solve_quartic(c4: Real, c3: Real, c2: Real, c1: Real, c0: Real) -> Vec<Real> {
+    // Normal form x^4 + Ax^3 + Bx^2 + Cx + D = 0
+    let a = c3 / c4;
+    let b = c2 / c4;
+    let c = c1 / c4;
+    let d = c0 / c4;
+
+    // Substitute x = y - A/4 to eliminate the cubic term: y^4 + py^2 + qy + r = 0
+    let sq_a = a * a;
+    let p = -3.0 / 8.0 * sq_a + b;
+    let q = sq_a * a / 8.0 - a * b / 2.0 + c;
+    let r = -3.0 / 256.0 * sq_a * sq_a + sq_a * b / 16.0 - a * c / 4.0 + d;
+
+    let mut ret: Vec<Real> = Vec::with_capacity(4);
+    if is_zero(r) {
+        // No absolute term: y(y^3 + py + q) = 0
+        ret.push(0.0);
+        ret.extend(solve_cubic(1.0, 0.0, p, q));
+    } else {
+        // Solve the resolvent cubic and take one real root
+        let z = solve_cubic(1.0, -p / 2.0, -r, r * p / 2.0 - q * q / 8.0)[0];
+
+        // Build two quadric equations
+        let u = z * z - r;
+        let v = 2.0 * z - p;
+        let u = if is_zero(u) {
+            0.0
+        } else if u > 0.0 {
+            u.sqrt()
+        } else {
+            return ret;
+        };
+        let v = if is_zero(v) {
+            0.0
+        } else if v > 0.0 {
+            v.sqrt()
+        } else {
+            return ret;
+        };
+
+        let sv = if q < 0.0 { -v } else { v };
+        if let Some((x0, x1)) = solve_quadratic(1.0, sv, z - u) {
+            ret.push(x0);
+            ret.push(x1);
+        }
+        if let Some((x0, x1)) = solve_quadratic(1.0, -sv, z + u) {
+            ret.push(x0);
+            ret.push(x1);
+        }
+    }
+
+    // Resubstitute and polish with Newton's method on the original polynomial
+    for x in &mut ret {
+        *x -= a / 4.0;
+        for _ in 0..2 {
+            let f = (((c4 * *x + c3) * *x + c2) * *x + c1) * *x + c0;
+            let df = ((4.0 * c4 * *x + 3.0 * c3) * *x + 2.0 * c2) * *x + c1;
+            if df != 0.0 {
+                *x -= f / df;
+            }
+        }
+    }
+    ret.sort_by(|x, y| x.partial_cmp(y).unwrap());
+    ret
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_roots(got: &[Real], expected: &[Real]) {
+        assert_eq!(got.len(), expected.len());
+        for (g, e) in got.iter().zip(expected.iter()) {
+            assert!(g.relative_eq(e, 1e-7, 1e-7), "{} != {}", g, e);
+        }
+    }
+
+    #[test]
+    fn quadratic() {
+        let (x0, x1) = solve_quadratic(2.0, -6.0, 4.0).unwrap();
+        assert_roots(&[x0, x1], &[1.0, 2.0]);
+        assert!(solve_quadratic(1.0, 0.0, 1.0).is_none());
+    }
+
+    #[test]
+    fn cubic() {
+        // (x - 1)(x + 2)(x - 3) = x^3 - 2x^2 - 5x + 6
+        let mut roots = solve_cubic(1.0, -2.0, -5.0, 6.0);
+        roots.sort_by(|x, y| x.partial_cmp(y).unwrap());
+        assert_roots(&roots, &[-2.0, 1.0, 3.0]);
+    }
+
+    #[test]
+    fn quartic() {
+        // (x - 1)(x - 2)(x - 3)(x - 4) = x^4 - 10x^3 + 35x^2 - 50x + 24
+        assert_roots(
+            &solve_quartic(1.0, -10.0, 35.0, -50.0, 24.0),
+            &[1.0, 2.0, 3.0, 4.0],
+        );
+        // (x^2 + 1)(x - 1)(x + 0.5)
+        assert_roots(&solve_quartic(2.0, -1.0, 1.0, -1.0, -1.0), &[-0.5, 1.0]);
+        assert!(solve_quartic(1.0, 0.0, 0.0, 0.0, 1.0).is_empty());
+    }
+}
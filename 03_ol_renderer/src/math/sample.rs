@@ -20,3 +20,56 @@ pub fn sphere_uniform() -> Vec3f {
         vec3(ret.x, -ret.y, ret.z)
     }
 }
+
+/// Map `u` in `[0, 1)^2` to the unit disk, preserving uniformity and adjacency.
+///
+/// See Shirley, P., & Chiu, K. (1997). A low distortion map between disk and square.
+/// Journal of Graphics Tools, 2(3), 45-52.
+pub fn disk_concentric(u: Vec2f) -> Vec2f {
+    let (a, b) = (2.0 * u.x - 1.0, 2.0 * u.y - 1.0);
+    if a == 0.0 && b == 0.0 {
+        return vec2(0.0, 0.0);
+    }
+    let (r, theta) = if a.abs() > b.abs() {
+        (a, REAL_PI / 4.0 * (b / a))
+    } else {
+        (b, REAL_PI / 2.0 - REAL_PI / 4.0 * (a / b))
+    };
+    r * vec2(theta.cos(), theta.sin())
+}
+
+/// Map `u` in `[0, 1)^2` uniformly onto the regular polygon with `n` vertices on the
+/// unit circle, the first one at angle `rotation`
+pub fn polygon_uniform(n: u32, rotation: Real, u: Vec2f) -> Vec2f {
+    assert!(n >= 3);
+    // Pick a triangle fanning from the centre, then a point in it
+    let x = u.x * n as Real;
+    let i = (x as u32).min(n - 1);
+    let s = x - i as Real;
+    let angle = |k: u32| rotation + 2.0 * REAL_PI * k as Real / n as Real;
+    let v0 = vec2(angle(i).cos(), angle(i).sin());
+    let v1 = vec2(angle(i + 1).cos(), angle(i + 1).sin());
+    let r = u.y.sqrt();
+    r * ((1.0 - s) * v0 + s * v1)
+}
+
+/// Map `u` in `[0, 1)^2` to barycentric coordinates `(beta, gamma)` uniformly
+/// distributed over a triangle
+pub fn triangle_uniform(u: Vec2f) -> (Real, Real) {
+    let s = u.x.sqrt();
+    (s * (1.0 - u.y), s * u.y)
+}
+
+/// Map `u` in `[0, 1)^2` to directions uniformly distributed in the cone around
+/// positive y whose half angle has cosine `cos_max`
+pub fn cone_uniform(cos_max: Real, u: Vec2f) -> Vec3f {
+    let y = 1.0 - u.x * (1.0 - cos_max);
+    let r = (1.0 - y * y).max(0.0).sqrt();
+    let phi = 2.0 * REAL_PI * u.y;
+    vec3(r * phi.cos(), y, r * phi.sin())
+}
+
+/// Density of `cone_uniform` with respect to solid angle
+pub fn cone_uniform_pdf(cos_max: Real) -> Real {
+    1.0 / (2.0 * REAL_PI * (1.0 - cos_max))
+}
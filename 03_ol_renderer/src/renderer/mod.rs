@@ -1,17 +1,28 @@
 //! Renderer interface
 
 pub mod path_tracing;
+pub mod sequence;
 pub mod whitted;
 
 pub mod prelude {
     pub use super::path_tracing::*;
+    pub use super::sequence::*;
     pub use super::whitted::*;
+    use arena::*;
     use math::{model::*, *};
 
     pub trait Renderer {
-        fn render(&self, r: Ray) -> Color3f;
+        /// Radiance along `r`. Per-sample objects (BxDFs, sample lists, ...) are
+        /// allocated in `arena`, which the caller may reset afterwards.
+        fn render_in(&self, r: Ray, arena: &Arena) -> Color3f;
 
-        fn is_visible(&self, p1: Vec3f, p2: Vec3f) -> bool;
+        /// `render_in` with the arena of the current thread, reset per call
+        fn render(&self, r: Ray) -> Color3f {
+            with_thread_arena(|arena| self.render_in(r, arena))
+        }
+
+        /// Is nothing hit by `r` within its `(t_min, t_max)`
+        fn is_visible(&self, r: Ray) -> bool;
     }
 }
 
@@ -2,6 +2,7 @@
 
 extern crate rand;
 
+use arena::Arena;
 use entity::*;
 use light::*;
 use material::*;
@@ -17,108 +18,158 @@ pub struct PathTracer {
 }
 
 impl Renderer for PathTracer {
-    fn is_visible(&self, p1: Vec3f, p2: Vec3f) -> bool {
-        let d = p2 - p1;
-        let dis = d.magnitude() - 1e-6;
-        let r = Ray::new(p1, d);
-        !self.entities.iter().any(|ent| {
-            if let Some(i) = ent.has_inct(r.clone()) {
-                i.0 < dis
-            } else {
-                false
-            }
-        })
+    fn is_visible(&self, r: Ray) -> bool {
+        !self.entities.iter().any(|ent| ent.has_inct(r.clone()).is_some())
+    }
+
+    fn render_in(&self, r: Ray, arena: &Arena) -> Color3f {
+        self.render_d(r, 0, None, arena)
     }
+}
 
-    fn render(&self, r: Ray) -> Color3f {
-        self.render_d(r, 0)
+/// Weight of a sample with density `pdf` combined with another strategy of
+/// density `other_pdf`, by the power heuristic
+///
+/// See Veach, E., & Guibas, L. J. (1995).
+/// Optimally combining sampling techniques for Monte Carlo rendering.
+fn mis_weight(pdf: Real, other_pdf: Real) -> Real {
+    let (a, b) = (pdf * pdf, other_pdf * other_pdf);
+    if a + b == 0.0 {
+        return 0.0;
     }
+    a / (a + b)
 }
 
 impl PathTracer {
-    fn render_d(&self, r: Ray, depth: u32) -> Color3f {
+    /// `bxdf_pdf` is the density of sampling `r` by the BxDF of the surface it
+    /// leaves, when it does. Emission found by such rays is weighted against
+    /// sampling the lights directly.
+    fn render_d(&self, r: Ray, depth: u32, bxdf_pdf: Option<Real>, arena: &Arena) -> Color3f {
         if depth > self.max_depth {
             return BLACK;
         }
 
-        let inct = self.entities.iter().fold(None, |acc, ent| {
-            if let Some(i) = ent.inct(r.clone()) {
-                match acc {
-                    None => Some(i),
-                    Some(v) => Some(v.nearer(i)),
-                }
-            } else {
-                acc
+        match nearest_inct(&self.entities, r.clone()) {
+            None => {
+                let emit = self.lights.iter().fold(BLACK, |acc, l| acc + l.emit(&r));
+                let emit = match bxdf_pdf {
+                    Some(pdf) if emit != BLACK => {
+                        let from_light = Ray::new(r.t_to_point(INFINITE_DISTANCE), -r.d);
+                        emit * mis_weight(pdf, self.light_pdf(from_light, r.p))
+                    }
+                    _ => emit,
+                };
+                self.background + emit
             }
-        });
-
-        match inct {
-            None => self.background,
             Some(i) => {
-                let pos = i.position + i.normal * 1e-4;
-                self.direct_illu(pos, -r.d, i.normal, &i.material)
-                    + self.indirect_illu(pos, -r.d, i.normal, &i.material, depth)
-                    + i.material.emit(-r.d) + i.material.ambient()
+                let material = i.material.build(arena);
+                let emit = material.emit(-r.d);
+                let emit = match bxdf_pdf {
+                    Some(pdf) if emit != BLACK => {
+                        let from_light = Ray::new(i.position, -r.d);
+                        emit * mis_weight(pdf, self.light_pdf(from_light, r.p))
+                    }
+                    _ => emit,
+                };
+                // Rays leaving the last vertex find no emission, so its light
+                // samples are not weighted against them
+                let last = depth == self.max_depth;
+                self.direct_illu(&i, material, -r.d, r.time, last, arena)
+                    + self.indirect_illu(&i, material, -r.d, r.time, depth, arena)
+                    + emit + material.ambient()
             }
         }
     }
 
+    /// Density of `light_sample_once` sampling `from_light` toward `dst_pnt`
+    fn light_pdf(&self, from_light: Ray, dst_pnt: Vec3f) -> Real {
+        if self.lights.is_empty() {
+            return 0.0;
+        }
+        let pdf: Real = self
+            .lights
+            .iter()
+            .filter(|l| !l.is_delta())
+            .map(|l| l.pdf_to(from_light.clone(), dst_pnt))
+            .sum();
+        pdf / self.lights.len() as Real
+    }
+
     fn light_sample_once(
         &self,
-        pnt: Vec3f,
+        inct: &Intersection,
+        material: &BxDF,
         dir_in: Vec3f,
-        normal: Vec3f,
-        material: &Box<BxDF>,
-        rng: &mut self::rand::ThreadRng,
+        time: Real,
+        last: bool,
+        arena: &Arena,
     ) -> Color3f {
         use self::rand::distributions::*;
-        let light = &self.lights[Uniform::from(0..self.lights.len()).sample(rng)];
+        let idx = Uniform::from(0..self.lights.len()).sample(&mut rand::thread_rng());
+        let light = &self.lights[idx];
 
-        let sam = light.sample_to(1, pnt);
-        if sam.is_empty() || !self.is_visible(sam[0].ray.p, pnt) {
+        let sam = light.sample_to(1, inct.position, arena);
+        if sam.is_empty() {
             return BLACK;
         }
         let sam = &sam[0];
+        let shadow_ray =
+            Ray::spawn_to(inct.position, inct.p_error, inct.geo_normal, sam.ray.p, time);
+        if !self.is_visible(shadow_ray) {
+            return BLACK;
+        }
 
         let color = material.f(dir_in, -sam.ray.d).mul_element_wise(sam.color)
-            * dot(-sam.ray.d, normal).max(0.0)
-            * dot(sam.ray.d, sam.light_normal).max(0.0);
-        color * self.lights.len() as Real / light.pdf_to(sam.ray.clone(), pnt)
+            * dot(-sam.ray.d, inct.normal).max(0.0);
+        let pdf = light.pdf_to(sam.ray.clone(), inct.position) / self.lights.len() as Real;
+        if pdf <= 0.0 {
+            return BLACK;
+        }
+        if light.is_delta() || last {
+            return color / pdf;
+        }
+        color * mis_weight(pdf, material.pdf(&dir_in, &-sam.ray.d)) / pdf
     }
 
     fn direct_illu(
         &self,
-        pnt: Vec3f,
+        inct: &Intersection,
+        material: &BxDF,
         dir_in: Vec3f,
-        normal: Vec3f,
-        material: &Box<BxDF>,
+        time: Real,
+        last: bool,
+        arena: &Arena,
     ) -> Color3f {
         if self.lights.is_empty() {
             return BLACK;
         }
-        let mut rng = rand::thread_rng();
         (0..self.spp).fold(BLACK, |acc, _| {
-            acc + self.light_sample_once(pnt, dir_in, normal, material, &mut rng)
+            acc + self.light_sample_once(inct, material, dir_in, time, last, arena)
         }) / self.spp as Real
     }
 
     fn indirect_illu(
         &self,
-        pnt: Vec3f,
+        inct: &Intersection,
+        material: &BxDF,
         dir_in: Vec3f,
-        normal: Vec3f,
-        material: &Box<BxDF>,
+        time: Real,
         depth: u32,
+        arena: &Arena,
     ) -> Color3f {
+        if depth >= self.max_depth {
+            return BLACK;
+        }
         material
-            .sample(&dir_in, self.spp)
+            .sample(&dir_in, self.spp, arena)
             .iter()
             .fold(BLACK, |acc, sam_dir| {
-                let ref_ray = Ray::new(pnt, sam_dir.clone());
-                let rendered = self.render_d(ref_ray, depth + 1);
-                let bxdf = material.f(dir_in, sam_dir.clone());
-                let nacc = rendered.mul_element_wise(bxdf) * dot(*sam_dir, normal)
-                    / material.pdf(&dir_in, sam_dir);
+                let ref_ray =
+                    Ray::spawn(inct.position, inct.p_error, inct.geo_normal, *sam_dir, time);
+                let pdf = material.pdf(&dir_in, sam_dir);
+                let rendered = self.render_d(ref_ray, depth + 1, Some(pdf), arena);
+                let bxdf = material.f(dir_in, *sam_dir);
+                let nacc = rendered.mul_element_wise(bxdf) * dot(*sam_dir, inct.normal) / pdf;
                 acc + nacc
             }) / self.spp as Real
     }
@@ -139,3 +190,86 @@ impl PathTracer {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use buf::Buf2D;
+    use std::sync::Arc;
+
+    #[test]
+    fn area_light_sampling() {
+        let tracer = |sample_bulb: bool| {
+            let bulb: Arc<Surface + Send> = Arc::new(sphere::Sphere::new(
+                vec3(0.0, 0.5, 0.0),
+                0.2,
+                Box::new(|_, _, ly, _, _| DiffuseLight::new(ly, WHITE)),
+            ));
+            let entities: Vec<Box<Entity>> = vec![
+                Box::new(plane::Plane::new(
+                    ZERO_VEC3,
+                    Y_VEC3,
+                    Box::new(|_, lx, ly, _, _| Phong::new(BLACK, WHITE, lx, ly, 1.0)),
+                )),
+                Box::new(bulb.clone()),
+            ];
+            let mut lights: Vec<Box<Light>> = Vec::new();
+            if sample_bulb {
+                lights.push(Box::new(AreaLight::new(bulb)));
+            }
+            PathTracer::new(entities, lights, BLACK, 1, 1)
+        };
+
+        // The same radiance, whether the bulb is only hit by chance or also sampled
+        let r = Ray::new(vec3(0.5, 1.0, 0.0), vec3(-0.2, -1.0, 0.0));
+        let mean = |tracer: PathTracer| {
+            let cnt = 20000;
+            (0..cnt).map(|_| tracer.render(r.clone()).x).sum::<Real>() / cnt as Real
+        };
+        let (by_chance, sampled) = (mean(tracer(false)), mean(tracer(true)));
+        assert!(by_chance.is_finite() && sampled > 0.0);
+        assert!((by_chance - sampled).abs() < 0.2 * sampled);
+    }
+
+    #[test]
+    fn white_furnace() {
+        // A convex diffuse sphere reflecting half of a uniform environment
+        let albedo = 0.5;
+        let entities: Vec<Box<Entity>> = vec![Box::new(sphere::Sphere::new(
+            ZERO_VEC3,
+            1.0,
+            Box::new(move |_, lx, ly, _, _| {
+                Phong::new(BLACK, albedo / REAL_PI * WHITE, lx, ly, 0.0)
+            }),
+        ))];
+        let env = EnvironmentLight::new(Buf2D::new(8, 4, &WHITE));
+        let tracer = PathTracer::new(entities, vec![Box::new(env)], BLACK, 1, 1);
+
+        let miss = Ray::new(vec3(0.0, 2.0, 5.0), -Z_VEC3);
+        assert!(tracer.render(miss).relative_eq(&WHITE, 1e-9, 1e-9));
+        let hit = Ray::new(vec3(0.3, 0.2, 5.0), -Z_VEC3);
+        let cnt = 20000;
+        let mean = (0..cnt).map(|_| tracer.render(hit.clone()).x).sum::<Real>() / cnt as Real;
+        assert!((mean - albedo).abs() < 0.02 * albedo);
+    }
+
+    #[test]
+    fn last_bounce() {
+        // Only the lights are sampled at the camera vertex, which still sees all of them
+        let albedo = 0.5;
+        let entities: Vec<Box<Entity>> = vec![Box::new(plane::Plane::new(
+            ZERO_VEC3,
+            Y_VEC3,
+            Box::new(move |_, lx, ly, _, _| {
+                Phong::new(BLACK, albedo / REAL_PI * WHITE, lx, ly, 0.0)
+            }),
+        ))];
+        let env = EnvironmentLight::new(Buf2D::new(8, 4, &WHITE));
+        let tracer = PathTracer::new(entities, vec![Box::new(env)], BLACK, 0, 1);
+
+        let r = Ray::new(vec3(0.0, 1.0, 0.0), vec3(0.3, -1.0, 0.2));
+        let cnt = 100000;
+        let mean = (0..cnt).map(|_| tracer.render(r.clone()).x).sum::<Real>() / cnt as Real;
+        assert!((mean - albedo).abs() < 0.02 * albedo);
+    }
+}
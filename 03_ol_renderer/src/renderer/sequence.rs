@@ -0,0 +1,123 @@
+//! Rendering of images and image sequences
+
+extern crate image;
+extern crate rand;
+extern crate rayon;
+
+use self::rayon::prelude::*;
+use buf::*;
+use camera::*;
+use math::*;
+use renderer::*;
+use std::io;
+use std::ops::Range;
+
+/// Image seen by `camera`, with the average of `spp` samples jittered over each pixel.
+/// Pixels are rendered in parallel.
+pub fn render_image<R, C>(
+    renderer: &R,
+    camera: &C,
+    img_w: u32,
+    img_h: u32,
+    spp: u32,
+) -> Buf2D<Color3f>
+where
+    R: Renderer + Sync,
+    C: Camera + Sync,
+{
+    assert!(spp > 0);
+    let pixels: Vec<Color3f> = (0..img_w * img_h)
+        .into_par_iter()
+        .map(|i| {
+            let (x, y) = (i % img_w, i / img_w);
+            let sum: Color3f = (0..spp)
+                .map(|_| {
+                    let offset = vec2(rand::random::<Real>(), rand::random::<Real>());
+                    renderer.render(camera.raster_to_ray(x, y, offset, img_w, img_h))
+                })
+                .sum();
+            sum / spp as Real
+        })
+        .collect();
+    Buf2D::from_fn(img_w, img_h, |x, y| pixels[(y * img_w + x) as usize])
+}
+
+/// Save `img` with each color channel clamped to `[0, 1]`.
+/// The format is deduced from the extension of `path`.
+pub fn save_image(img: &Buf2D<Color3f>, path: &str) -> io::Result<()> {
+    let to_u8 = |v: Real| (v.clamp(0.0, 1.0) * 255.0) as u8;
+    image::ImageBuffer::from_fn(img.get_width(), img.get_height(), |x, y| {
+        let c = img.at(x, y);
+        image::Rgb {
+            data: [to_u8(c.r()), to_u8(c.g()), to_u8(c.b())],
+        }
+    })
+    .save(path)
+}
+
+/// Frames of an animation rendered along a `CameraPath`
+pub struct FrameSequence {
+    path_pattern: String,
+    img_w: u32,
+    img_h: u32,
+    fps: Real,
+    spp: u32,
+}
+
+impl FrameSequence {
+    /// Frames are saved to `path_pattern` with `{}` replaced by the frame number
+    /// padded to 4 digits, eg. `"./target/turntable_{}.png"`
+    pub fn new(path_pattern: &str, img_w: u32, img_h: u32, fps: Real) -> FrameSequence {
+        assert!(path_pattern.contains("{}") && fps > 0.0);
+        FrameSequence {
+            path_pattern: path_pattern.to_string(),
+            img_w,
+            img_h,
+            fps,
+            spp: 1,
+        }
+    }
+
+    /// Samples per pixel
+    pub fn set_spp(&mut self, spp: u32) -> &mut Self {
+        assert!(spp > 0);
+        self.spp = spp;
+        self
+    }
+
+    pub fn frame_path(&self, frame: u32) -> String {
+        self.path_pattern.replace("{}", &format!("{:04}", frame))
+    }
+
+    /// Frame `n` is shown at time `n / fps`
+    pub fn frame_time(&self, frame: u32) -> Real {
+        frame as Real / self.fps
+    }
+
+    /// Render and save the frames in `frames`, each with the camera of `path` at its time
+    pub fn render<R>(&self, renderer: &R, path: &CameraPath, frames: Range<u32>) -> io::Result<()>
+    where
+        R: Renderer + Sync,
+    {
+        for frame in frames {
+            let camera = path.camera_at(self.frame_time(frame));
+            let img = render_image(renderer, &camera, self.img_w, self.img_h, self.spp);
+            save_image(&img, &self.frame_path(frame))?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn frames() {
+        let mut seq = FrameSequence::new("./target/frame_{}.png", 4, 3, 24.0);
+        seq.set_spp(2);
+        assert_eq!(seq.frame_path(7), "./target/frame_0007.png");
+        assert_eq!(seq.frame_path(12345), "./target/frame_12345.png");
+        assert!((seq.frame_time(36) - 1.5).abs() < 1e-9);
+    }
+}
@@ -4,6 +4,7 @@
 //! An improved illumination model for shaded display.
 //! Acm Siggraph Computer Graphics, 13(2), 14.
 
+use arena::Arena;
 use entity::*;
 use light::*;
 use math::*;
@@ -17,66 +18,60 @@ pub struct WhittedRenderer {
 }
 
 impl Renderer for WhittedRenderer {
-    fn render(&self, r: Ray) -> Color3f {
-        self.render_d(r, 0)
+    fn render_in(&self, r: Ray, arena: &Arena) -> Color3f {
+        self.render_d(r, 0, arena)
     }
 
-    fn is_visible(&self, p1: Vec3f, p2: Vec3f) -> bool {
-        let d = p2 - p1;
-        let dis = d.magnitude() - 1e-6;
-        let r = Ray::new(p1, d);
-        !self.entities.iter().any(|ent|{
-            if let Some(i) = ent.has_inct(r.clone()) {
-                i.0 < dis
-            } else {
-                false
-            }
-        })
+    fn is_visible(&self, r: Ray) -> bool {
+        !self.entities.iter().any(|ent| ent.has_inct(r.clone()).is_some())
     }
 }
 
 impl WhittedRenderer {
-    fn render_d(&self, r: Ray, depth: u32) -> Color3f {
+    fn render_d(&self, r: Ray, depth: u32, arena: &Arena) -> Color3f {
         if depth > self.max_depth {
             return BLACK;
         }
 
-        let mut inct: Option<Intersection> = None;
-        for ent in &self.entities {
-            if let Some(i) = ent.inct(r.clone()) {
-                inct = match inct {
-                    None => Some(i),
-                    Some(v) => Some(v.nearer(i)),
-                }
-            }
-        }
+        let inct = nearest_inct(&self.entities, r.clone());
 
         if inct.is_none() {
-            return self.background;
+            return self.lights.iter().fold(self.background, |acc, l| acc + l.emit(&r));
         }
         let inct = inct.unwrap();
+        let material = inct.material.build(arena);
 
         // Direct illumination
         let mut direct_illu = BLACK;
         for light in &self.lights {
-            let sam = light.sample_to(1, inct.position);
-            if sam.is_empty() || !self.is_visible(sam[0].ray.p, inct.position) {
+            let sam = light.sample_to(1, inct.position, arena);
+            if sam.is_empty() {
                 continue;
             }
             let sam = &sam[0];
-            direct_illu += inct.material
+            let shadow_ray =
+                Ray::spawn_to(inct.position, inct.p_error, inct.geo_normal, sam.ray.p, r.time);
+            if !self.is_visible(shadow_ray) {
+                continue;
+            }
+            let pdf = light.pdf_to(sam.ray.clone(), inct.position);
+            if pdf <= 0.0 {
+                continue;
+            }
+            direct_illu += material
                 .f(-r.d, -sam.ray.d)
                 .mul_element_wise(sam.color)
-                * dot(-sam.ray.d, inct.normal);
+                * dot(-sam.ray.d, inct.normal).max(0.0)
+                / pdf;
         }
 
         // Indirect illumination
         let ref_dir = reflect_vec(inct.normal, -r.d);
-        let ref_ray = Ray::new(inct.position + inct.normal * 1e-6, ref_dir);
-        let indirect_illu = self.render_d(ref_ray, depth + 1)
-            .mul_element_wise(inct.material.f(-r.d, ref_dir));
+        let ref_ray = Ray::spawn(inct.position, inct.p_error, inct.geo_normal, ref_dir, r.time);
+        let indirect_illu = self.render_d(ref_ray, depth + 1, arena)
+            .mul_element_wise(material.f(-r.d, ref_dir));
 
-        direct_illu + indirect_illu + inct.material.ambient()
+        direct_illu + indirect_illu + material.ambient()
     }
 
     pub fn new(